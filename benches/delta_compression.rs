@@ -0,0 +1,146 @@
+//! Bytes saved by delta compression on a recorded session
+//!
+//! Replays a session through the pipeline with and without delta compression
+//! and reports the output size and processing time of each:
+//!
+//! ```bash
+//! cargo bench --bench delta_compression -- recording.zip.acmi
+//! ```
+//!
+//! Recordings may be `.zip.acmi` files written by the bridge or plain text
+//! ACMI. Without a path, a session in the addon's format is generated: 24
+//! aircraft, 8 ships and 4 static objects updated 10 times per second for 10
+//! minutes, with every property resent on each tick.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use stormworks_tacview::acmi::read_recording;
+use stormworks_tacview::pipeline::Pipeline;
+use stormworks_tacview::AppConfig;
+
+fn main() -> Result<()> {
+    let path = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    let (name, session) = match path {
+        Some(path) => (path.clone(), read_recording(Path::new(&path))?),
+        None => ("generated session".to_string(), generate_session()),
+    };
+    let batches = split_batches(&session);
+    println!(
+        "{}: {} batches, {} bytes",
+        name,
+        batches.len(),
+        session.len()
+    );
+
+    let raw = replay(&batches, &AppConfig::default());
+    let delta = replay(
+        &batches,
+        &AppConfig {
+            delta_compression: true,
+            ..AppConfig::default()
+        },
+    );
+
+    for (label, (bytes, elapsed)) in [("raw", raw), ("delta", delta)] {
+        println!(
+            "{:>6}: {:>12} bytes {:>6.1}% {:>10.1?}",
+            label,
+            bytes,
+            bytes as f64 * 100.0 / raw.0 as f64,
+            elapsed
+        );
+    }
+    println!(
+        "saved: {} bytes ({:.1}%)",
+        raw.0 - delta.0,
+        (raw.0 - delta.0) as f64 * 100.0 / raw.0 as f64
+    );
+    Ok(())
+}
+
+/// Split a session into the batches Stormworks would send, one per time frame
+fn split_batches(session: &str) -> Vec<(f64, String)> {
+    let mut batches: Vec<(f64, String)> = vec![(0.0, String::new())];
+    for line in session.lines() {
+        if let Some(time) = line.strip_prefix('#').and_then(|t| t.parse().ok()) {
+            batches.push((time, String::new()));
+        }
+        let batch = &mut batches.last_mut().unwrap().1;
+        batch.push_str(line);
+        batch.push('\n');
+    }
+    batches.retain(|(_, batch)| !batch.is_empty());
+    batches
+}
+
+/// Run every batch through a pipeline, returning the bytes written and the time taken
+fn replay(batches: &[(f64, String)], config: &AppConfig) -> (usize, Duration) {
+    let mut pipeline = Pipeline::from_config(config);
+    let start = Instant::now();
+    let mut bytes = 0;
+
+    for (time, batch) in batches {
+        // Batches arrive at their recorded time, so keyframes are spaced as in a live session
+        let now = start + Duration::from_secs_f64(time.max(0.0));
+        bytes += pipeline.process(batch, now).len();
+    }
+    (bytes, start.elapsed())
+}
+
+/// Generate a session in the format sent by the Stormworks addon
+fn generate_session() -> String {
+    const TICK: f64 = 0.1;
+    const TICKS: usize = 6000;
+
+    let mut session = String::from(
+        "FileType=text/acmi/tacview\nFileVersion=2.2\n\
+         0,ReferenceTime=2024-01-01T00:00:00Z\n",
+    );
+    for tick in 0..TICKS {
+        let t = tick as f64 * TICK;
+        session.push_str(&format!("#{t:.2}\n"));
+
+        for i in 0..24 {
+            let heading = (i as f64 * 15.0 + t * 3.0) % 360.0;
+            let radius = 0.02 + i as f64 * 0.001;
+            let angle = heading.to_radians();
+            session.push_str(&format!(
+                "{:x},T={:.7}|{:.7}|{:.1}|{:.1}|{:.1}|{:.1},Name=F-{},Type=Air+FixedWing,\
+                 Coalition=Allies,Color=Blue,Pilot=Player {},Group=Flight {}\n",
+                0x100 + i,
+                radius * angle.sin(),
+                radius * angle.cos(),
+                800.0 + i as f64 * 50.0 + (t * 0.2).sin() * 30.0,
+                -20.0,
+                (t * 0.2).cos() * 3.0,
+                heading,
+                i,
+                i,
+                i / 4,
+            ));
+        }
+        for i in 0..8 {
+            session.push_str(&format!(
+                "{:x},T={:.7}|{:.7}|0.0|0.0|0.0|90.0,Name=Ship {},Type=Sea+Watercraft,\
+                 Coalition=Allies,Color=Blue\n",
+                0x200 + i,
+                0.05 + t * 0.00001,
+                i as f64 * 0.01,
+                i,
+            ));
+        }
+        for i in 0..4 {
+            session.push_str(&format!(
+                "{:x},T={:.7}|{:.7}|10.0,Name=Base {},Type=Ground+Static+Building,\
+                 Coalition=Allies,Color=Blue\n",
+                0x300 + i,
+                i as f64 * 0.1,
+                -0.1,
+                i,
+            ));
+        }
+    }
+    session
+}
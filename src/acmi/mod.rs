@@ -0,0 +1,18 @@
+//! ACMI format model
+//!
+//! This module parses Tacview ACMI text into records and tracks the state
+//! of the objects they describe, so that features beyond plain forwarding
+//! can work with structured data.
+
+pub mod parser;
+pub mod recording;
+pub mod state;
+pub mod validator;
+
+pub use parser::{
+    escape_value, logical_lines, parse_object_id, parse_records, AcmiRecord, ObjectUpdate,
+    ParseError, Transform, GLOBAL_OBJECT_ID,
+};
+pub use recording::{read_recording, write_recording};
+pub use state::{ObjectState, WorldState};
+pub use validator::{validate, Validator, Violation, ViolationKind};
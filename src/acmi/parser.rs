@@ -0,0 +1,508 @@
+use std::fmt;
+
+/// Object ID used by ACMI for global properties and events
+pub const GLOBAL_OBJECT_ID: u64 = 0;
+
+/// Error returned when an ACMI line cannot be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    message: String,
+}
+
+impl ParseError {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A single logical line of an ACMI stream
+#[derive(Debug, Clone, PartialEq)]
+pub enum AcmiRecord {
+    /// File header such as `FileType=text/acmi/tacview`
+    Header { key: String, value: String },
+    /// Time frame marker `#<seconds>`, relative to ReferenceTime
+    TimeFrame(f64),
+    /// Property update for an object, or for the global object (ID 0)
+    Update(ObjectUpdate),
+    /// Object removal `-<id>`
+    Removal(u64),
+    /// Comment line starting with `//`
+    Comment(String),
+}
+
+/// Property update for a single object
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectUpdate {
+    pub id: u64,
+    /// Properties in the order they appeared, with values unescaped
+    pub properties: Vec<(String, String)>,
+}
+
+impl ObjectUpdate {
+    /// Create an update with no properties
+    pub fn new(id: u64) -> Self {
+        Self {
+            id,
+            properties: Vec::new(),
+        }
+    }
+
+    /// Append a property to the update
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.properties.push((key.into(), value.into()));
+        self
+    }
+
+    /// Get the last value given for a property
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Parse the `T=` property, if present
+    pub fn transform(&self) -> Option<Result<Transform, ParseError>> {
+        self.property("T").map(Transform::parse)
+    }
+
+    /// Create a global `Event` update such as `Event=Message|1a|Hello`
+    ///
+    /// Object IDs are listed between the event type and the text. The text
+    /// is escaped when the update is written.
+    pub fn event(kind: &str, objects: &[u64], text: &str) -> Self {
+        let mut value = kind.to_string();
+        for id in objects {
+            value.push_str(&format!("|{id:x}"));
+        }
+        value.push('|');
+        value.push_str(text);
+        Self::new(GLOBAL_OBJECT_ID).with("Event", value)
+    }
+
+    /// Check whether this is an update of the global object
+    pub fn is_global(&self) -> bool {
+        self.id == GLOBAL_OBJECT_ID
+    }
+}
+
+impl AcmiRecord {
+    /// Parse a single logical line
+    ///
+    /// Continuation lines must already have been joined, see [`logical_lines`].
+    pub fn parse(line: &str) -> Result<Self, ParseError> {
+        let line = line.trim_end_matches(['\r', '\n']);
+
+        if let Some(comment) = line.strip_prefix("//") {
+            return Ok(Self::Comment(comment.to_string()));
+        }
+
+        if let Some(time) = line.strip_prefix('#') {
+            return time
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|t| t.is_finite())
+                .map(Self::TimeFrame)
+                .ok_or_else(|| ParseError::new(format!("invalid time frame: {line:?}")));
+        }
+
+        if let Some(id) = line.strip_prefix('-') {
+            return parse_object_id(id.trim()).map(Self::Removal);
+        }
+
+        let fields = split_unescaped(line, ',');
+        if fields.len() == 1 {
+            return match line.split_once('=') {
+                Some((key, value)) if !key.is_empty() => Ok(Self::Header {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                }),
+                _ => Err(ParseError::new(format!("unrecognized line: {line:?}"))),
+            };
+        }
+
+        let id = parse_object_id(fields[0])?;
+        let mut update = ObjectUpdate::new(id);
+
+        for field in &fields[1..] {
+            if field.is_empty() {
+                continue;
+            }
+
+            match field.split_once('=') {
+                Some((key, value)) if !key.is_empty() => {
+                    update
+                        .properties
+                        .push((key.to_string(), unescape_value(value)));
+                }
+                _ => {
+                    return Err(ParseError::new(format!(
+                        "property without key=value form in object {:x}: {field:?}",
+                        id
+                    )))
+                }
+            }
+        }
+
+        Ok(Self::Update(update))
+    }
+}
+
+impl fmt::Display for AcmiRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Header { key, value } => write!(f, "{key}={value}"),
+            Self::TimeFrame(time) => write!(f, "#{time}"),
+            Self::Update(update) => {
+                write!(f, "{:x}", update.id)?;
+                for (key, value) in &update.properties {
+                    write!(f, ",{key}={}", escape_value(value))?;
+                }
+                Ok(())
+            }
+            Self::Removal(id) => write!(f, "-{id:x}"),
+            Self::Comment(comment) => write!(f, "//{comment}"),
+        }
+    }
+}
+
+/// Object position and orientation from the `T=` property
+///
+/// Components omitted in the ACMI (empty between `|` separators) are `None`,
+/// meaning "unchanged since the previous update". Longitude and latitude are
+/// offsets from the global ReferenceLongitude/ReferenceLatitude.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Transform {
+    pub longitude: Option<f64>,
+    pub latitude: Option<f64>,
+    pub altitude: Option<f64>,
+    pub roll: Option<f64>,
+    pub pitch: Option<f64>,
+    pub yaw: Option<f64>,
+    pub u: Option<f64>,
+    pub v: Option<f64>,
+    pub heading: Option<f64>,
+}
+
+impl Transform {
+    /// Parse a `T=` value with 3, 5, 6 or 9 components
+    pub fn parse(value: &str) -> Result<Self, ParseError> {
+        let mut components = Vec::with_capacity(9);
+        for part in value.split('|') {
+            let part = part.trim();
+            if part.is_empty() {
+                components.push(None);
+            } else {
+                let number = part.parse::<f64>().ok().filter(|n| n.is_finite());
+                match number {
+                    Some(n) => components.push(Some(n)),
+                    None => {
+                        return Err(ParseError::new(format!(
+                            "non-numeric coordinate {part:?} in T={value}"
+                        )))
+                    }
+                }
+            }
+        }
+
+        let c = |i: usize| components[i];
+        let transform = match components.len() {
+            3 => Self {
+                longitude: c(0),
+                latitude: c(1),
+                altitude: c(2),
+                ..Self::default()
+            },
+            5 => Self {
+                longitude: c(0),
+                latitude: c(1),
+                altitude: c(2),
+                u: c(3),
+                v: c(4),
+                ..Self::default()
+            },
+            6 => Self {
+                longitude: c(0),
+                latitude: c(1),
+                altitude: c(2),
+                roll: c(3),
+                pitch: c(4),
+                yaw: c(5),
+                ..Self::default()
+            },
+            9 => Self {
+                longitude: c(0),
+                latitude: c(1),
+                altitude: c(2),
+                roll: c(3),
+                pitch: c(4),
+                yaw: c(5),
+                u: c(6),
+                v: c(7),
+                heading: c(8),
+            },
+            n => {
+                return Err(ParseError::new(format!(
+                    "T= must have 3, 5, 6 or 9 components, found {n}: T={value}"
+                )))
+            }
+        };
+
+        Ok(transform)
+    }
+
+    /// Overwrite the components that are present in `other`
+    pub fn merge(&mut self, other: &Transform) {
+        fn take(dst: &mut Option<f64>, src: Option<f64>) {
+            if src.is_some() {
+                *dst = src;
+            }
+        }
+
+        take(&mut self.longitude, other.longitude);
+        take(&mut self.latitude, other.latitude);
+        take(&mut self.altitude, other.altitude);
+        take(&mut self.roll, other.roll);
+        take(&mut self.pitch, other.pitch);
+        take(&mut self.yaw, other.yaw);
+        take(&mut self.u, other.u);
+        take(&mut self.v, other.v);
+        take(&mut self.heading, other.heading);
+    }
+
+    /// Heading in degrees, preferring the explicit heading over yaw
+    pub fn heading_or_yaw(&self) -> Option<f64> {
+        self.heading.or(self.yaw)
+    }
+
+    /// Format as a `T=` value using the shortest component layout that
+    /// holds every present component
+    pub fn to_acmi(&self) -> String {
+        let has_orientation = self.roll.is_some() || self.pitch.is_some() || self.yaw.is_some();
+        let has_flat = self.u.is_some() || self.v.is_some();
+
+        let components: Vec<Option<f64>> =
+            if self.heading.is_some() || (has_orientation && has_flat) {
+                vec![
+                    self.longitude,
+                    self.latitude,
+                    self.altitude,
+                    self.roll,
+                    self.pitch,
+                    self.yaw,
+                    self.u,
+                    self.v,
+                    self.heading,
+                ]
+            } else if has_orientation {
+                vec![
+                    self.longitude,
+                    self.latitude,
+                    self.altitude,
+                    self.roll,
+                    self.pitch,
+                    self.yaw,
+                ]
+            } else if has_flat {
+                vec![self.longitude, self.latitude, self.altitude, self.u, self.v]
+            } else {
+                vec![self.longitude, self.latitude, self.altitude]
+            };
+
+        components
+            .iter()
+            .map(|c| c.map(|n| n.to_string()).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join("|")
+    }
+}
+
+/// Split ACMI text into logical lines
+///
+/// A line ending with a backslash continues on the next line; the joined
+/// line keeps the escaped newline so property values can be unescaped later.
+/// Each line is returned with the 1-based line number it starts on. Empty
+/// lines are skipped, as is the byte order mark Tacview starts its files with.
+pub fn logical_lines(text: &str) -> Vec<(usize, String)> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+
+    for (index, raw) in text.lines().enumerate() {
+        let raw = raw.trim_end_matches('\r');
+        let (start, mut line) = match current.take() {
+            Some((start, mut line)) => {
+                line.push('\n');
+                line.push_str(raw);
+                (start, line)
+            }
+            None => (index + 1, raw.to_string()),
+        };
+
+        if ends_with_unescaped_backslash(&line) {
+            current = Some((start, line));
+            continue;
+        }
+
+        if !line.trim().is_empty() {
+            lines.push((start, std::mem::take(&mut line)));
+        }
+    }
+
+    if let Some((start, line)) = current {
+        lines.push((start, line));
+    }
+
+    lines
+}
+
+/// Parse all records in ACMI text, skipping lines that fail to parse
+pub fn parse_records(text: &str) -> Vec<AcmiRecord> {
+    logical_lines(text)
+        .iter()
+        .filter_map(|(_, line)| AcmiRecord::parse(line).ok())
+        .collect()
+}
+
+/// Escape a property value for writing to ACMI
+///
+/// Commas and line breaks must be preceded by a backslash.
+pub fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ',' => escaped.push_str("\\,"),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverse [`escape_value`]
+pub fn unescape_value(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.next() {
+                unescaped.push(next);
+                continue;
+            }
+        }
+        unescaped.push(c);
+    }
+    unescaped
+}
+
+/// Parse a hexadecimal object ID
+pub fn parse_object_id(id: &str) -> Result<u64, ParseError> {
+    u64::from_str_radix(id, 16).map_err(|_| ParseError::new(format!("invalid object ID: {id:?}")))
+}
+
+/// Split on a separator, ignoring separators preceded by a backslash
+pub(crate) fn split_unescaped(line: &str, separator: char) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == separator {
+            fields.push(&line[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    fields.push(&line[start..]);
+
+    fields
+}
+
+fn ends_with_unescaped_backslash(line: &str) -> bool {
+    let trailing = line.chars().rev().take_while(|&c| c == '\\').count();
+    trailing % 2 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_records() {
+        assert_eq!(
+            AcmiRecord::parse("FileType=text/acmi/tacview").unwrap(),
+            AcmiRecord::Header {
+                key: "FileType".to_string(),
+                value: "text/acmi/tacview".to_string()
+            }
+        );
+        assert_eq!(
+            AcmiRecord::parse("#12.5").unwrap(),
+            AcmiRecord::TimeFrame(12.5)
+        );
+        assert_eq!(AcmiRecord::parse("-1a").unwrap(), AcmiRecord::Removal(0x1a));
+
+        let record = AcmiRecord::parse("40000003,T=0|0|2000|0|0,Name=A\\,B").unwrap();
+        let AcmiRecord::Update(update) = &record else {
+            panic!("expected object update");
+        };
+        assert_eq!(update.id, 0x40000003);
+        assert_eq!(update.property("Name"), Some("A,B"));
+        assert_eq!(record.to_string(), "40000003,T=0|0|2000|0|0,Name=A\\,B");
+
+        assert!(AcmiRecord::parse("#abc").is_err());
+        assert!(AcmiRecord::parse("xyz,T=1|2|3").is_err());
+        assert!(AcmiRecord::parse("just text").is_err());
+    }
+
+    #[test]
+    fn test_transform() {
+        let t = Transform::parse("1.5|2||10|20|30").unwrap();
+        assert_eq!(t.longitude, Some(1.5));
+        assert_eq!(t.altitude, None);
+        assert_eq!(t.yaw, Some(30.0));
+        assert_eq!(t.to_acmi(), "1.5|2||10|20|30");
+
+        let mut base = Transform::parse("1|2|3").unwrap();
+        base.merge(&Transform::parse("|5|").unwrap());
+        assert_eq!(base.to_acmi(), "1|5|3");
+
+        assert!(Transform::parse("1|2").is_err());
+        assert!(Transform::parse("1|x|3").is_err());
+    }
+
+    #[test]
+    fn test_logical_lines_and_escaping() {
+        let text = "#1\n0,Event=Message|1|first\\\nsecond\n\n-1\n";
+        let lines = logical_lines(text);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1].0, 2);
+        assert_eq!(lines[2].0, 5);
+
+        let record = AcmiRecord::parse(&lines[1].1).unwrap();
+        let AcmiRecord::Update(update) = record else {
+            panic!("expected global update");
+        };
+        assert_eq!(update.property("Event"), Some("Message|1|first\nsecond"));
+
+        let value = "a,b\\c\nd";
+        assert_eq!(unescape_value(&escape_value(value)), value);
+    }
+}
@@ -0,0 +1,88 @@
+use anyhow::{Context, Result};
+use std::io::{Read, Write};
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// Magic bytes at the start of a zip archive
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Read the ACMI text of a recording
+///
+/// Accepts both the `.zip.acmi` files written by the bridge and plain text
+/// ACMI. Zipped recordings are recognized by their content, not their name,
+/// and the first `.acmi` entry of the archive is read.
+pub fn read_recording(path: &Path) -> Result<String> {
+    let bytes = std::fs::read(path).with_context(|| format!("Failed to read {path:?}"))?;
+
+    if !bytes.starts_with(ZIP_MAGIC) {
+        return String::from_utf8(bytes).with_context(|| format!("{path:?} is not UTF-8 text"));
+    }
+
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))
+        .with_context(|| format!("Failed to open ZIP file: {path:?}"))?;
+    let index = (0..archive.len())
+        .find(|&i| {
+            archive
+                .by_index(i)
+                .is_ok_and(|entry| entry.name().ends_with(".acmi"))
+        })
+        .unwrap_or(0);
+
+    let mut entry = archive
+        .by_index(index)
+        .with_context(|| format!("No ACMI file in ZIP file: {path:?}"))?;
+    let mut text = String::new();
+    entry
+        .read_to_string(&mut text)
+        .with_context(|| format!("Failed to read ACMI from ZIP file: {path:?}"))?;
+    Ok(text)
+}
+
+/// Write ACMI text as a zipped recording
+///
+/// The archive holds a single entry named after the file, with `.zip.acmi`
+/// replaced by `.txt.acmi`, as Tacview expects.
+pub fn write_recording(path: &Path, acmi: &[u8]) -> Result<()> {
+    let zip_file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create ZIP file: {path:?}"))?;
+
+    let mut zip = ZipWriter::new(zip_file);
+    let options = FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .unix_permissions(0o755);
+
+    let entry_name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("recording")
+        .replace(".zip", ".txt");
+    let entry_name = format!("{entry_name}.acmi");
+
+    zip.start_file(&entry_name, options)
+        .with_context(|| format!("Failed to start file in ZIP: {entry_name}"))?;
+    zip.write_all(acmi)
+        .with_context(|| format!("Failed to write ACMI content to ZIP: {path:?}"))?;
+    zip.finish()
+        .with_context(|| format!("Failed to finalize ZIP file: {path:?}"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_and_read_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Sortie.zip.acmi");
+        let acmi = "FileType=text/acmi/tacview\nFileVersion=2.2\n#0\n";
+
+        write_recording(&path, acmi.as_bytes()).unwrap();
+        assert_eq!(read_recording(&path).unwrap(), acmi);
+
+        let file = std::fs::File::open(&path).unwrap();
+        let mut archive = zip::ZipArchive::new(file).unwrap();
+        assert_eq!(archive.by_index(0).unwrap().name(), "Sortie.txt.acmi");
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::parser::{AcmiRecord, ObjectUpdate, Transform};
+
+/// Last known state of a single object
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectState {
+    pub id: u64,
+    /// All properties except `T`, with the latest value for each
+    pub properties: BTreeMap<String, String>,
+    /// Accumulated position and orientation
+    pub transform: Transform,
+    /// Time frame of the first update for this object
+    pub first_seen: f64,
+    /// Time frame of the latest update for this object
+    pub last_seen: f64,
+}
+
+impl ObjectState {
+    fn new(id: u64, time: f64) -> Self {
+        Self {
+            id,
+            properties: BTreeMap::new(),
+            transform: Transform::default(),
+            first_seen: time,
+            last_seen: time,
+        }
+    }
+
+    /// Get a property value
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(String::as_str)
+    }
+
+    /// The `Name` property
+    pub fn name(&self) -> Option<&str> {
+        self.property("Name")
+    }
+
+    /// The `Type` property, e.g. `Air+FixedWing`
+    pub fn object_type(&self) -> Option<&str> {
+        self.property("Type")
+    }
+
+    /// The `Coalition` property
+    pub fn coalition(&self) -> Option<&str> {
+        self.property("Coalition")
+    }
+
+    /// Check whether the object's `Type` contains the given tag
+    pub fn has_type_tag(&self, tag: &str) -> bool {
+        self.object_type()
+            .is_some_and(|t| t.split('+').any(|part| part.eq_ignore_ascii_case(tag)))
+    }
+
+    /// An update with the full state of the object, `T` first
+    pub fn to_update(&self) -> ObjectUpdate {
+        let mut update = ObjectUpdate::new(self.id).with("T", self.transform.to_acmi());
+        for (key, value) in &self.properties {
+            update.properties.push((key.clone(), value.clone()));
+        }
+        update
+    }
+}
+
+/// State of the whole ACMI scene built up from a stream of records
+///
+/// Global properties of interest (ReferenceTime, ReferenceLongitude and
+/// ReferenceLatitude) are tracked alongside per-object state. Events are
+/// not stored since they only apply to the frame they appear in.
+#[derive(Debug, Clone, Default)]
+pub struct WorldState {
+    /// Current time frame in seconds
+    pub time: f64,
+    /// Global properties (object ID 0), excluding events
+    pub global: BTreeMap<String, String>,
+    /// File headers such as FileType and FileVersion
+    pub headers: BTreeMap<String, String>,
+    objects: HashMap<u64, ObjectState>,
+}
+
+impl WorldState {
+    /// Create an empty world state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a single record to the state
+    pub fn apply(&mut self, record: &AcmiRecord) {
+        match record {
+            AcmiRecord::Header { key, value } => {
+                self.headers.insert(key.clone(), value.clone());
+            }
+            AcmiRecord::TimeFrame(time) => self.time = *time,
+            AcmiRecord::Update(update) if update.is_global() => {
+                for (key, value) in &update.properties {
+                    if key != "Event" {
+                        self.global.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            AcmiRecord::Update(update) => self.apply_update(update),
+            AcmiRecord::Removal(id) => {
+                self.objects.remove(id);
+            }
+            AcmiRecord::Comment(_) => {}
+        }
+    }
+
+    fn apply_update(&mut self, update: &ObjectUpdate) {
+        let time = self.time;
+        let object = self
+            .objects
+            .entry(update.id)
+            .or_insert_with(|| ObjectState::new(update.id, time));
+        object.last_seen = time;
+
+        for (key, value) in &update.properties {
+            if key == "T" {
+                if let Ok(transform) = Transform::parse(value) {
+                    object.transform.merge(&transform);
+                }
+            } else {
+                object.properties.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    /// Get an object by ID
+    pub fn object(&self, id: u64) -> Option<&ObjectState> {
+        self.objects.get(&id)
+    }
+
+    /// Iterate over all live objects in arbitrary order
+    pub fn objects(&self) -> impl Iterator<Item = &ObjectState> {
+        self.objects.values()
+    }
+
+    /// Number of live objects
+    pub fn object_count(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Remove an object without a removal record
+    pub fn remove(&mut self, id: u64) -> Option<ObjectState> {
+        self.objects.remove(&id)
+    }
+
+    /// Global ReferenceLongitude, defaulting to 0
+    pub fn reference_longitude(&self) -> f64 {
+        self.global_number("ReferenceLongitude")
+    }
+
+    /// Global ReferenceLatitude, defaulting to 0
+    pub fn reference_latitude(&self) -> f64 {
+        self.global_number("ReferenceLatitude")
+    }
+
+    /// Absolute longitude, latitude and altitude of an object
+    ///
+    /// Returns `None` until the object has reported all three components.
+    pub fn absolute_position(&self, object: &ObjectState) -> Option<(f64, f64, f64)> {
+        let t = &object.transform;
+        Some((
+            t.longitude? + self.reference_longitude(),
+            t.latitude? + self.reference_latitude(),
+            t.altitude?,
+        ))
+    }
+
+    fn global_number(&self, key: &str) -> f64 {
+        self.global
+            .get(key)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acmi::parser::parse_records;
+
+    #[test]
+    fn test_world_state_tracks_objects() {
+        let mut world = WorldState::new();
+        let text = "FileType=text/acmi/tacview\n\
+            0,ReferenceLongitude=180\n\
+            0,ReferenceLatitude=0\n\
+            #1\n\
+            101,T=0.5|0.25|100,Name=Heli,Type=Air+Rotorcraft\n\
+            #2\n\
+            101,T=|0.5|\n\
+            0,Event=Message|101|hello\n\
+            102,T=1|1|1\n\
+            -102\n";
+
+        for record in parse_records(text) {
+            world.apply(&record);
+        }
+
+        assert_eq!(world.time, 2.0);
+        assert_eq!(world.object_count(), 1);
+        assert!(!world.global.contains_key("Event"));
+
+        let heli = world.object(0x101).unwrap();
+        assert_eq!(heli.first_seen, 1.0);
+        assert_eq!(heli.last_seen, 2.0);
+        assert!(heli.has_type_tag("rotorcraft"));
+        assert_eq!(world.absolute_position(heli), Some((180.5, 0.5, 100.0)));
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use super::parser::{logical_lines, parse_object_id, split_unescaped, AcmiRecord, Transform};
+
+/// Kind of ACMI conformance problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ViolationKind {
+    /// The line is not valid ACMI at all
+    Syntax,
+    /// A property without `=`, usually the rest of a value with an unescaped comma
+    UnescapedComma,
+    /// A `T=` value with non-numeric components or the wrong number of them
+    InvalidTransform,
+    /// A file header such as `FileType` given more than once
+    DuplicateHeader,
+    /// A time frame earlier than the one before it
+    TimeBackwards,
+}
+
+impl ViolationKind {
+    /// Name used in logs and metrics labels
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Syntax => "syntax",
+            Self::UnescapedComma => "unescaped_comma",
+            Self::InvalidTransform => "invalid_transform",
+            Self::DuplicateHeader => "duplicate_header",
+            Self::TimeBackwards => "time_backwards",
+        }
+    }
+}
+
+/// A problem found on a line of ACMI
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// 1-based line number the logical line starts on
+    pub line: usize,
+    pub kind: ViolationKind,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Checks ACMI for problems that make Tacview drop objects without a warning
+///
+/// The validator keeps state between calls to [`Validator::check`], so a
+/// stream can be checked one frame at a time and still have duplicate
+/// headers and backwards time frames detected across frames.
+#[derive(Debug, Default)]
+pub struct Validator {
+    headers: HashSet<String>,
+    last_time: Option<f64>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check a piece of ACMI text, with line numbers relative to its start
+    pub fn check(&mut self, text: &str) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for (line_number, line) in logical_lines(text) {
+            if let Some((kind, message)) = self.check_line(&line) {
+                violations.push(Violation {
+                    line: line_number,
+                    kind,
+                    message,
+                });
+            }
+        }
+        violations
+    }
+
+    fn check_line(&mut self, line: &str) -> Option<(ViolationKind, String)> {
+        let record = match AcmiRecord::parse(line) {
+            Ok(record) => record,
+            Err(e) => return Some(classify_parse_error(line, e.to_string())),
+        };
+
+        match record {
+            AcmiRecord::Header { key, .. } => (!self.headers.insert(key.clone())).then(|| {
+                (
+                    ViolationKind::DuplicateHeader,
+                    format!("duplicate header {key}"),
+                )
+            }),
+            AcmiRecord::TimeFrame(time) => {
+                let last = self.last_time.replace(time);
+                last.filter(|last| time < *last).map(|last| {
+                    (
+                        ViolationKind::TimeBackwards,
+                        format!("time frame #{time} is earlier than the previous #{last}"),
+                    )
+                })
+            }
+            AcmiRecord::Update(update) => {
+                let value = update.property("T")?;
+                Transform::parse(value).err().map(|e| {
+                    (
+                        ViolationKind::InvalidTransform,
+                        format!("object {:x}: {e}", update.id),
+                    )
+                })
+            }
+            AcmiRecord::Removal(_) | AcmiRecord::Comment(_) => None,
+        }
+    }
+}
+
+/// Tell unescaped commas apart from other syntax errors
+fn classify_parse_error(line: &str, message: String) -> (ViolationKind, String) {
+    let fields = split_unescaped(line, ',');
+    let is_object = fields.len() > 1 && parse_object_id(fields[0]).is_ok();
+
+    match fields.iter().skip(1).find(|field| !field.contains('=')) {
+        Some(field) if is_object && !field.is_empty() => (
+            ViolationKind::UnescapedComma,
+            format!("{field:?} is not a property, escape commas in values as \\,"),
+        ),
+        _ => (ViolationKind::Syntax, message),
+    }
+}
+
+/// Check a complete ACMI file
+pub fn validate(text: &str) -> Vec<Violation> {
+    Validator::new().check(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_reports_line_numbers() {
+        let acmi = "FileType=text/acmi/tacview\n\
+            FileVersion=2.2\n\
+            #1\n\
+            101,T=1|2|300,Name=Gear up\\, flaps down\n\
+            102,T=1|2|abc\n\
+            103,T=1|2|3|4\n\
+            104,Name=Gear up, flaps down\n\
+            FileType=text/acmi/tacview\n\
+            #0.5\n\
+            ???\n";
+
+        let violations = validate(acmi);
+        let found: Vec<_> = violations.iter().map(|v| (v.line, v.kind)).collect();
+        assert_eq!(
+            found,
+            [
+                (5, ViolationKind::InvalidTransform),
+                (6, ViolationKind::InvalidTransform),
+                (7, ViolationKind::UnescapedComma),
+                (8, ViolationKind::DuplicateHeader),
+                (9, ViolationKind::TimeBackwards),
+                (10, ViolationKind::Syntax),
+            ]
+        );
+        assert_eq!(
+            violations[2].to_string(),
+            "line 7: \" flaps down\" is not a property, escape commas in values as \\,"
+        );
+
+        // State carries over between frames of a stream
+        let mut validator = Validator::new();
+        assert!(validator.check("#1\n101,T=1|2|3\n").is_empty());
+        assert_eq!(
+            validator.check("#0.9\n")[0].kind,
+            ViolationKind::TimeBackwards
+        );
+    }
+}
@@ -1,68 +1,847 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use tracing::{info, warn};
 
+/// Prefix of environment variables that override configuration options
+pub const ENV_PREFIX: &str = "STORMWORKS_TACVIEW_";
+
+/// Highest dead reckoning output rate in Hz, bounded by the pipeline tick rate
+pub const MAX_DEAD_RECKONING_RATE: f64 = 50.0;
+
+/// Outputs whose update rate can be limited in `output_rates`
+pub const OUTPUT_KINDS: [&str; 5] = ["file", "tacview", "stream", "websocket", "csv"];
+
+/// Default of the `filename_template` option
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "Stormworks-{timestamp}";
+
+/// Placeholders accepted in the `filename_template` option
+const FILENAME_PLACEHOLDERS: [&str; 3] = ["timestamp", "date", "time"];
+
+/// Options whose values are not shown in logs or `config show`
+const SECRET_KEYS: [&str; 1] = ["admin_token"];
+
+/// Accepted values of the `log_level` option
+const LOG_LEVELS: [&str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+
 /// Application configuration structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Every option can be set in the YAML file, overridden by an environment
+/// variable (`STORMWORKS_TACVIEW_` followed by the upper-case option name)
+/// and finally by a command line flag.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppConfig {
     /// Output directory for ACMI files
     pub output_dir: PathBuf,
+    /// Name of recordings without extension, see [`AppConfig::recording_name`]
+    pub filename_template: String,
+    /// Number of recordings kept in the output directory, all when 0
+    pub retention_max_files: usize,
+    /// Days recordings are kept in the output directory, forever when 0
+    pub retention_days: f64,
+    /// Objects with all tags of any of these types are left out of recordings
+    pub exclude_types: Vec<String>,
+    /// Address the HTTP server binds to
+    pub http_bind: IpAddr,
+    /// HTTP server port
+    pub http_port: u16,
+    /// Address the Tacview TCP server binds to
+    pub tcp_bind: IpAddr,
+    /// Tacview TCP server port
+    pub tcp_port: u16,
+    /// Address the UDP ingest server binds to
+    pub udp_bind: IpAddr,
+    /// UDP ingest port, disabled when unset
+    pub udp_port: Option<u16>,
+    /// Log level for the bridge (error, warn, info, debug, trace)
+    pub log_level: String,
+    /// WebSocket live feed update rate in Hz
+    pub ws_rate: f64,
+    /// Seconds without frames while recording before health checks fail
+    pub health_window_secs: u64,
+    /// Seconds without updates before an object is removed, disabled when 0
+    pub object_timeout_secs: f64,
+    /// Derive TakenOff, Landed and Destroyed events from object updates
+    pub flight_events: bool,
+    /// Flight event thresholds by Type tags, e.g. `Air+Rotorcraft`
+    pub flight_event_thresholds: BTreeMap<String, FlightThresholds>,
+    /// How the time frames written to the outputs are assigned
+    pub time_mode: TimeMode,
+    /// Rate in Hz of dead-reckoned positions sent to live clients, disabled when 0
+    pub dead_reckoning_rate: f64,
+    /// Longest time in seconds to extrapolate an object without updates
+    pub dead_reckoning_max_secs: f64,
+    /// Strip object properties that did not change since they were last sent
+    pub delta_compression: bool,
+    /// Seconds between full keyframes when delta compression is enabled
+    pub delta_keyframe_secs: f64,
+    /// Maximum update rate in Hz by kind of output, unlimited when absent or 0
+    pub output_rates: BTreeMap<String, f64>,
+    /// Check frames from Stormworks for ACMI violations and log them
+    pub validate_ingest: bool,
+    /// Write object updates to CSV alongside each recording
+    pub csv_export: bool,
+    /// Columns of the CSV export, the default columns and numeric properties when empty
+    pub csv_columns: Vec<String>,
+    /// Write one CSV file per object instead of a single file
+    pub csv_per_object: bool,
+    /// Seconds to wait for requests and outputs to finish when shutting down
+    pub shutdown_timeout_secs: f64,
+    /// Token allowing admin requests from other hosts, loopback only when unset
+    pub admin_token: Option<String>,
+}
+
+/// How the time frames (`#t` lines) written to the outputs are assigned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum TimeMode {
+    /// Forward the addon's time frames unchanged
+    #[default]
+    Addon,
+    /// Keep the addon's time frames but never let them go backwards
+    Monotonic,
+    /// Stamp batches with the wall-clock time since the session started
+    WallClock,
+}
+
+/// Thresholds deciding whether an object is airborne
+///
+/// An object is airborne when both its height above ground and its speed are
+/// at least the minimum, and the state must hold for `settle_secs` before a
+/// takeoff or landing event is emitted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FlightThresholds {
+    /// Minimum height above ground in meters
+    pub min_agl: f64,
+    /// Minimum speed in meters per second
+    pub min_speed: f64,
+    /// Seconds a state change must last before it is reported
+    pub settle_secs: f64,
+}
+
+impl Default for FlightThresholds {
+    fn default() -> Self {
+        Self {
+            min_agl: 5.0,
+            min_speed: 20.0,
+            settle_secs: 2.0,
+        }
+    }
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             output_dir: get_default_output_dir(),
+            filename_template: DEFAULT_FILENAME_TEMPLATE.to_string(),
+            retention_max_files: 0,
+            retention_days: 0.0,
+            exclude_types: Vec::new(),
+            http_bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            http_port: 3000,
+            tcp_bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            tcp_port: 42674,
+            udp_bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            udp_port: None,
+            log_level: "info".to_string(),
+            ws_rate: 5.0,
+            health_window_secs: 30,
+            object_timeout_secs: 0.0,
+            flight_events: false,
+            flight_event_thresholds: BTreeMap::from([
+                ("Air".to_string(), FlightThresholds::default()),
+                (
+                    "Air+Rotorcraft".to_string(),
+                    FlightThresholds {
+                        min_agl: 1.5,
+                        min_speed: 0.0,
+                        ..FlightThresholds::default()
+                    },
+                ),
+            ]),
+            time_mode: TimeMode::Addon,
+            dead_reckoning_rate: 0.0,
+            dead_reckoning_max_secs: 1.0,
+            delta_compression: false,
+            delta_keyframe_secs: 10.0,
+            output_rates: BTreeMap::new(),
+            validate_ingest: false,
+            csv_export: false,
+            csv_columns: Vec::new(),
+            csv_per_object: false,
+            shutdown_timeout_secs: 10.0,
+            admin_token: None,
+        }
+    }
+}
+
+/// Command line overrides for configuration options
+///
+/// Options left unset keep the value from the lower layers.
+#[derive(Debug, Clone, Default, Serialize, clap::Args)]
+pub struct ConfigOverrides {
+    /// Output directory for ACMI files
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Recording file name without extension (default: Stormworks-{timestamp})
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename_template: Option<String>,
+
+    /// Number of recordings to keep, all when 0 (default: 0)
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention_max_files: Option<usize>,
+
+    /// Days to keep recordings, forever when 0 (default: 0)
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention_days: Option<f64>,
+
+    /// Comma-separated object types left out of recordings (default: none)
+    #[arg(long, global = true, value_delimiter = ',')]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_types: Option<Vec<String>>,
+
+    /// HTTP server bind address (default: 127.0.0.1)
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_bind: Option<IpAddr>,
+
+    /// HTTP server port (default: 3000)
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_port: Option<u16>,
+
+    /// TCP server bind address (default: 0.0.0.0)
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tcp_bind: Option<IpAddr>,
+
+    /// TCP server port (default: 42674)
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tcp_port: Option<u16>,
+
+    /// UDP ingest bind address (default: 127.0.0.1)
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp_bind: Option<IpAddr>,
+
+    /// UDP ingest port (disabled by default)
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp_port: Option<u16>,
+
+    /// Log level: error, warn, info, debug or trace (default: info)
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+
+    /// WebSocket live feed update rate in Hz (default: 5)
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ws_rate: Option<f64>,
+
+    /// Seconds without frames while recording before /healthz fails (default: 30)
+    #[arg(long = "health-window", global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_window_secs: Option<u64>,
+
+    /// Seconds without updates before an object is removed (default: 0, disabled)
+    #[arg(long = "object-timeout", global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub object_timeout_secs: Option<f64>,
+
+    /// Derive takeoff, landing and destruction events (default: false)
+    #[arg(long, global = true, num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flight_events: Option<bool>,
+
+    /// How output time frames are assigned (default: addon)
+    #[arg(long, global = true, value_enum)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_mode: Option<TimeMode>,
+
+    /// Rate in Hz of dead-reckoned positions for live clients (default: 0, disabled)
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dead_reckoning_rate: Option<f64>,
+
+    /// Longest time in seconds to extrapolate an object (default: 1)
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dead_reckoning_max_secs: Option<f64>,
+
+    /// Only send object properties that changed (default: false)
+    #[arg(long, global = true, num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta_compression: Option<bool>,
+
+    /// Seconds between full keyframes with delta compression (default: 10)
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta_keyframe_secs: Option<f64>,
+
+    /// Log ACMI violations in frames from Stormworks (default: false)
+    #[arg(long, global = true, num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validate_ingest: Option<bool>,
+
+    /// Write object updates to CSV alongside recordings (default: false)
+    #[arg(long, global = true, num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub csv_export: Option<bool>,
+
+    /// Comma-separated columns of the CSV export (default: time to yaw and numeric properties)
+    #[arg(long, global = true, value_delimiter = ',')]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub csv_columns: Option<Vec<String>>,
+
+    /// Write one CSV file per object (default: false)
+    #[arg(long, global = true, num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub csv_per_object: Option<bool>,
+
+    /// Seconds to drain requests and flush outputs at shutdown (default: 10)
+    #[arg(long = "shutdown-timeout", global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shutdown_timeout_secs: Option<f64>,
+
+    /// Token allowing admin requests from other hosts (default: loopback only)
+    #[arg(long, global = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_token: Option<String>,
+}
+
+/// Where the effective value of a configuration option came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Built-in default
+    Default,
+    /// The YAML configuration file
+    File(PathBuf),
+    /// An environment variable
+    Environment(String),
+    /// A command line flag
+    CommandLine,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::File(path) => write!(f, "file {}", path.display()),
+            Self::Environment(var) => write!(f, "environment {var}"),
+            Self::CommandLine => write!(f, "command line"),
         }
     }
 }
 
+/// Kind of problem found while validating configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigErrorKind {
+    /// The configuration file could not be read
+    Io,
+    /// The file is not valid YAML
+    Syntax,
+    /// The file is valid YAML but not a mapping of options
+    NotAMapping,
+    /// An option name that the bridge does not know
+    UnknownKey,
+    /// A known option with a value of the wrong type or out of range
+    InvalidValue,
+}
+
+/// Problem found while validating configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub kind: ConfigErrorKind,
+    /// Where the offending value came from
+    pub source: ConfigSource,
+    /// Option the problem relates to, if any
+    pub key: Option<String>,
+    /// One-based line and column in the configuration file, if known
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.source, self.location) {
+            (ConfigSource::File(path), Some((line, column))) => {
+                write!(f, "{}:{line}:{column}: ", path.display())?
+            }
+            (ConfigSource::File(path), None) => write!(f, "{}: ", path.display())?,
+            (ConfigSource::Environment(var), _) => write!(f, "{var}: ")?,
+            (ConfigSource::CommandLine, _) => write!(f, "command line: ")?,
+            (ConfigSource::Default, _) => {}
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Effective configuration together with the source of every option
+#[derive(Debug, Clone)]
+pub struct LoadedConfig {
+    pub config: AppConfig,
+    /// Configuration file that was consulted, if one could be determined
+    pub path: Option<PathBuf>,
+    /// Source of each option, in declaration order
+    pub sources: Vec<(String, ConfigSource)>,
+    /// Problems found while loading; the affected values were ignored
+    pub errors: Vec<ConfigError>,
+}
+
+impl LoadedConfig {
+    /// Source of a single option
+    pub fn source(&self, key: &str) -> Option<&ConfigSource> {
+        self.sources
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, source)| source)
+    }
+
+    /// Render the effective configuration as YAML, annotating each option
+    /// with where its value came from
+    pub fn render(&self) -> String {
+        let values = to_mapping(&self.config);
+        let mut out = match &self.path {
+            Some(path) => format!("# Configuration file: {}\n", path.display()),
+            None => "# Configuration file: (unknown)\n".to_string(),
+        };
+
+        for (key, source) in &self.sources {
+            let value = values.get(key.as_str()).cloned().unwrap_or(Value::Null);
+            out.push_str(&format!(
+                "{key}: {}  # {source}\n",
+                shown_value(key, &value)
+            ));
+        }
+
+        out
+    }
+}
+
+/// A single option whose value differs between two configurations
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    pub key: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+impl ConfigChange {
+    /// Whether the change only takes effect after a restart
+    ///
+    /// Servers keep their sockets bound while the process runs, so bind
+    /// addresses and ports cannot be changed without dropping connections.
+    pub fn requires_restart(&self) -> bool {
+        matches!(
+            self.key.as_str(),
+            "http_bind" | "http_port" | "tcp_bind" | "tcp_port" | "udp_bind" | "udp_port"
+        )
+    }
+}
+
+impl fmt::Display for ConfigChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.key,
+            shown_value(&self.key, &self.old),
+            shown_value(&self.key, &self.new)
+        )
+    }
+}
+
 impl AppConfig {
     /// Load configuration from file or create default if not found
     pub fn load() -> Self {
-        match Self::load_from_file() {
-            Ok(config) => {
-                info!("Loaded configuration from file");
-                config
-            }
-            Err(e) => {
-                warn!("Failed to load configuration: {}", e);
-                let config = Self::default();
+        Self::load_layered(None, &ConfigOverrides::default()).config
+    }
+
+    /// Load configuration by layering defaults, the YAML file, environment
+    /// variables and command line overrides, in increasing priority
+    ///
+    /// When `path` is `None` the default configuration file location is
+    /// used, and a default file is created there if none exists. Layers that
+    /// fail to load are logged and skipped.
+    pub fn load_layered(path: Option<&Path>, overrides: &ConfigOverrides) -> LoadedConfig {
+        Self::load_layered_with_env(path, overrides, std::env::vars())
+    }
+
+    /// Like [`AppConfig::load_layered`], reading environment variables from
+    /// `env` instead of the process environment
+    pub fn load_layered_with_env(
+        path: Option<&Path>,
+        overrides: &ConfigOverrides,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> LoadedConfig {
+        let env: HashMap<String, String> = env.into_iter().collect();
+        let explicit_path = path.is_some();
+        let path = path
+            .map(Path::to_path_buf)
+            .or_else(|| get_config_file_path().ok());
+
+        let mut merged = to_mapping(&Self::default());
+        let mut sources: Vec<(String, ConfigSource)> = merged
+            .keys()
+            .filter_map(Value::as_str)
+            .map(|key| (key.to_string(), ConfigSource::Default))
+            .collect();
+
+        // Configuration file, skipping only the options that are invalid
+        let mut errors = Vec::new();
+        match &path {
+            Some(path) if path.exists() => {
+                let source = ConfigSource::File(path.clone());
+                let (layer, file_errors) = validate_file(path);
+                for error in &file_errors {
+                    warn!("Ignoring invalid configuration: {}", error);
+                }
+                errors.extend(file_errors);
 
-                // Try to create default config file
-                if let Err(e) = Self::create_default_config_file(&config) {
+                match apply_layer(&mut merged, &mut sources, layer, |_| source.clone()) {
+                    Ok(()) => info!("Loaded configuration from file"),
+                    Err(e) => warn!("Failed to load configuration: {:#}", e),
+                }
+            }
+            Some(path) if explicit_path => {
+                warn!("Configuration file not found: {:?}", path);
+                errors.push(ConfigError {
+                    kind: ConfigErrorKind::Io,
+                    source: ConfigSource::File(path.clone()),
+                    key: None,
+                    location: None,
+                    message: "configuration file not found".to_string(),
+                });
+            }
+            Some(path) => {
+                warn!("Configuration file not found: {:?}", path);
+                if let Err(e) = Self::create_default_config_file(path, &Self::default()) {
                     warn!("Failed to create default configuration file: {}", e);
                 }
+            }
+            None => warn!("Unable to determine config directory"),
+        }
+
+        // Environment variables, applied one at a time so a single bad value
+        // does not discard the others
+        let keys: Vec<String> = sources.iter().map(|(key, _)| key.clone()).collect();
+        for key in keys {
+            let var = format!("{ENV_PREFIX}{}", key.to_uppercase());
+            let Some(raw) = env.get(&var).cloned() else {
+                continue;
+            };
 
-                config
+            let value = serde_yaml::from_str::<Value>(&raw).unwrap_or(Value::String(raw));
+            let mut layer = Mapping::new();
+            layer.insert(Value::String(key.clone()), value);
+
+            let source = ConfigSource::Environment(var.clone());
+            if let Err(e) = apply_layer(&mut merged, &mut sources, layer, |_| source.clone()) {
+                warn!("Ignoring environment variable {}: {:#}", var, e);
+                errors.push(ConfigError {
+                    kind: ConfigErrorKind::InvalidValue,
+                    source,
+                    key: Some(key),
+                    location: None,
+                    message: format!("{e:#}"),
+                });
             }
         }
+
+        // Command line
+        let layer = to_mapping(overrides);
+        if let Err(e) = apply_layer(&mut merged, &mut sources, layer, |_| {
+            ConfigSource::CommandLine
+        }) {
+            warn!("Ignoring command line configuration: {:#}", e);
+            errors.push(ConfigError {
+                kind: ConfigErrorKind::InvalidValue,
+                source: ConfigSource::CommandLine,
+                key: None,
+                location: None,
+                message: format!("{e:#}"),
+            });
+        }
+
+        let config = serde_yaml::from_value(Value::Mapping(merged)).unwrap_or_else(|e| {
+            warn!("Failed to build configuration, using defaults: {}", e);
+            Self::default()
+        });
+
+        LoadedConfig {
+            config,
+            path,
+            sources,
+            errors,
+        }
     }
 
-    /// Load configuration from the config file
-    fn load_from_file() -> Result<Self> {
-        let config_path = get_config_file_path()?;
+    /// Reload the layered configuration after the file changed
+    ///
+    /// Unlike [`AppConfig::load_layered`], a file that cannot be read or
+    /// contains invalid values is an error rather than being skipped, so the
+    /// caller can keep the configuration currently in effect.
+    pub fn reload(path: &Path, overrides: &ConfigOverrides) -> Result<LoadedConfig> {
+        let (_, errors) = validate_file(path);
+        if let Some(error) = errors.into_iter().next() {
+            return Err(error.into());
+        }
+
+        Ok(Self::load_layered(Some(path), overrides))
+    }
+
+    /// Check a configuration file without loading it
+    ///
+    /// Returns every problem found, each pointing at the offending line and
+    /// column where possible. An empty list means the file is valid.
+    pub fn validate_file(path: &Path) -> Vec<ConfigError> {
+        validate_file(path).1
+    }
 
-        if !config_path.exists() {
-            return Err(anyhow::anyhow!(
-                "Configuration file not found: {config_path:?}"
+    /// Check that option values are within their allowed ranges
+    ///
+    /// Returns the offending option and a description for each problem.
+    pub fn check(&self) -> Vec<(&'static str, String)> {
+        let mut problems = Vec::new();
+
+        if !LOG_LEVELS.contains(&self.log_level.to_ascii_lowercase().as_str()) {
+            problems.push((
+                "log_level",
+                format!(
+                    "unknown log level `{}`, expected one of: {}",
+                    self.log_level,
+                    LOG_LEVELS.join(", ")
+                ),
+            ));
+        }
+        if !(self.ws_rate.is_finite() && self.ws_rate > 0.0) {
+            problems.push((
+                "ws_rate",
+                format!("ws_rate must be a positive number, got {}", self.ws_rate),
+            ));
+        }
+        if !(self.object_timeout_secs.is_finite() && self.object_timeout_secs >= 0.0) {
+            problems.push((
+                "object_timeout_secs",
+                format!(
+                    "object_timeout_secs must be 0 or a positive number, got {}",
+                    self.object_timeout_secs
+                ),
+            ));
+        }
+        if !(self.dead_reckoning_rate.is_finite()
+            && (0.0..=MAX_DEAD_RECKONING_RATE).contains(&self.dead_reckoning_rate))
+        {
+            problems.push((
+                "dead_reckoning_rate",
+                format!(
+                    "dead_reckoning_rate must be between 0 and {MAX_DEAD_RECKONING_RATE}, got {}",
+                    self.dead_reckoning_rate
+                ),
+            ));
+        }
+        if !(self.dead_reckoning_max_secs.is_finite() && self.dead_reckoning_max_secs > 0.0) {
+            problems.push((
+                "dead_reckoning_max_secs",
+                format!(
+                    "dead_reckoning_max_secs must be a positive number, got {}",
+                    self.dead_reckoning_max_secs
+                ),
+            ));
+        }
+        if !(self.delta_keyframe_secs.is_finite() && self.delta_keyframe_secs > 0.0) {
+            problems.push((
+                "delta_keyframe_secs",
+                format!(
+                    "delta_keyframe_secs must be a positive number, got {}",
+                    self.delta_keyframe_secs
+                ),
+            ));
+        }
+        for (kind, rate) in &self.output_rates {
+            if !OUTPUT_KINDS.contains(&kind.as_str()) {
+                problems.push((
+                    "output_rates",
+                    format!(
+                        "unknown output `{kind}`, expected one of: {}",
+                        OUTPUT_KINDS.join(", ")
+                    ),
+                ));
+            } else if !(rate.is_finite() && *rate >= 0.0) {
+                problems.push((
+                    "output_rates",
+                    format!("rate for `{kind}` must be 0 or a positive number, got {rate}"),
+                ));
+            }
+        }
+        if let Err(message) = check_filename_template(&self.filename_template) {
+            problems.push(("filename_template", message));
+        }
+        if !(self.retention_days.is_finite() && self.retention_days >= 0.0) {
+            problems.push((
+                "retention_days",
+                format!(
+                    "retention_days must be 0 or a positive number, got {}",
+                    self.retention_days
+                ),
+            ));
+        }
+        if self
+            .exclude_types
+            .iter()
+            .any(|types| types.split('+').any(|tag| tag.trim().is_empty()))
+        {
+            problems.push((
+                "exclude_types",
+                "exclude_types must not contain empty type tags".to_string(),
+            ));
+        }
+        if self
+            .csv_columns
+            .iter()
+            .any(|column| column.trim().is_empty())
+        {
+            problems.push((
+                "csv_columns",
+                "csv_columns must not contain empty names".to_string(),
+            ));
+        }
+        for (types, thresholds) in &self.flight_event_thresholds {
+            let values = [
+                thresholds.min_agl,
+                thresholds.min_speed,
+                thresholds.settle_secs,
+            ];
+            if values.iter().any(|v| !(v.is_finite() && *v >= 0.0)) {
+                problems.push((
+                    "flight_event_thresholds",
+                    format!("thresholds for `{types}` must be 0 or positive numbers"),
+                ));
+            }
+        }
+        if self
+            .admin_token
+            .as_ref()
+            .is_some_and(|token| token.trim().is_empty())
+        {
+            problems.push((
+                "admin_token",
+                "admin_token must not be empty, leave it unset to allow loopback only".to_string(),
+            ));
+        }
+        if !(self.shutdown_timeout_secs.is_finite() && self.shutdown_timeout_secs > 0.0) {
+            problems.push((
+                "shutdown_timeout_secs",
+                format!(
+                    "shutdown_timeout_secs must be a positive number, got {}",
+                    self.shutdown_timeout_secs
+                ),
+            ));
+        }
+        if self.health_window_secs == 0 {
+            problems.push((
+                "health_window_secs",
+                "health_window_secs must be at least 1".to_string(),
             ));
         }
 
-        let content = std::fs::read_to_string(&config_path)
-            .with_context(|| format!("Failed to read configuration file: {config_path:?}"))?;
+        problems
+    }
 
-        let config: AppConfig = serde_yaml::from_str(&content)
-            .with_context(|| format!("Failed to parse configuration file: {config_path:?}"))?;
+    /// Options whose values differ from `other`, in declaration order
+    pub fn diff(&self, other: &AppConfig) -> Vec<ConfigChange> {
+        let old = to_mapping(self);
+        let new = to_mapping(other);
 
-        Ok(config)
+        old.iter()
+            .filter_map(|(key, old_value)| {
+                let new_value = new.get(key).cloned().unwrap_or(Value::Null);
+                (*old_value != new_value).then(|| ConfigChange {
+                    key: key.as_str().unwrap_or_default().to_string(),
+                    old: old_value.clone(),
+                    new: new_value,
+                })
+            })
+            .collect()
     }
 
-    /// Create default configuration file
-    fn create_default_config_file(config: &AppConfig) -> Result<()> {
-        let config_path = get_config_file_path()?;
+    /// Address the HTTP server binds to
+    pub fn http_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.http_bind, self.http_port)
+    }
+
+    /// Address the Tacview TCP server binds to
+    pub fn tcp_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.tcp_bind, self.tcp_port)
+    }
+
+    /// Address the UDP ingest server binds to, if enabled
+    pub fn udp_addr(&self) -> Option<SocketAddr> {
+        self.udp_port
+            .map(|port| SocketAddr::new(self.udp_bind, port))
+    }
+
+    /// File name of a recording started at `time`, without extension
+    ///
+    /// `{timestamp}` in the template is replaced by the Unix time in seconds,
+    /// `{date}` by the local date (`2024-05-31`) and `{time}` by the local
+    /// time (`14-05-09`).
+    pub fn recording_name(&self, time: SystemTime) -> String {
+        let local: chrono::DateTime<chrono::Local> = time.into();
+        let timestamp = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.filename_template
+            .replace("{timestamp}", &timestamp.to_string())
+            .replace("{date}", &local.format("%Y-%m-%d").to_string())
+            .replace("{time}", &local.format("%H-%M-%S").to_string())
+    }
 
+    /// Whether objects of a `Type` are left out of recordings
+    pub fn is_excluded_type(&self, object_type: &str) -> bool {
+        self.exclude_types.iter().any(|types| {
+            types.split('+').all(|tag| {
+                object_type
+                    .split('+')
+                    .any(|part| part.eq_ignore_ascii_case(tag.trim()))
+            })
+        })
+    }
+
+    /// How long shutdown waits for requests and outputs to finish
+    pub fn shutdown_timeout(&self) -> std::time::Duration {
+        std::time::Duration::try_from_secs_f64(self.shutdown_timeout_secs)
+            .unwrap_or(std::time::Duration::from_secs(10))
+    }
+
+    /// Whether debug output is enabled by the configured log level
+    pub fn is_verbose(&self) -> bool {
+        matches!(
+            self.log_level.to_ascii_lowercase().as_str(),
+            "debug" | "trace"
+        )
+    }
+
+    /// Create default configuration file
+    fn create_default_config_file(config_path: &Path, config: &AppConfig) -> Result<()> {
         // Create config directory if it doesn't exist
         if let Some(parent) = config_path.parent() {
             std::fs::create_dir_all(parent)
@@ -77,7 +856,7 @@ impl AppConfig {
         let yaml_content = serde_yaml::to_string(config)
             .with_context(|| "Failed to serialize default configuration")?;
 
-        std::fs::write(&config_path, yaml_content).with_context(|| {
+        std::fs::write(config_path, yaml_content).with_context(|| {
             format!("Failed to write default configuration file: {config_path:?}")
         })?;
 
@@ -85,6 +864,11 @@ impl AppConfig {
         Ok(())
     }
 
+    /// Location of the configuration file
+    pub fn config_file_path() -> Option<PathBuf> {
+        get_config_file_path().ok()
+    }
+
     /// Ensure the output directory exists
     pub fn ensure_output_dir(&self) -> Result<()> {
         if !self.output_dir.exists() {
@@ -122,6 +906,59 @@ impl AppConfig {
     }
 }
 
+/// Polls the configuration file and reloads it when it changes
+///
+/// Environment variables and command line overrides are re-applied on every
+/// reload, so they keep priority over edits to the file.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    overrides: ConfigOverrides,
+    current: AppConfig,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    /// Watch `path`, starting from the configuration currently in effect
+    pub fn new(path: PathBuf, overrides: ConfigOverrides, current: AppConfig) -> Self {
+        let modified = modified_time(&path);
+        Self {
+            path,
+            overrides,
+            current,
+            modified,
+        }
+    }
+
+    /// Configuration currently in effect
+    pub fn current(&self) -> &AppConfig {
+        &self.current
+    }
+
+    /// Check the file for changes
+    ///
+    /// Returns the changed options when the file was modified and the new
+    /// configuration is valid. An invalid edit is returned as an error and the
+    /// current configuration stays in effect until the file changes again.
+    pub fn poll(&mut self) -> Result<Vec<ConfigChange>> {
+        let modified = modified_time(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return Ok(Vec::new());
+        }
+        self.modified = modified;
+
+        let loaded = AppConfig::reload(&self.path, &self.overrides)?;
+        let changes = self.current.diff(&loaded.config);
+        self.current = loaded.config;
+
+        Ok(changes)
+    }
+}
+
+/// Modification time of a file, if it exists
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
 /// Get the path to the configuration file
 fn get_config_file_path() -> Result<PathBuf> {
     let config_dir = dirs::config_dir()
@@ -130,6 +967,275 @@ fn get_config_file_path() -> Result<PathBuf> {
     Ok(config_dir.join("stormworks-tacview.yml"))
 }
 
+/// Like [`inline_value`], but hiding secrets so they stay out of logs
+fn shown_value(key: &str, value: &Value) -> String {
+    if SECRET_KEYS.contains(&key) && !value.is_null() {
+        "(hidden)".to_string()
+    } else {
+        inline_value(value)
+    }
+}
+
+/// Render a value on a single line
+///
+/// Mappings and sequences use the JSON flow style, which is also valid YAML.
+fn inline_value(value: &Value) -> String {
+    match value {
+        Value::Mapping(_) | Value::Sequence(_) => serde_json::to_string(value).unwrap_or_default(),
+        _ => serde_yaml::to_string(value)
+            .map(|s| s.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// Check that a file name template only uses known placeholders and makes a
+/// plain file name
+fn check_filename_template(template: &str) -> Result<(), String> {
+    if template.trim().is_empty() {
+        return Err("filename_template must not be empty".to_string());
+    }
+    if template.contains(['/', '\\']) {
+        return Err(format!(
+            "filename_template must be a file name without directories, got `{template}`"
+        ));
+    }
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}') else {
+            return Err(format!("unclosed `{{` in filename_template `{template}`"));
+        };
+        let name = &rest[open + 1..open + close];
+        if !FILENAME_PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "unknown placeholder `{{{name}}}` in filename_template, expected one of: {}",
+                FILENAME_PLACEHOLDERS
+                    .iter()
+                    .map(|p| format!("{{{p}}}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        rest = &rest[open + close + 1..];
+    }
+    Ok(())
+}
+
+/// Serialize a value into a YAML mapping of its options
+fn to_mapping<T: Serialize>(value: &T) -> Mapping {
+    match serde_yaml::to_value(value) {
+        Ok(Value::Mapping(mapping)) => mapping,
+        _ => Mapping::new(),
+    }
+}
+
+/// Overlay the known options from `layer` onto `merged`
+///
+/// The layer is only applied if the result is still a valid configuration.
+/// Options not present in the defaults are ignored.
+fn apply_layer(
+    merged: &mut Mapping,
+    sources: &mut [(String, ConfigSource)],
+    layer: Mapping,
+    source: impl Fn(&str) -> ConfigSource,
+) -> Result<()> {
+    let mut candidate = merged.clone();
+    let mut applied = Vec::new();
+
+    for (key, value) in layer {
+        let Some(key) = key.as_str().map(str::to_string) else {
+            continue;
+        };
+        if candidate.contains_key(key.as_str()) {
+            candidate.insert(Value::String(key.clone()), value);
+            applied.push(key);
+        }
+    }
+
+    let config = serde_yaml::from_value::<AppConfig>(Value::Mapping(candidate.clone()))
+        .context("Invalid configuration value")?;
+    if let Some((_, problem)) = config
+        .check()
+        .into_iter()
+        .find(|(key, _)| applied.iter().any(|k| k == key))
+    {
+        anyhow::bail!(problem);
+    }
+
+    *merged = candidate;
+    for key in applied {
+        if let Some(entry) = sources.iter_mut().find(|(k, _)| *k == key) {
+            entry.1 = source(&key);
+        }
+    }
+
+    Ok(())
+}
+
+/// Read and validate a configuration file
+///
+/// Returns the valid options and a list of problems with the rest.
+fn validate_file(path: &Path) -> (Mapping, Vec<ConfigError>) {
+    let source = ConfigSource::File(path.to_path_buf());
+    match std::fs::read_to_string(path) {
+        Ok(content) => validate_layer(&content, &source),
+        Err(e) => (
+            Mapping::new(),
+            vec![ConfigError {
+                kind: ConfigErrorKind::Io,
+                source,
+                key: None,
+                location: None,
+                message: format!("failed to read configuration file: {e}"),
+            }],
+        ),
+    }
+}
+
+/// Validate the options in a YAML document one at a time
+///
+/// Returns the options that are valid on their own and a list of problems
+/// with the rest, so a single typo does not discard the whole file.
+fn validate_layer(content: &str, source: &ConfigSource) -> (Mapping, Vec<ConfigError>) {
+    let error = |kind, key: Option<&str>, location, message| ConfigError {
+        kind,
+        source: source.clone(),
+        key: key.map(str::to_string),
+        location,
+        message,
+    };
+
+    let document: Value = match serde_yaml::from_str(content) {
+        Ok(document) => document,
+        Err(e) => {
+            let location = e.location().map(|l| (l.line(), l.column()));
+            let message = format!("invalid YAML: {e}");
+            return (
+                Mapping::new(),
+                vec![error(ConfigErrorKind::Syntax, None, location, message)],
+            );
+        }
+    };
+
+    let layer = match document {
+        Value::Mapping(mapping) => mapping,
+        Value::Null => Mapping::new(),
+        _ => {
+            let message = "expected a mapping of option names to values".to_string();
+            return (
+                Mapping::new(),
+                vec![error(
+                    ConfigErrorKind::NotAMapping,
+                    None,
+                    Some((1, 1)),
+                    message,
+                )],
+            );
+        }
+    };
+
+    let defaults = to_mapping(&AppConfig::default());
+    let known: Vec<&str> = defaults.keys().filter_map(Value::as_str).collect();
+    let mut valid = Mapping::new();
+    let mut errors = Vec::new();
+
+    for (key, value) in layer {
+        let Some(key) = key.as_str() else {
+            let message = format!("option names must be strings, got {key:?}");
+            errors.push(error(ConfigErrorKind::UnknownKey, None, None, message));
+            continue;
+        };
+        let location = key_location(content, key);
+
+        if !known.contains(&key) {
+            let mut message = format!("unknown option `{key}`");
+            if let Some(suggestion) = closest_key(key, &known) {
+                message.push_str(&format!(", did you mean `{suggestion}`?"));
+            }
+            errors.push(error(
+                ConfigErrorKind::UnknownKey,
+                Some(key),
+                location,
+                message,
+            ));
+            continue;
+        }
+
+        let mut candidate = defaults.clone();
+        candidate.insert(Value::String(key.to_string()), value.clone());
+        let problem = match serde_yaml::from_value::<AppConfig>(Value::Mapping(candidate)) {
+            Ok(config) => config
+                .check()
+                .into_iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, problem)| problem),
+            Err(e) => Some(format!("invalid value for `{key}`: {e}")),
+        };
+
+        match problem {
+            Some(message) => errors.push(error(
+                ConfigErrorKind::InvalidValue,
+                Some(key),
+                location.map(|(line, _)| (line, value_column(content, line))),
+                message,
+            )),
+            None => {
+                valid.insert(Value::String(key.to_string()), value);
+            }
+        }
+    }
+
+    (valid, errors)
+}
+
+/// One-based line and column of a top-level option in a YAML document
+fn key_location(content: &str, key: &str) -> Option<(usize, usize)> {
+    content.lines().enumerate().find_map(|(i, line)| {
+        let name = line.split(':').next()?.trim_end();
+        let name = name.trim_matches(|c| c == '"' || c == '\'');
+        (!line.starts_with(char::is_whitespace) && name == key).then_some((i + 1, 1))
+    })
+}
+
+/// One-based column where the value of the option on `line` starts
+fn value_column(content: &str, line: usize) -> usize {
+    content
+        .lines()
+        .nth(line - 1)
+        .and_then(|text| {
+            let colon = text.find(':')?;
+            let rest = &text[colon + 1..];
+            Some(colon + 2 + (rest.len() - rest.trim_start().len()))
+        })
+        .unwrap_or(1)
+}
+
+/// Known option closest to a misspelled one, if any is close enough
+fn closest_key<'a>(key: &str, known: &[&'a str]) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|candidate| (edit_distance(key, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= 2.max(key.len() / 3))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
 /// Get the default output directory
 fn get_default_output_dir() -> PathBuf {
     dirs::document_dir()
@@ -156,6 +1262,7 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let config = AppConfig {
             output_dir: temp_dir.path().to_path_buf(),
+            ..AppConfig::default()
         };
 
         // First file should use original name
@@ -175,10 +1282,164 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let config = AppConfig {
             output_dir: temp_dir.path().join("new_dir"),
+            ..AppConfig::default()
         };
 
         assert!(!config.output_dir.exists());
         config.ensure_output_dir().unwrap();
         assert!(config.output_dir.exists());
     }
+
+    #[test]
+    fn test_layered_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yml");
+        std::fs::write(&path, "http_port: 4000\ntcp_port: 5000\nlog_level: warn\n").unwrap();
+
+        let env = [
+            ("STORMWORKS_TACVIEW_TCP_PORT", "6000"),
+            ("TCP_PORT", "7000"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        let overrides = ConfigOverrides {
+            log_level: Some("debug".to_string()),
+            ..ConfigOverrides::default()
+        };
+        let loaded = AppConfig::load_layered_with_env(Some(&path), &overrides, env);
+
+        assert_eq!(loaded.config.http_port, 4000);
+        assert_eq!(loaded.config.tcp_port, 6000);
+        assert_eq!(loaded.config.log_level, "debug");
+        assert_eq!(loaded.config.ws_rate, 5.0);
+
+        assert_eq!(loaded.source("http_port"), Some(&ConfigSource::File(path)));
+        assert_eq!(
+            loaded.source("tcp_port"),
+            Some(&ConfigSource::Environment(
+                "STORMWORKS_TACVIEW_TCP_PORT".to_string()
+            ))
+        );
+        assert_eq!(loaded.source("log_level"), Some(&ConfigSource::CommandLine));
+        assert_eq!(loaded.source("ws_rate"), Some(&ConfigSource::Default));
+        assert!(loaded
+            .render()
+            .contains("tcp_port: 6000  # environment STORMWORKS_TACVIEW_TCP_PORT\n"));
+    }
+
+    #[test]
+    fn test_config_watcher() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yml");
+        std::fs::write(&path, "http_port: 4000\nws_rate: 5.0\n").unwrap();
+
+        let overrides = ConfigOverrides::default();
+        let loaded = AppConfig::load_layered(Some(&path), &overrides);
+        let mut watcher = ConfigWatcher::new(path.clone(), overrides, loaded.config);
+        assert!(watcher.poll().unwrap().is_empty());
+
+        // Bump the modification time explicitly, filesystem timestamps may be coarse
+        let touch = |secs: u64| {
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            let time = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+            file.set_modified(time).unwrap();
+        };
+
+        std::fs::write(&path, "http_port: 4001\nws_rate: 10.0\n").unwrap();
+        touch(1_000);
+        let changes = watcher.poll().unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].to_string(), "http_port: 4000 -> 4001");
+        assert!(changes[0].requires_restart());
+        assert_eq!(changes[1].to_string(), "ws_rate: 5.0 -> 10.0");
+        assert!(!changes[1].requires_restart());
+
+        // Invalid edits are rejected and the previous configuration is kept
+        std::fs::write(&path, "http_port: 4002\nws_rate: fast\n").unwrap();
+        touch(2_000);
+        assert!(watcher.poll().is_err());
+        assert_eq!(watcher.current().http_port, 4001);
+        assert_eq!(watcher.current().ws_rate, 10.0);
+    }
+
+    #[test]
+    fn test_recording_name_and_excluded_types() {
+        let time = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let config = AppConfig {
+            filename_template: "Flight-{date}-{timestamp}".to_string(),
+            exclude_types: vec!["Navaid".to_string(), "Sea+Watercraft".to_string()],
+            ..AppConfig::default()
+        };
+        let date = chrono::DateTime::<chrono::Local>::from(time).format("%Y-%m-%d");
+        assert_eq!(
+            config.recording_name(time),
+            format!("Flight-{date}-1700000000")
+        );
+        assert_eq!(
+            AppConfig::default().recording_name(time),
+            "Stormworks-1700000000"
+        );
+
+        assert!(config.is_excluded_type("Navaid+Static+Bullseye"));
+        assert!(config.is_excluded_type("Watercraft+Sea"));
+        assert!(!config.is_excluded_type("Sea+Submarine"));
+        assert!(!config.is_excluded_type("Air+FixedWing"));
+
+        for template in ["", "records/{date}", "{hour}", "Flight-{date"] {
+            let config = AppConfig {
+                filename_template: template.to_string(),
+                ..AppConfig::default()
+            };
+            assert_eq!(config.check()[0].0, "filename_template", "{template}");
+        }
+    }
+
+    #[test]
+    fn test_validate_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yml");
+        std::fs::write(
+            &path,
+            "output_dir: /tmp/acmi\nhtp_port: 4000\ntcp_port: lots\nlog_level: loud\n",
+        )
+        .unwrap();
+
+        let errors = AppConfig::validate_file(&path);
+        assert_eq!(errors.len(), 3);
+
+        assert_eq!(errors[0].kind, ConfigErrorKind::UnknownKey);
+        assert_eq!(errors[0].location, Some((2, 1)));
+        assert!(errors[0].message.contains("did you mean `http_port`?"));
+
+        assert_eq!(errors[1].kind, ConfigErrorKind::InvalidValue);
+        assert_eq!(errors[1].key.as_deref(), Some("tcp_port"));
+        assert_eq!(errors[1].location, Some((3, 11)));
+        assert!(errors[1]
+            .to_string()
+            .starts_with(&format!("{}:3:11: ", path.display())));
+
+        assert_eq!(errors[2].key.as_deref(), Some("log_level"));
+
+        // Valid options are still loaded, only the invalid ones are skipped
+        let loaded = AppConfig::load_layered_with_env(
+            Some(&path),
+            &ConfigOverrides::default(),
+            std::iter::empty(),
+        );
+        assert_eq!(loaded.config.output_dir, PathBuf::from("/tmp/acmi"));
+        assert_eq!(loaded.config.tcp_port, 42674);
+        assert_eq!(loaded.errors.len(), 3);
+
+        std::fs::write(&path, "http_port: [3000\n").unwrap();
+        let errors = AppConfig::validate_file(&path);
+        assert_eq!(errors[0].kind, ConfigErrorKind::Syntax);
+        assert!(errors[0].location.is_some());
+
+        // Typos inside a section are caught too
+        std::fs::write(&path, "flight_event_thresholds:\n  Air: { min_sped: 40 }\n").unwrap();
+        let errors = AppConfig::validate_file(&path);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ConfigErrorKind::InvalidValue);
+        assert_eq!(errors[0].key.as_deref(), Some("flight_event_thresholds"));
+        assert!(errors[0].message.contains("unknown field `min_sped`"));
+    }
 }
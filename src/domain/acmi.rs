@@ -18,4 +18,25 @@ pub trait AcmiRepository: Send + Sync {
 
     /// Perform a step operation (for periodic processing)
     fn step(&self);
+
+    /// Short name for the kind of repository, used in logs and metrics
+    fn name(&self) -> &'static str {
+        "acmi"
+    }
+
+    /// Whether the repository feeds live viewers rather than a recording
+    ///
+    /// Live repositories additionally receive data meant only for display,
+    /// such as dead-reckoned positions.
+    fn is_live(&self) -> bool {
+        false
+    }
+
+    /// Write anything still buffered and let go of the destination
+    ///
+    /// Called once at shutdown, after the last write. Recordings are
+    /// finalized separately by stopping them.
+    async fn close(&self) -> Result<()> {
+        Ok(())
+    }
 }
@@ -0,0 +1,111 @@
+use crate::acmi::{parse_object_id, AcmiRecord, ObjectUpdate};
+
+/// Event types that can be injected through `GET /event`
+const EVENT_TYPES: [&str; 3] = ["Bookmark", "Message", "Debug"];
+
+/// Text event requested by Stormworks, e.g. from a `?mark <text>` chat command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventRequest {
+    /// Event type, one of Bookmark, Message or Debug
+    pub kind: &'static str,
+    /// Objects the event refers to
+    pub objects: Vec<u64>,
+    pub text: String,
+}
+
+impl EventRequest {
+    /// Build an event from decoded query parameters
+    ///
+    /// `type` and `text` are required. Objects are given as hexadecimal IDs,
+    /// either in repeated `object` parameters or separated by commas.
+    pub fn from_query(params: &[(String, String)]) -> Result<Self, String> {
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let kind = param("type").ok_or("missing `type` parameter")?;
+        let kind = EVENT_TYPES
+            .into_iter()
+            .find(|t| t.eq_ignore_ascii_case(kind))
+            .ok_or_else(|| {
+                format!(
+                    "unsupported event type `{kind}`, expected one of: {}",
+                    EVENT_TYPES.join(", ")
+                )
+            })?;
+
+        let text = param("text").unwrap_or("");
+        if text.trim().is_empty() {
+            return Err("missing `text` parameter".to_string());
+        }
+
+        let mut objects = Vec::new();
+        for (_, value) in params.iter().filter(|(key, _)| key == "object") {
+            for id in value.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+                objects.push(parse_object_id(id).map_err(|e| e.to_string())?);
+            }
+        }
+
+        Ok(Self {
+            kind,
+            objects,
+            text: text.to_string(),
+        })
+    }
+
+    /// The ACMI record for this event, with the text escaped when written
+    pub fn to_record(&self) -> AcmiRecord {
+        AcmiRecord::Update(ObjectUpdate::event(self.kind, &self.objects, &self.text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_event_request() {
+        let event = EventRequest::from_query(&params(&[
+            ("type", "bookmark"),
+            ("text", "Engine failure, gear up\nsecond line"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            event.to_record().to_string(),
+            "0,Event=Bookmark|Engine failure\\, gear up\\\nsecond line"
+        );
+
+        let event = EventRequest::from_query(&params(&[
+            ("type", "Message"),
+            ("object", "1a,2b"),
+            ("object", "3c"),
+            ("text", "Hello"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            event.to_record().to_string(),
+            "0,Event=Message|1a|2b|3c|Hello"
+        );
+
+        assert!(
+            EventRequest::from_query(&params(&[("type", "Destroyed"), ("text", "x")])).is_err()
+        );
+        assert!(EventRequest::from_query(&params(&[("type", "Bookmark")])).is_err());
+        assert!(EventRequest::from_query(&params(&[
+            ("type", "Message"),
+            ("object", "zz"),
+            ("text", "x")
+        ]))
+        .is_err());
+    }
+}
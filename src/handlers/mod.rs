@@ -1,7 +1,14 @@
 //! HTTP handlers for Stormworks integration
 //!
-//! This module contains state management for the application.
+//! This module contains state management and status reporting for the
+//! application.
 
-pub use stormworks::{AcmiRepositories, AppState, FileAcmiRepositories};
+pub use event::EventRequest;
+pub use outputs::{parse_rate_override, OutputReport};
+pub use status::{HealthReport, StatusReport};
+pub use stormworks::{AcmiRepositories, AppState, FileAcmiRepositories, ServerPorts};
 
+pub mod event;
+pub mod outputs;
+pub mod status;
 pub mod stormworks;
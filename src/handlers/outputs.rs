@@ -0,0 +1,81 @@
+use serde::Serialize;
+
+use crate::domain::AcmiRepository;
+use crate::handlers::stormworks::AppState;
+use crate::infra::DownsampledRepository;
+
+/// An output as listed by `GET /admin/outputs`
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputReport {
+    pub id: u64,
+    /// Kind of output: file, csv, tacview, stream or websocket
+    pub kind: &'static str,
+    /// Address of the client, for outputs sending to one
+    pub peer: Option<String>,
+    pub live: bool,
+    /// Update rate in Hz, 0 when every update is sent
+    pub rate: f64,
+    /// Whether the rate was set through the admin API instead of the configuration
+    pub overridden: bool,
+}
+
+impl OutputReport {
+    pub fn new(output: &DownsampledRepository) -> Self {
+        Self {
+            id: output.id(),
+            kind: output.name(),
+            peer: output.peer().map(str::to_string),
+            live: output.is_live(),
+            rate: output.rate(),
+            overridden: output.rate_override().is_some(),
+        }
+    }
+
+    /// List every output of the application state
+    pub fn collect(state: &AppState) -> Vec<Self> {
+        state
+            .outputs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|output| Self::new(output))
+            .collect()
+    }
+}
+
+/// Parse the `rate` parameter of `POST /admin/outputs/<id>/rate`
+///
+/// A number of Hz overrides the configured rate, 0 sends every update, and
+/// `default` goes back to the configured rate.
+pub fn parse_rate_override(value: &str) -> Result<Option<f64>, String> {
+    if value.eq_ignore_ascii_case("default") {
+        return Ok(None);
+    }
+    match value.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate >= 0.0 => Ok(Some(rate)),
+        _ => Err(format!(
+            "invalid rate `{value}`, expected a number of Hz, 0 or `default`"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rate_override() {
+        assert_eq!(parse_rate_override("5"), Ok(Some(5.0)));
+        assert_eq!(parse_rate_override("0.5"), Ok(Some(0.5)));
+        assert_eq!(parse_rate_override("0"), Ok(Some(0.0)));
+
+        // Going back to the configured rate
+        assert_eq!(parse_rate_override("default"), Ok(None));
+        assert_eq!(parse_rate_override("Default"), Ok(None));
+
+        for invalid in ["-1", "fast", "", "NaN", "inf"] {
+            let error = parse_rate_override(invalid).unwrap_err();
+            assert!(error.contains(&format!("`{invalid}`")), "{error}");
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+
+use crate::domain::AcmiFileRepository;
+use crate::handlers::stormworks::AppState;
+
+/// Snapshot of what the bridge is doing, served by `GET /status`
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusReport {
+    pub version: &'static str,
+    pub uptime_seconds: f64,
+    pub ports: PortsReport,
+    pub recording: RecordingReport,
+    pub tacview_clients: u64,
+    pub websocket_clients: usize,
+    pub frames_received: u64,
+    /// `None` until the first frame arrives
+    pub seconds_since_last_frame: Option<f64>,
+    pub config_file: Option<PathBuf>,
+}
+
+/// Ports the servers were started on
+#[derive(Debug, Clone, Serialize)]
+pub struct PortsReport {
+    pub http: Option<u16>,
+    pub tcp: Option<u16>,
+    pub udp: Option<u16>,
+}
+
+/// State of the file recording
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingReport {
+    pub active: bool,
+    pub file: Option<PathBuf>,
+    pub bytes: u64,
+}
+
+/// Result of the health check served by `GET /healthz`
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub reason: Option<String>,
+}
+
+impl StatusReport {
+    /// Collect the current status from the application state
+    pub async fn collect(state: &AppState) -> Self {
+        let mut recording = RecordingReport {
+            active: false,
+            file: None,
+            bytes: 0,
+        };
+
+        for repo in state.file_repositories.lock().await.iter() {
+            if repo.is_recording() {
+                recording.active = true;
+                recording.file = recording.file.or_else(|| repo.current_file());
+                recording.bytes += repo.recording_size().unwrap_or(0);
+            }
+        }
+
+        let frames_received =
+            state.metrics.frames_received.get("http") + state.metrics.frames_received.get("udp");
+        let ports = state.ports.lock().unwrap().clone();
+
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            uptime_seconds: state.started_at.elapsed().as_secs_f64(),
+            ports: PortsReport {
+                http: ports.http,
+                tcp: ports.tcp,
+                udp: ports.udp,
+            },
+            recording,
+            tacview_clients: state.metrics.tacview_clients.load(Ordering::Relaxed),
+            websocket_clients: state
+                .websocket_feed
+                .as_ref()
+                .map_or(0, |feed| feed.subscriber_count()),
+            frames_received,
+            seconds_since_last_frame: state.since_last_frame().map(|d| d.as_secs_f64()),
+            config_file: state.config_path.clone(),
+        }
+    }
+}
+
+impl HealthReport {
+    /// Check that frames are still arriving while a recording is active
+    ///
+    /// Silence is measured from the last frame or the start of the latest
+    /// recording, whichever came later.
+    pub async fn collect(state: &AppState) -> Self {
+        let mut recording_started_at = None;
+        for repo in state.file_repositories.lock().await.iter() {
+            recording_started_at = recording_started_at.max(repo.recording_started_at());
+        }
+        let recording = recording_started_at.is_some();
+
+        let silence = (*state.last_frame_at.lock().unwrap())
+            .max(recording_started_at)
+            .map_or_else(|| state.started_at.elapsed(), |at| at.elapsed());
+
+        let window = state.health_window();
+        if recording && silence > window {
+            Self {
+                healthy: false,
+                reason: Some(format!(
+                    "recording but no frames received for {:.1}s (window: {}s)",
+                    silence.as_secs_f64(),
+                    window.as_secs_f64()
+                )),
+            }
+        } else {
+            Self {
+                healthy: true,
+                reason: None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::FileAcmiRepository;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_health_fails_when_recording_goes_silent() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = AppState::new();
+        *state.health_window.lock().unwrap() = Duration::ZERO;
+
+        let repo = Arc::new(FileAcmiRepository::new_with_config(crate::AppConfig {
+            output_dir: temp_dir.path().to_path_buf(),
+            ..crate::AppConfig::default()
+        }));
+        state.file_repositories.lock().await.push(repo.clone());
+
+        // Not recording, so silence is fine
+        assert!(HealthReport::collect(&state).await.healthy);
+
+        repo.start().unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let health = HealthReport::collect(&state).await;
+        assert!(!health.healthy);
+        assert!(health.reason.unwrap().contains("no frames"));
+
+        let status = StatusReport::collect(&state).await;
+        assert!(status.recording.active);
+        assert!(status.recording.file.is_some());
+        assert!(status.seconds_since_last_frame.is_none());
+
+        repo.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_health_counts_silence_from_recording_start() {
+        let temp_dir = TempDir::new().unwrap();
+        let state = AppState::new();
+        *state.health_window.lock().unwrap() = Duration::from_millis(50);
+
+        let repo = Arc::new(FileAcmiRepository::new_with_config(crate::AppConfig {
+            output_dir: temp_dir.path().to_path_buf(),
+            ..crate::AppConfig::default()
+        }));
+        state.file_repositories.lock().await.push(repo.clone());
+
+        // Idle for longer than the window, then start recording
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        repo.start().unwrap();
+        assert!(HealthReport::collect(&state).await.healthy);
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert!(!HealthReport::collect(&state).await.healthy);
+
+        repo.stop().await.unwrap();
+    }
+}
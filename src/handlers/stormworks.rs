@@ -1,8 +1,22 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
+use tracing::{error, info, warn};
 
-use crate::domain::AcmiRepository;
-use crate::infra::FileAcmiRepository;
+use crate::acmi::Validator;
+use crate::config::AppConfig;
+use crate::domain::{AcmiFileRepository, AcmiRepository};
+use crate::infra::{
+    CsvAcmiRepository, DownsampledRepository, FileAcmiRepository, WebSocketFeedRepository,
+};
+use crate::metrics::{Metrics, RuntimeGauges};
+use crate::pipeline::Pipeline;
+use crate::server::InFlight;
 
 /// Shared state for ACMI repositories
 pub type AcmiRepositories = Arc<Mutex<Vec<Arc<dyn AcmiRepository>>>>;
@@ -10,11 +24,51 @@ pub type AcmiRepositories = Arc<Mutex<Vec<Arc<dyn AcmiRepository>>>>;
 /// Shared state for file-based ACMI repositories
 pub type FileAcmiRepositories = Arc<Mutex<Vec<Arc<FileAcmiRepository>>>>;
 
+/// Ports the servers were bound to
+#[derive(Debug, Clone, Default)]
+pub struct ServerPorts {
+    pub http: Option<u16>,
+    pub tcp: Option<u16>,
+    pub udp: Option<u16>,
+}
+
 /// Application state containing both repository types
 pub struct AppState {
     pub acmi_repositories: AcmiRepositories,
     pub file_repositories: FileAcmiRepositories,
-    pub verbose: bool,
+    /// Live object feed served to WebSocket clients, if enabled
+    pub websocket_feed: Option<Arc<WebSocketFeedRepository>>,
+    /// CSV export that follows the recordings, if enabled
+    pub csv_export: Option<Arc<CsvAcmiRepository>>,
+    /// Shared with the outputs, which record their own writes
+    pub metrics: Arc<Metrics>,
+    /// Whether per-request and per-connection logs are written, follows the log level
+    pub verbose: AtomicBool,
+    /// When the application state was created
+    pub started_at: Instant,
+    /// When the last ACMI frame was received from Stormworks
+    pub last_frame_at: std::sync::Mutex<Option<Instant>>,
+    /// Ports the servers are bound to, reported by the status endpoint
+    pub ports: std::sync::Mutex<ServerPorts>,
+    /// Location of the configuration file, if known
+    pub config_path: Option<PathBuf>,
+    /// How long recording may go without frames before health checks fail
+    pub health_window: std::sync::Mutex<Duration>,
+    /// Token admin requests from other hosts must carry, see [`AppConfig::admin_token`]
+    pub admin_token: std::sync::Mutex<Option<String>>,
+    /// Interval between live feed updates sent to WebSocket clients
+    pub ws_interval: std::sync::Mutex<Duration>,
+    /// Processing applied to incoming ACMI before it is written
+    pub pipeline: Mutex<Pipeline>,
+    /// Repositories added with [`AppState::add_output`], which can be rate limited
+    pub outputs: std::sync::Mutex<Vec<Arc<DownsampledRepository>>>,
+    /// Configured update rate by kind of output
+    pub output_rates: std::sync::Mutex<BTreeMap<String, f64>>,
+    /// Checks frames from Stormworks when ingest validation is enabled
+    pub validator: std::sync::Mutex<Option<Validator>>,
+    /// Connections streaming to Tacview, WebSocket and ACMI stream clients
+    pub clients: InFlight,
+    next_output_id: AtomicU64,
 }
 
 impl Default for AppState {
@@ -25,18 +79,365 @@ impl Default for AppState {
 
 impl AppState {
     pub fn new() -> Self {
-        Self {
-            acmi_repositories: Arc::new(Mutex::new(Vec::new())),
-            file_repositories: Arc::new(Mutex::new(Vec::new())),
-            verbose: false,
-        }
+        Self::new_with_verbose(false)
     }
 
     pub fn new_with_verbose(verbose: bool) -> Self {
         Self {
             acmi_repositories: Arc::new(Mutex::new(Vec::new())),
             file_repositories: Arc::new(Mutex::new(Vec::new())),
-            verbose,
+            websocket_feed: None,
+            csv_export: None,
+            metrics: Arc::new(Metrics::new()),
+            verbose: AtomicBool::new(verbose),
+            started_at: Instant::now(),
+            last_frame_at: std::sync::Mutex::new(None),
+            ports: std::sync::Mutex::new(ServerPorts::default()),
+            config_path: None,
+            health_window: std::sync::Mutex::new(Duration::from_secs(30)),
+            admin_token: std::sync::Mutex::new(None),
+            ws_interval: std::sync::Mutex::new(Duration::from_millis(200)),
+            pipeline: Mutex::new(Pipeline::from_config(&AppConfig::default())),
+            outputs: std::sync::Mutex::new(Vec::new()),
+            output_rates: std::sync::Mutex::new(BTreeMap::new()),
+            validator: std::sync::Mutex::new(None),
+            clients: InFlight::new(),
+            next_output_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Apply the options that can change while the servers are running
+    ///
+    /// Bind addresses and ports are ignored, they only take effect on restart.
+    pub async fn apply_config(&self, config: &AppConfig) {
+        self.verbose.store(config.is_verbose(), Ordering::Relaxed);
+        *self.health_window.lock().unwrap() = Duration::from_secs(config.health_window_secs);
+        *self.admin_token.lock().unwrap() = config.admin_token.clone();
+        *self.ws_interval.lock().unwrap() = Duration::from_secs_f64(1.0 / config.ws_rate.max(0.1));
+
+        for repo in self.file_repositories.lock().await.iter() {
+            repo.set_config(config.clone());
+        }
+        if let Some(csv) = &self.csv_export {
+            csv.set_config(config.clone());
+        }
+
+        self.pipeline.lock().await.configure(config);
+
+        {
+            let mut validator = self.validator.lock().unwrap();
+            match (config.validate_ingest, validator.is_some()) {
+                (true, false) => *validator = Some(Validator::new()),
+                (false, true) => *validator = None,
+                _ => {}
+            }
+        }
+
+        *self.output_rates.lock().unwrap() = config.output_rates.clone();
+        for output in self.outputs.lock().unwrap().iter() {
+            output.set_default_rate(self.default_rate(output.name()));
+        }
+    }
+
+    /// Whether per-request and per-connection logs are written
+    pub fn is_verbose(&self) -> bool {
+        self.verbose.load(Ordering::Relaxed)
+    }
+
+    /// Configured update rate for a kind of output, 0 when unlimited
+    fn default_rate(&self, kind: &str) -> f64 {
+        self.output_rates
+            .lock()
+            .unwrap()
+            .get(kind)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Register a repository to receive ACMI data, limited to the configured
+    /// rate for its kind
+    ///
+    /// `peer` is the address of the client the repository sends to, if any.
+    /// Returns the output, whose ID identifies it in the admin API.
+    pub async fn add_output(
+        &self,
+        repo: Arc<dyn AcmiRepository>,
+        peer: Option<String>,
+    ) -> Arc<DownsampledRepository> {
+        let id = self.next_output_id.fetch_add(1, Ordering::Relaxed);
+        let output = Arc::new(DownsampledRepository::new(
+            id,
+            repo,
+            peer,
+            self.metrics.clone(),
+        ));
+        output.set_default_rate(self.default_rate(output.name()));
+
+        self.outputs.lock().unwrap().push(output.clone());
+        self.acmi_repositories
+            .lock()
+            .await
+            .push(output.clone() as Arc<dyn AcmiRepository>);
+        output
+    }
+
+    /// Stop writing to an output added with [`AppState::add_output`]
+    pub async fn remove_output(&self, output: &Arc<DownsampledRepository>) {
+        let repo = output.clone() as Arc<dyn AcmiRepository>;
+        self.acmi_repositories
+            .lock()
+            .await
+            .retain(|r| !Arc::ptr_eq(r, &repo));
+        self.outputs
+            .lock()
+            .unwrap()
+            .retain(|o| !Arc::ptr_eq(o, output));
+    }
+
+    /// Find an output by its ID
+    pub fn output(&self, id: u64) -> Option<Arc<DownsampledRepository>> {
+        self.outputs
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|output| output.id() == id)
+            .cloned()
+    }
+
+    /// How long recording may go without frames before health checks fail
+    pub fn health_window(&self) -> Duration {
+        *self.health_window.lock().unwrap()
+    }
+
+    /// Interval between live feed updates sent to WebSocket clients
+    pub fn ws_interval(&self) -> Duration {
+        *self.ws_interval.lock().unwrap()
+    }
+
+    /// Record that an ACMI frame was received from the given source
+    ///
+    /// Returns the total number of frames received from that source.
+    pub fn record_frame(&self, source: &'static str) -> u64 {
+        *self.last_frame_at.lock().unwrap() = Some(Instant::now());
+        self.metrics.frames_received.inc(source)
+    }
+
+    /// Time since the last ACMI frame, if any was received
+    pub fn since_last_frame(&self) -> Option<Duration> {
+        self.last_frame_at.lock().unwrap().map(|at| at.elapsed())
+    }
+
+    /// Write ACMI data to every registered repository
+    ///
+    /// A failing repository is logged and skipped so the remaining ones still
+    /// receive the data. Returns the number of repositories written to.
+    pub async fn broadcast(&self, acmi: &str) -> usize {
+        self.write_repositories(acmi, false).await
+    }
+
+    /// Write ACMI data only to live repositories, leaving recordings untouched
+    ///
+    /// Returns the number of repositories written to.
+    pub async fn broadcast_live(&self, acmi: &str) -> usize {
+        self.write_repositories(acmi, true).await
+    }
+
+    async fn write_repositories(&self, acmi: &str, live_only: bool) -> usize {
+        let repos = self.acmi_repositories.lock().await;
+        let mut written = 0;
+
+        for (i, repo) in repos.iter().enumerate() {
+            if live_only && !repo.is_live() {
+                continue;
+            }
+
+            // Outputs record the metrics of what they actually write
+            let result = repo.write(acmi).await;
+            written += 1;
+
+            if let Err(e) = result {
+                error!(
+                    "Failed to write ACMI data to {} repository {}: {}",
+                    repo.name(),
+                    i,
+                    e
+                );
+            }
+        }
+
+        written
+    }
+
+    /// Check a frame from Stormworks for ACMI violations, if enabled
+    ///
+    /// Violations are counted and logged; the frame is forwarded regardless.
+    pub fn validate_frame(&self, source: &'static str, acmi: &str) {
+        let mut validator = self.validator.lock().unwrap();
+        let Some(validator) = validator.as_mut() else {
+            return;
+        };
+
+        for violation in validator.check(acmi) {
+            self.metrics.acmi_violations.inc(violation.kind.as_str());
+            warn!("Invalid ACMI in {} frame, {}", source, violation);
+        }
+    }
+
+    /// Run ACMI received from Stormworks through the pipeline and write it
+    /// to every registered repository
+    ///
+    /// The pipeline stays locked while writing so that batches reach the
+    /// repositories in the order they were processed. Returns the number of
+    /// repositories written to.
+    pub async fn ingest(&self, acmi: &str) -> usize {
+        let mut pipeline = self.pipeline.lock().await;
+        let processed = pipeline.process(acmi, Instant::now());
+        self.broadcast(&processed).await
+    }
+
+    /// Have the pipeline write the full state with the next batch
+    ///
+    /// Called when an output was added or started recording, since stages
+    /// like delta compression otherwise only send what changed.
+    pub async fn resync(&self) {
+        self.pipeline.lock().await.resync();
+    }
+
+    /// Let the pipeline start over from the addon's clock, on `/start`
+    pub async fn new_session(&self) {
+        self.pipeline.lock().await.new_session();
+    }
+
+    /// ReferenceTime for headers, when the bridge assigns the time frames
+    ///
+    /// Only known in wall clock time mode, where time frames count from the
+    /// session start. Otherwise they follow the addon's clock.
+    pub async fn reference_time(&self) -> Option<DateTime<Utc>> {
+        let now = Instant::now();
+        let start = self.pipeline.lock().await.session_start(now)?;
+        let elapsed = chrono::Duration::from_std(now.saturating_duration_since(start)).ok()?;
+        Some(Utc::now() - elapsed)
+    }
+
+    /// Write records injected by the pipeline's periodic processing
+    pub async fn tick(&self) {
+        let now = Instant::now();
+        {
+            let mut pipeline = self.pipeline.lock().await;
+            if let Some(acmi) = pipeline.tick(now) {
+                self.broadcast(&acmi).await;
+            }
+            if let Some(acmi) = pipeline.live_tick(now) {
+                self.broadcast_live(&acmi).await;
+            }
+        }
+
+        // Rate limited outputs write what they collected when due
+        let outputs = self.outputs.lock().unwrap().clone();
+        for output in outputs {
+            if let Err(e) = output.flush(now).await {
+                error!(
+                    "Failed to write ACMI data to {} output {}: {}",
+                    output.name(),
+                    output.id(),
+                    e
+                );
+            }
+        }
+    }
+
+    /// Flush and close every output, finalize recordings and wait for
+    /// streaming clients to disconnect
+    ///
+    /// Closing outputs and waiting for clients each give up after `timeout`,
+    /// so a stuck client cannot keep recordings from being saved. Fails if
+    /// anything did not finish cleanly, after doing everything it can.
+    pub async fn shutdown(&self, timeout: Duration) -> Result<()> {
+        let mut failures = 0;
+
+        // Records held back by the pipeline or a rate limit go out first
+        self.tick().await;
+
+        let outputs = self.outputs.lock().unwrap().clone();
+        let closing = futures::future::join_all(
+            outputs
+                .iter()
+                .map(|output| async move { (output, output.close().await) }),
+        );
+        match tokio::time::timeout(timeout, closing).await {
+            Ok(results) => {
+                for (output, result) in results {
+                    if let Err(e) = result {
+                        error!(
+                            "Failed to close {} output {}: {}",
+                            output.name(),
+                            output.id(),
+                            e
+                        );
+                        failures += 1;
+                    }
+                }
+            }
+            Err(_) => {
+                error!("Outputs did not finish writing within {:?}", timeout);
+                failures += 1;
+            }
+        }
+
+        for repo in self.file_repositories.lock().await.iter() {
+            if repo.is_recording() {
+                match repo.stop().await {
+                    Ok(()) => info!("Stopped ACMI recording"),
+                    Err(e) => {
+                        error!("Failed to stop ACMI recording: {}", e);
+                        failures += 1;
+                    }
+                }
+            }
         }
+        if let Some(csv) = self.csv_export.as_ref().filter(|csv| csv.is_recording()) {
+            if let Err(e) = csv.stop().await {
+                error!("Failed to stop CSV export: {}", e);
+                failures += 1;
+            }
+        }
+
+        if tokio::time::timeout(timeout, self.clients.wait_idle())
+            .await
+            .is_err()
+        {
+            error!(
+                "{} client(s) still connected after {:?}",
+                self.clients.count(),
+                timeout
+            );
+            failures += 1;
+        }
+
+        if failures > 0 {
+            bail!("{failures} problem(s) while shutting down");
+        }
+        Ok(())
+    }
+
+    /// Sample the gauges reported alongside the metrics counters
+    pub async fn runtime_gauges(&self) -> RuntimeGauges {
+        let mut gauges = RuntimeGauges {
+            acmi_repositories: self.acmi_repositories.lock().await.len(),
+            websocket_clients: self
+                .websocket_feed
+                .as_ref()
+                .map_or(0, |feed| feed.subscriber_count()),
+            ..RuntimeGauges::default()
+        };
+
+        for repo in self.file_repositories.lock().await.iter() {
+            if repo.is_recording() {
+                gauges.recording = true;
+                gauges.recording_file_bytes += repo.recording_size().unwrap_or(0);
+            }
+        }
+
+        gauges
     }
 }
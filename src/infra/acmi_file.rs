@@ -1,24 +1,26 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
-use std::io::Write;
-use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime};
 use tempfile::NamedTempFile;
 use tracing::{error, info, warn};
-use zip::write::FileOptions;
-use zip::ZipWriter;
 
+use crate::acmi::{logical_lines, write_recording, AcmiRecord};
 use crate::config::AppConfig;
 use crate::domain::{AcmiFileRepository, AcmiRepository};
 
 /// File-based ACMI repository implementation
 ///
 /// This implementation writes ACMI data to files and provides lifecycle management
-/// for starting/stopping recordings.
+/// for starting/stopping recordings. Objects of the configured excluded types
+/// are left out, and old recordings are removed by the retention options
+/// whenever a recording is saved.
 pub struct FileAcmiRepository {
     state: Arc<Mutex<FileAcmiState>>,
-    config: AppConfig,
+    config: Mutex<AppConfig>,
 }
 
 #[derive(Debug)]
@@ -26,6 +28,13 @@ struct FileAcmiState {
     filename: Option<PathBuf>,
     is_recording: bool,
     temp_file: Option<NamedTempFile>,
+    bytes_written: u64,
+    /// When the current recording was started
+    started_at: Option<Instant>,
+    /// Latest `Type` of each object, to apply `exclude_types`
+    types: HashMap<u64, String>,
+    /// ReferenceTime of the next recordings, see [`FileAcmiRepository::set_reference_time`]
+    reference_time: Option<DateTime<Utc>>,
 }
 
 impl Drop for FileAcmiRepository {
@@ -63,17 +72,40 @@ impl FileAcmiRepository {
                 filename: None,
                 is_recording: false,
                 temp_file: None,
+                bytes_written: 0,
+                started_at: None,
+                types: HashMap::new(),
+                reference_time: None,
             })),
-            config,
+            config: Mutex::new(config),
         }
     }
 
+    /// Configuration used for new recordings
+    pub fn config(&self) -> AppConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Replace the configuration used for new recordings
+    ///
+    /// A recording in progress keeps the file name it was started with, but
+    /// excluded types apply to the rest of it.
+    pub fn set_config(&self, config: AppConfig) {
+        *self.config.lock().unwrap() = config;
+    }
+
     /// Generate ACMI file header with metadata
-    fn generate_acmi_header() -> String {
-        "FileType=text/acmi/tacview\n\
+    pub(crate) fn generate_acmi_header() -> String {
+        Self::acmi_header("2023-01-01T00:00:00.000Z")
+    }
+
+    /// File header with the given ReferenceTime and RecordingTime
+    fn acmi_header(reference_time: &str) -> String {
+        format!(
+            "FileType=text/acmi/tacview\n\
             FileVersion=2.2\n\
-            0,ReferenceTime=2023-01-01T00:00:00.000Z\n\
-            0,RecordingTime=2023-01-01T00:00:00.000Z\n\
+            0,ReferenceTime={reference_time}\n\
+            0,RecordingTime={reference_time}\n\
             0,Title=StormworksACMI\n\
             0,DataRecorder=StormworksACMI 0.1.0\n\
             0,DataSource=Stormworks\n\
@@ -81,58 +113,119 @@ impl FileAcmiRepository {
             0,ReferenceLongitude=180\n\
             0,ReferenceLatitude=0\n\
             40000003,T=0|0|2000|0|0,Type=Navaid+Static+Bullseye,Color=Blue,Coalition=Allies\n"
-            .to_string()
+        )
+    }
+
+    /// Use a ReferenceTime matching the bridge's timeline for the next
+    /// recordings, or the fixed one of the default header with `None`
+    pub fn set_reference_time(&self, reference_time: Option<DateTime<Utc>>) {
+        self.state.lock().unwrap().reference_time = reference_time;
     }
 
     /// Save ACMI data from temporary file to ZIP file
     fn save_acmi_file(&self, filename: &PathBuf, temp_file: NamedTempFile) -> Result<()> {
-        // Create ZIP file and copy content from temporary file
-        let zip_file = std::fs::File::create(filename)
-            .with_context(|| format!("Failed to create ZIP file: {filename:?}"))?;
-
-        let mut zip = ZipWriter::new(zip_file);
-        let options = FileOptions::default()
-            .compression_method(zip::CompressionMethod::Deflated)
-            .unix_permissions(0o755);
-
-        // Generate txt.acmi filename based on zip filename
-        let txt_filename = filename
-            .file_stem()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .replace(".zip", ".txt");
-        let txt_filename = format!("{txt_filename}.acmi");
-
-        zip.start_file(&txt_filename, options)
-            .with_context(|| format!("Failed to start file in ZIP: {txt_filename}"))?;
-
         // Copy content from temporary file to ZIP
         let temp_path = temp_file.path();
         let temp_content = std::fs::read(temp_path)
             .with_context(|| format!("Failed to read temporary file: {temp_path:?}"))?;
 
-        zip.write_all(&temp_content)
-            .with_context(|| format!("Failed to write ACMI content to ZIP: {filename:?}"))?;
-
-        zip.finish()
-            .with_context(|| format!("Failed to finalize ZIP file: {filename:?}"))?;
+        write_recording(filename, &temp_content)?;
 
         // Temporary file is automatically deleted when dropped
         info!("Saved ACMI file: {:?}", filename);
         Ok(())
     }
 
-    /// Generate filename based on current timestamp
+    /// Path the current recording will be saved to, if one is in progress
+    pub fn current_file(&self) -> Option<PathBuf> {
+        self.state.lock().unwrap().filename.clone()
+    }
+
+    /// When the current recording was started, if one is in progress
+    pub fn recording_started_at(&self) -> Option<Instant> {
+        let state = self.state.lock().unwrap();
+        state.is_recording.then_some(state.started_at).flatten()
+    }
+
+    /// Uncompressed size of the current recording, if one is in progress
+    pub fn recording_size(&self) -> Option<u64> {
+        let state = self.state.lock().unwrap();
+        state.is_recording.then_some(state.bytes_written)
+    }
+
+    /// Generate filename from the configured template
     fn generate_filename(&self) -> PathBuf {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let config = self.config();
+        let name = config.recording_name(SystemTime::now());
+        config.generate_output_path(&format!("{name}.zip.acmi"))
+    }
+}
 
-        let base_filename = format!("Stormworks-{now}.zip.acmi");
-        self.config.generate_output_path(&base_filename)
+/// Leave the objects of excluded types out of ACMI
+///
+/// An object is recognized from the update carrying its `Type` on, so lines
+/// sent before it are kept.
+fn filter_excluded(acmi: &str, config: &AppConfig, types: &mut HashMap<u64, String>) -> String {
+    let mut output = String::with_capacity(acmi.len());
+    for (_, line) in logical_lines(acmi) {
+        let excluded = match AcmiRecord::parse(&line) {
+            Ok(AcmiRecord::Update(update)) if !update.is_global() => {
+                if let Some(object_type) = update.property("Type") {
+                    types.insert(update.id, object_type.to_string());
+                }
+                types
+                    .get(&update.id)
+                    .is_some_and(|t| config.is_excluded_type(t))
+            }
+            Ok(AcmiRecord::Removal(id)) => types
+                .remove(&id)
+                .is_some_and(|t| config.is_excluded_type(&t)),
+            _ => false,
+        };
+        if !excluded {
+            output.push_str(&line);
+            output.push('\n');
+        }
     }
+    output
+}
+
+/// Remove recordings beyond the configured number or age
+///
+/// Only `.acmi` files directly in the output directory are considered, and
+/// `keep` is never removed. Returns the removed files.
+fn apply_retention(config: &AppConfig, keep: &Path) -> Result<Vec<PathBuf>> {
+    if config.retention_max_files == 0 && config.retention_days <= 0.0 {
+        return Ok(Vec::new());
+    }
+
+    let entries = std::fs::read_dir(&config.output_dir)
+        .with_context(|| format!("Failed to list {:?}", config.output_dir))?;
+    let mut recordings: Vec<(SystemTime, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "acmi") && path != keep)
+        .filter_map(|path| Some((path.metadata().ok()?.modified().ok()?, path)))
+        .collect();
+    // Newest first, the kept recording counts as the newest
+    recordings.sort_by(|a, b| b.cmp(a));
+
+    let max_age = (config.retention_days > 0.0)
+        .then(|| Duration::from_secs_f64(config.retention_days * 86_400.0));
+    let now = SystemTime::now();
+    let mut removed = Vec::new();
+    for (index, (modified, path)) in recordings.into_iter().enumerate() {
+        let too_many = config.retention_max_files > 0 && index + 1 >= config.retention_max_files;
+        let too_old = max_age
+            .is_some_and(|max_age| now.duration_since(modified).unwrap_or_default() > max_age);
+        if too_many || too_old {
+            std::fs::remove_file(&path).with_context(|| format!("Failed to remove {path:?}"))?;
+            info!("Removed old recording: {:?}", path);
+            removed.push(path);
+        }
+    }
+    Ok(removed)
 }
 
 impl Default for FileAcmiRepository {
@@ -144,9 +237,18 @@ impl Default for FileAcmiRepository {
 #[async_trait]
 impl AcmiRepository for FileAcmiRepository {
     async fn write(&self, acmi: &str) -> Result<()> {
-        let mut state = self.state.lock().unwrap();
+        let config = self.config();
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
 
         if state.is_recording {
+            let filtered;
+            let acmi = if config.exclude_types.is_empty() {
+                acmi
+            } else {
+                filtered = filter_excluded(acmi, &config, &mut state.types);
+                filtered.as_str()
+            };
             if let Some(ref mut temp_file) = state.temp_file {
                 use std::io::Write;
                 temp_file
@@ -155,6 +257,7 @@ impl AcmiRepository for FileAcmiRepository {
                 temp_file
                     .flush()
                     .with_context(|| "Failed to flush temporary ACMI file")?;
+                state.bytes_written += acmi.len() as u64;
             }
         }
 
@@ -164,6 +267,10 @@ impl AcmiRepository for FileAcmiRepository {
     fn step(&self) {
         // No periodic processing needed for file repository
     }
+
+    fn name(&self) -> &'static str {
+        "file"
+    }
 }
 
 #[async_trait]
@@ -179,12 +286,15 @@ impl AcmiFileRepository for FileAcmiRepository {
         }
 
         // Ensure output directory exists
-        if let Err(e) = self.config.ensure_output_dir() {
+        if let Err(e) = self.config().ensure_output_dir() {
             warn!("Failed to ensure output directory: {}", e);
         }
 
         let filename = self.generate_filename();
-        let header = Self::generate_acmi_header();
+        let header = match state.reference_time {
+            Some(time) => Self::acmi_header(&time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()),
+            None => Self::generate_acmi_header(),
+        };
 
         // Create temporary file and write header
         let mut temp_file =
@@ -201,6 +311,9 @@ impl AcmiFileRepository for FileAcmiRepository {
         state.filename = Some(filename.clone());
         state.temp_file = Some(temp_file);
         state.is_recording = true;
+        state.bytes_written = header.len() as u64;
+        state.started_at = Some(Instant::now());
+        state.types.clear();
 
         info!("Started ACMI recording: {:?}", filename);
 
@@ -226,6 +339,9 @@ impl AcmiFileRepository for FileAcmiRepository {
         if let (Some(filename), Some(temp_file)) = (filename, temp_file) {
             self.save_acmi_file(&filename, temp_file)?;
             info!("Stopped ACMI recording and saved ZIP: {:?}", filename);
+            if let Err(e) = apply_retention(&self.config(), &filename) {
+                warn!("Failed to remove old recordings: {:#}", e);
+            }
         } else {
             warn!("No filename or temporary file to process when stopping recording");
         }
@@ -0,0 +1,86 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use crate::domain::AcmiRepository;
+
+/// Number of ACMI writes that may queue up before a slow client is dropped
+const STREAM_QUEUE_SIZE: usize = 256;
+
+/// Channel-backed ACMI repository for streaming clients
+///
+/// Writes are queued for a connection task to send, so a slow client never
+/// delays the other repositories. A client that falls too far behind is
+/// marked closed and stops receiving data.
+pub struct ChannelAcmiRepository {
+    sender: mpsc::Sender<String>,
+    closed: AtomicBool,
+    /// Whether the end of the stream was queued by [`AcmiRepository::close`]
+    ending: AtomicBool,
+}
+
+impl ChannelAcmiRepository {
+    /// Create a repository and the receiver its writes are delivered to
+    pub fn new() -> (Self, mpsc::Receiver<String>) {
+        let (sender, receiver) = mpsc::channel(STREAM_QUEUE_SIZE);
+        let repo = Self {
+            sender,
+            closed: AtomicBool::new(false),
+            ending: AtomicBool::new(false),
+        };
+        (repo, receiver)
+    }
+
+    /// Check if the client has gone away or was dropped for being too slow
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed) || self.sender.is_closed()
+    }
+}
+
+#[async_trait]
+impl AcmiRepository for ChannelAcmiRepository {
+    async fn write(&self, acmi: &str) -> Result<()> {
+        // Empty writes are reserved for marking the end of the stream
+        if acmi.is_empty() || self.is_closed() || self.ending.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        match self.sender.try_send(acmi.to_string()) {
+            Ok(()) => Ok(()),
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                warn!("Dropping ACMI stream client that is not keeping up");
+                self.closed.store(true, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                self.closed.store(true, Ordering::Relaxed);
+                Ok(())
+            }
+        }
+    }
+
+    fn step(&self) {
+        // No periodic processing needed for stream repository
+    }
+
+    fn name(&self) -> &'static str {
+        "stream"
+    }
+
+    fn is_live(&self) -> bool {
+        true
+    }
+
+    /// Queue an empty write after the pending ones, which ends the
+    /// connection once everything before it was sent
+    async fn close(&self) -> Result<()> {
+        if self.is_closed() || self.ending.swap(true, Ordering::Relaxed) {
+            return Ok(());
+        }
+        // A client that went away no longer reads the queue
+        let _ = self.sender.send(String::new()).await;
+        Ok(())
+    }
+}
@@ -0,0 +1,373 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+use crate::config::AppConfig;
+use crate::domain::{AcmiFileRepository, AcmiRepository};
+use crate::infra::FileAcmiRepository;
+use crate::tools::csv::{object_path, CsvWriter, NumericProperties, DEFAULT_COLUMNS};
+
+/// Live CSV export of object updates
+///
+/// When `csv_export` is enabled, this repository follows the recording
+/// lifecycle of [`FileAcmiRepository`]: starting a recording creates a CSV
+/// file (or a directory of files, one per object) next to the ACMI file, and
+/// every object update is written as a row until the recording stops.
+///
+/// Without `csv_columns`, rows hold the default columns followed by every
+/// numeric property seen so far. When a new numeric property appears after
+/// rows were written, the export continues in a new file (or directory) whose
+/// header includes it.
+pub struct CsvAcmiRepository {
+    state: Mutex<CsvState>,
+    config: Mutex<AppConfig>,
+}
+
+struct CsvState {
+    writer: CsvWriter,
+    /// Whether object state is tracked, so names are known when a recording starts
+    enabled: bool,
+    /// Numeric properties seen so far, added as columns when none are configured
+    properties: NumericProperties,
+    output: Option<CsvOutput>,
+    /// Configuration and file name the current recording was started with
+    recording: Option<(AppConfig, String)>,
+    /// Whether rows were written to the current output
+    has_rows: bool,
+}
+
+/// Where the rows of the current recording go
+enum CsvOutput {
+    Single {
+        path: PathBuf,
+        file: BufWriter<File>,
+    },
+    PerObject {
+        dir: PathBuf,
+        header: String,
+        files: HashMap<u64, BufWriter<File>>,
+    },
+}
+
+impl CsvOutput {
+    fn write(&mut self, rows: Vec<(u64, String)>) -> Result<()> {
+        match self {
+            Self::Single { file, .. } => {
+                for (_, row) in rows {
+                    file.write_all(row.as_bytes())?;
+                }
+                file.flush()?;
+            }
+            Self::PerObject { dir, header, files } => {
+                let mut touched = Vec::new();
+                for (id, row) in rows {
+                    let file = match files.entry(id) {
+                        Entry::Occupied(entry) => entry.into_mut(),
+                        Entry::Vacant(entry) => {
+                            let path = object_path(dir, id);
+                            let mut file = BufWriter::new(
+                                File::create(&path)
+                                    .with_context(|| format!("Failed to create {path:?}"))?,
+                            );
+                            file.write_all(header.as_bytes())?;
+                            entry.insert(file)
+                        }
+                    };
+                    file.write_all(row.as_bytes())?;
+                    touched.push(id);
+                }
+                for id in touched {
+                    if let Some(file) = files.get_mut(&id) {
+                        file.flush()?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self) -> &PathBuf {
+        match self {
+            Self::Single { path, .. } => path,
+            Self::PerObject { dir, .. } => dir,
+        }
+    }
+}
+
+impl CsvAcmiRepository {
+    /// Create a CSV repository with the given configuration
+    pub fn new_with_config(config: AppConfig) -> Self {
+        // Start from the recording header so positions use the same reference point
+        let mut writer = CsvWriter::default();
+        writer.rows(&FileAcmiRepository::generate_acmi_header());
+
+        Self {
+            state: Mutex::new(CsvState {
+                writer,
+                enabled: config.csv_export,
+                properties: NumericProperties::default(),
+                output: None,
+                recording: None,
+                has_rows: false,
+            }),
+            config: Mutex::new(config),
+        }
+    }
+
+    /// Replace the configuration used for new recordings
+    ///
+    /// A recording in progress keeps its file and columns.
+    pub fn set_config(&self, config: AppConfig) {
+        self.state.lock().unwrap().enabled = config.csv_export;
+        *self.config.lock().unwrap() = config;
+    }
+
+    /// File or directory the current recording is written to
+    pub fn current_path(&self) -> Option<PathBuf> {
+        self.state
+            .lock()
+            .unwrap()
+            .output
+            .as_ref()
+            .map(|output| output.path().clone())
+    }
+
+    /// Columns of a new output: the configured ones, or the default columns
+    /// followed by the numeric properties seen so far
+    fn columns(config: &AppConfig, properties: &NumericProperties) -> Vec<String> {
+        if config.csv_columns.is_empty() {
+            DEFAULT_COLUMNS
+                .iter()
+                .map(|c| c.to_string())
+                .chain(properties.names())
+                .collect()
+        } else {
+            config.csv_columns.clone()
+        }
+    }
+
+    /// Create the file, or directory of files, rows are written to
+    fn open(config: &AppConfig, name: &str, header: String) -> Result<CsvOutput> {
+        if config.csv_per_object {
+            let dir = config.generate_output_path(&format!("{name}-csv"));
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create CSV directory {dir:?}"))?;
+            Ok(CsvOutput::PerObject {
+                dir,
+                header,
+                files: HashMap::new(),
+            })
+        } else {
+            let path = config.generate_output_path(&format!("{name}.csv"));
+            let mut file = BufWriter::new(
+                File::create(&path).with_context(|| format!("Failed to create {path:?}"))?,
+            );
+            file.write_all(header.as_bytes())
+                .and_then(|_| file.flush())
+                .with_context(|| format!("Failed to write CSV header to {path:?}"))?;
+            Ok(CsvOutput::Single { path, file })
+        }
+    }
+}
+
+impl CsvState {
+    /// Switch to a new output when numeric properties appeared that are not
+    /// columns yet, keeping the current one if nothing was written to it
+    fn add_property_columns(&mut self) -> Result<()> {
+        let Some((config, name)) = &self.recording else {
+            return Ok(());
+        };
+        if !config.csv_columns.is_empty() {
+            return Ok(());
+        }
+        let columns = CsvAcmiRepository::columns(config, &self.properties);
+        let mut grown = self.writer.columns().to_vec();
+        for column in columns {
+            if !grown.contains(&column) {
+                grown.push(column);
+            }
+        }
+        if grown.len() == self.writer.columns().len() {
+            return Ok(());
+        }
+
+        self.writer.set_columns(grown);
+        let header = self.writer.header();
+        // Nothing was written yet, so the output can simply be replaced
+        let output = match self.output.take() {
+            Some(CsvOutput::PerObject { dir, files, .. }) if !self.has_rows => {
+                CsvOutput::PerObject { dir, header, files }
+            }
+            Some(CsvOutput::Single { path, file }) if !self.has_rows => {
+                drop(file);
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("Failed to replace {path:?}"))?;
+                CsvAcmiRepository::open(config, name, header)?
+            }
+            _ => CsvAcmiRepository::open(config, name, header)?,
+        };
+        if self.has_rows {
+            info!(
+                "New numeric properties, continuing CSV export in {:?}",
+                output.path()
+            );
+        }
+        self.output = Some(output);
+        self.has_rows = false;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AcmiRepository for CsvAcmiRepository {
+    async fn write(&self, acmi: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.enabled && state.output.is_none() {
+            return Ok(());
+        }
+
+        state.properties.observe(acmi);
+        state.add_property_columns()?;
+
+        let CsvState {
+            writer,
+            output,
+            has_rows,
+            ..
+        } = &mut *state;
+        let rows = writer.rows(acmi);
+        if let Some(output) = output {
+            *has_rows |= !rows.is_empty();
+            output
+                .write(rows)
+                .with_context(|| format!("Failed to write CSV rows to {:?}", output.path()))?;
+        }
+        Ok(())
+    }
+
+    fn step(&self) {
+        // No periodic processing needed for CSV export
+    }
+
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+}
+
+#[async_trait]
+impl AcmiFileRepository for CsvAcmiRepository {
+    fn start(&self) -> Result<()> {
+        let config = self.config.lock().unwrap().clone();
+        let mut state = self.state.lock().unwrap();
+
+        if state.output.take().is_some() {
+            warn!("Stopping existing CSV export before starting new one");
+        }
+        if !config.csv_export {
+            return Ok(());
+        }
+
+        let name = config.recording_name(SystemTime::now());
+        let columns = Self::columns(&config, &state.properties);
+        state.writer.set_columns(columns);
+        let output = Self::open(&config, &name, state.writer.header())?;
+
+        info!("Started CSV export: {:?}", output.path());
+        state.output = Some(output);
+        state.recording = Some((config, name));
+        state.has_rows = false;
+        Ok(())
+    }
+
+    async fn stop(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.recording = None;
+        let Some(output) = state.output.take() else {
+            return Ok(());
+        };
+
+        // Files are flushed after every write and closed when dropped
+        info!("Stopped CSV export: {:?}", output.path());
+        Ok(())
+    }
+
+    fn is_recording(&self) -> bool {
+        self.state.lock().unwrap().output.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_csv_export_follows_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AppConfig {
+            output_dir: dir.path().to_path_buf(),
+            csv_export: true,
+            csv_columns: vec!["id".to_string(), "name".to_string(), "lon".to_string()],
+            ..AppConfig::default()
+        };
+        let repo = CsvAcmiRepository::new_with_config(config.clone());
+
+        repo.write("#0\n101,T=1|2|3,Name=Heli\n").await.unwrap();
+        repo.start().unwrap();
+        repo.write("#1\n101,T=2||\n").await.unwrap();
+        let path = repo.current_path().unwrap();
+        repo.stop().await.unwrap();
+        repo.write("#2\n101,T=3||\n").await.unwrap();
+
+        let csv = std::fs::read_to_string(path).unwrap();
+        assert_eq!(csv, "id,name,lon\n101,Heli,182\n");
+
+        // Nothing is written when the export is disabled
+        repo.set_config(AppConfig {
+            csv_export: false,
+            ..config
+        });
+        repo.start().unwrap();
+        assert!(!repo.is_recording());
+    }
+
+    #[tokio::test]
+    async fn test_csv_export_adds_numeric_properties() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = CsvAcmiRepository::new_with_config(AppConfig {
+            output_dir: dir.path().to_path_buf(),
+            filename_template: "Flight".to_string(),
+            csv_export: true,
+            ..AppConfig::default()
+        });
+
+        // Properties seen before the recording are columns from the start
+        repo.write("#0\n101,T=1|2|3,Fuel=0.9\n").await.unwrap();
+        repo.start().unwrap();
+        let first = repo.current_path().unwrap();
+        repo.write("#1\n101,T=2||,Fuel=0.8,Pilot=Ann\n")
+            .await
+            .unwrap();
+
+        // A new numeric property continues the export in a new file
+        repo.write("#2\n101,IAS=120\n").await.unwrap();
+        let second = repo.current_path().unwrap();
+        repo.stop().await.unwrap();
+
+        assert_ne!(first, second);
+        let header = |path: &PathBuf| {
+            let csv = std::fs::read_to_string(path).unwrap();
+            csv.lines().next().unwrap().to_string()
+        };
+        assert!(header(&first).ends_with(",yaw,Fuel"));
+        assert!(header(&second).ends_with(",yaw,Fuel,IAS"));
+        let csv = std::fs::read_to_string(&second).unwrap();
+        assert!(csv.ends_with(",0.8,120\n"), "{csv}");
+    }
+}
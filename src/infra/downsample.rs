@@ -0,0 +1,307 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::acmi::{logical_lines, AcmiRecord, ObjectUpdate, Transform};
+use crate::domain::AcmiRepository;
+use crate::metrics::Metrics;
+use crate::pipeline::render;
+
+/// Output repository limiting how often another repository is written to
+///
+/// While a rate is set, writes are collected and passed on at most that many
+/// times per second by [`DownsampledRepository::flush`]. Object updates in
+/// between are merged into the latest state of each object, stamped with the
+/// latest time frame. Events, removals and global properties are always
+/// passed on, in order.
+///
+/// Bytes, latency and errors are recorded in the metrics when the wrapped
+/// repository is written to, not when data is collected.
+pub struct DownsampledRepository {
+    id: u64,
+    peer: Option<String>,
+    inner: Arc<dyn AcmiRepository>,
+    metrics: Arc<Metrics>,
+    state: Mutex<DownsampleState>,
+}
+
+#[derive(Debug, Default)]
+struct DownsampleState {
+    /// Rate from the configuration, 0 when unlimited
+    default_rate: f64,
+    /// Rate set for this output through the admin API
+    rate_override: Option<f64>,
+    pending: Coalescer,
+    last_flush: Option<Instant>,
+}
+
+impl DownsampleState {
+    fn rate(&self) -> f64 {
+        self.rate_override.unwrap_or(self.default_rate)
+    }
+}
+
+impl DownsampledRepository {
+    /// Wrap a repository, identified by `id` and optionally the address of its client
+    pub fn new(
+        id: u64,
+        inner: Arc<dyn AcmiRepository>,
+        peer: Option<String>,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        Self {
+            id,
+            peer,
+            inner,
+            metrics,
+            state: Mutex::new(DownsampleState::default()),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Address of the client this output sends to, if any
+    pub fn peer(&self) -> Option<&str> {
+        self.peer.as_deref()
+    }
+
+    /// Effective rate in Hz, 0 when every write is passed on immediately
+    pub fn rate(&self) -> f64 {
+        self.state.lock().unwrap().rate()
+    }
+
+    /// Rate set for this output through the admin API, if any
+    pub fn rate_override(&self) -> Option<f64> {
+        self.state.lock().unwrap().rate_override
+    }
+
+    /// Set the rate used unless overridden
+    pub fn set_default_rate(&self, rate: f64) {
+        self.state.lock().unwrap().default_rate = rate;
+    }
+
+    /// Override the configured rate, or go back to it with `None`
+    pub fn set_rate_override(&self, rate: Option<f64>) {
+        self.state.lock().unwrap().rate_override = rate;
+    }
+
+    /// Write the collected data if the output is due for an update
+    pub async fn flush(&self, now: Instant) -> Result<()> {
+        let acmi = {
+            let mut state = self.state.lock().unwrap();
+            let rate = state.rate();
+            let due = rate <= 0.0
+                || state.last_flush.is_none_or(|last| {
+                    now.saturating_duration_since(last) >= Duration::from_secs_f64(1.0 / rate)
+                });
+            if !due {
+                return Ok(());
+            }
+            match state.pending.take() {
+                Some(acmi) => {
+                    state.last_flush = Some(now);
+                    acmi
+                }
+                None => return Ok(()),
+            }
+        };
+
+        self.write_inner(&acmi).await
+    }
+
+    /// Write to the wrapped repository and record it in the metrics
+    async fn write_inner(&self, acmi: &str) -> Result<()> {
+        let started = Instant::now();
+        let result = self.inner.write(acmi).await;
+        self.metrics.record_write(
+            self.inner.name(),
+            acmi.len(),
+            started.elapsed(),
+            result.is_ok(),
+        );
+        result
+    }
+}
+
+#[async_trait]
+impl AcmiRepository for DownsampledRepository {
+    async fn write(&self, acmi: &str) -> Result<()> {
+        let pending = {
+            let mut state = self.state.lock().unwrap();
+            if state.rate() > 0.0 {
+                state.pending.push(acmi);
+                return Ok(());
+            }
+            // Data collected before the limit was lifted goes first
+            state.pending.take()
+        };
+
+        if let Some(pending) = pending {
+            self.write_inner(&pending).await?;
+        }
+        self.write_inner(acmi).await
+    }
+
+    fn step(&self) {
+        self.inner.step();
+    }
+
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn is_live(&self) -> bool {
+        self.inner.is_live()
+    }
+
+    /// Write the collected data regardless of the rate, then close the
+    /// wrapped repository
+    async fn close(&self) -> Result<()> {
+        let pending = self.state.lock().unwrap().pending.take();
+        if let Some(pending) = pending {
+            self.write_inner(&pending).await?;
+        }
+        self.inner.close().await
+    }
+}
+
+/// Records collected between two writes of a downsampled output
+#[derive(Debug, Default)]
+struct Coalescer {
+    /// Latest time frame not yet written
+    time: Option<f64>,
+    records: Vec<AcmiRecord>,
+    /// Position in `records` of the merged update for each object
+    updates: HashMap<u64, usize>,
+}
+
+impl Coalescer {
+    fn push(&mut self, acmi: &str) {
+        for (line_number, line) in logical_lines(acmi) {
+            let record = match AcmiRecord::parse(&line) {
+                Ok(record) => record,
+                Err(e) => {
+                    debug!("Dropping unparseable ACMI line {}: {}", line_number, e);
+                    continue;
+                }
+            };
+
+            match record {
+                AcmiRecord::TimeFrame(time) => self.time = Some(time),
+                AcmiRecord::Update(update) if !update.is_global() => {
+                    match self.updates.get(&update.id) {
+                        Some(&index) => {
+                            if let AcmiRecord::Update(pending) = &mut self.records[index] {
+                                merge(pending, update);
+                            }
+                        }
+                        None => {
+                            self.updates.insert(update.id, self.records.len());
+                            self.records.push(AcmiRecord::Update(update));
+                        }
+                    }
+                }
+                AcmiRecord::Removal(id) => {
+                    // An update after the removal creates the object again
+                    self.updates.remove(&id);
+                    self.records.push(record);
+                }
+                record => self.records.push(record),
+            }
+        }
+    }
+
+    /// Render everything collected so far, or `None` if there is nothing new
+    fn take(&mut self) -> Option<String> {
+        let mut records = std::mem::take(&mut self.records);
+        self.updates.clear();
+        if let Some(time) = self.time.take() {
+            records.insert(0, AcmiRecord::TimeFrame(time));
+        }
+
+        (!records.is_empty()).then(|| render(&records))
+    }
+}
+
+/// Merge a later update of the same object into a pending one
+fn merge(pending: &mut ObjectUpdate, update: ObjectUpdate) {
+    for (key, value) in update.properties {
+        let Some((_, existing)) = pending.properties.iter_mut().find(|(k, _)| *k == key) else {
+            pending.properties.push((key, value));
+            continue;
+        };
+
+        // Components omitted from a later T= keep the earlier values
+        if key == "T" {
+            if let (Ok(mut transform), Ok(later)) =
+                (Transform::parse(existing), Transform::parse(&value))
+            {
+                transform.merge(&later);
+                *existing = transform.to_acmi();
+                continue;
+            }
+        }
+        *existing = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::ChannelAcmiRepository;
+
+    #[tokio::test]
+    async fn test_coalesces_until_due() {
+        let (channel, mut receiver) = ChannelAcmiRepository::new();
+        let metrics = Arc::new(Metrics::new());
+        let output = DownsampledRepository::new(1, Arc::new(channel), None, metrics.clone());
+        output.set_default_rate(2.0);
+        let start = Instant::now();
+
+        output
+            .write("#1\n1,T=1|2|300|0|0|90,Name=Heli\n2,T=5|5|0\n")
+            .await
+            .unwrap();
+        output
+            .write("#1.5\n1,T=1.5||310,Fuel=0.5\n0,Event=Bookmark|Hover\n-2\n")
+            .await
+            .unwrap();
+        output.write("#1.9\n1,T=||320\n2,T=6|6|0\n").await.unwrap();
+        assert!(receiver.try_recv().is_err());
+
+        assert_eq!(metrics.bytes_written.get("stream"), 0);
+
+        output.flush(start).await.unwrap();
+        let coalesced = receiver.try_recv().unwrap();
+        assert_eq!(
+            coalesced,
+            "#1.9\n1,T=1.5|2|320|0|0|90,Name=Heli,Fuel=0.5\n2,T=5|5|0\n\
+             0,Event=Bookmark|Hover\n-2\n2,T=6|6|0\n"
+        );
+        // Only what reached the client counts as written
+        assert_eq!(metrics.bytes_written.get("stream"), coalesced.len() as u64);
+
+        // Nothing is written before the interval has passed
+        output.write("#2\n1,T=2|2|320\n").await.unwrap();
+        output
+            .flush(start + Duration::from_millis(200))
+            .await
+            .unwrap();
+        assert!(receiver.try_recv().is_err());
+        output
+            .flush(start + Duration::from_millis(500))
+            .await
+            .unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), "#2\n1,T=2|2|320\n");
+
+        // Lifting the limit passes writes straight through
+        output.set_rate_override(Some(0.0));
+        output.write("#3\n1,T=3|2|320\n").await.unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), "#3\n1,T=3|2|320\n");
+    }
+}
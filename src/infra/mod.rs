@@ -1,10 +1,18 @@
 //! Infrastructure layer for ACMI repositories
 //!
 //! This module contains concrete implementations of the ACMI repository traits
-//! for file-based storage and real-time telemetry streaming.
+//! for file-based storage, real-time telemetry streaming and live feeds.
 
 pub mod acmi_file;
+pub mod acmi_stream;
+pub mod csv_file;
+pub mod downsample;
 pub mod real_time_telemetry;
+pub mod websocket_feed;
 
 pub use acmi_file::FileAcmiRepository;
+pub use acmi_stream::ChannelAcmiRepository;
+pub use csv_file::CsvAcmiRepository;
+pub use downsample::DownsampledRepository;
 pub use real_time_telemetry::TcpRealTimeTelemetryRepository;
+pub use websocket_feed::WebSocketFeedRepository;
@@ -1,11 +1,11 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tracing::{error, info};
+use tracing::{debug, error, info};
 
 use crate::domain::{AcmiRepository, RealTimeTelemetryRepository};
 
@@ -16,8 +16,9 @@ use crate::domain::{AcmiRepository, RealTimeTelemetryRepository};
 pub struct TcpRealTimeTelemetryRepository {
     stream: Arc<tokio::sync::Mutex<TcpStream>>,
     closed: Arc<AtomicBool>,
-    message_count: Arc<std::sync::atomic::AtomicU64>,
     verbose: bool,
+    /// ReferenceTime sent in the header, the current time when not set
+    reference_time: Option<DateTime<Utc>>,
 }
 
 impl TcpRealTimeTelemetryRepository {
@@ -26,8 +27,8 @@ impl TcpRealTimeTelemetryRepository {
         Self {
             stream: Arc::new(tokio::sync::Mutex::new(stream)),
             closed: Arc::new(AtomicBool::new(false)),
-            message_count: Arc::new(std::sync::atomic::AtomicU64::new(0)),
             verbose: false,
+            reference_time: None,
         }
     }
 
@@ -36,19 +37,33 @@ impl TcpRealTimeTelemetryRepository {
         Self {
             stream: Arc::new(tokio::sync::Mutex::new(stream)),
             closed: Arc::new(AtomicBool::new(false)),
-            message_count: Arc::new(std::sync::atomic::AtomicU64::new(0)),
             verbose,
+            reference_time: None,
         }
     }
 
+    /// Send the given ReferenceTime in the header, so the time frames that
+    /// follow line up with the bridge's timeline
+    pub fn with_reference_time(mut self, reference_time: Option<DateTime<Utc>>) -> Self {
+        self.reference_time = reference_time;
+        self
+    }
+
     /// Generate ACMI header for real-time telemetry
-    fn generate_realtime_header() -> String {
+    ///
+    /// Without a `reference_time`, the current time is used. No time frame is
+    /// included, the data that follows starts with its own.
+    pub(crate) fn generate_realtime_header(reference_time: Option<DateTime<Utc>>) -> String {
         let now = Utc::now();
         let time_str = now.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+        let reference_str = reference_time
+            .unwrap_or(now)
+            .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+            .to_string();
         format!(
             "FileType=text/acmi/tacview\n\
             FileVersion=2.2\n\
-            0,ReferenceTime={time_str}\n\
+            0,ReferenceTime={reference_str}\n\
             0,RecordingTime={time_str}\n\
             0,Title=StormworksACMI\n\
             0,DataRecorder=StormworksACMI 0.1.0\n\
@@ -56,8 +71,7 @@ impl TcpRealTimeTelemetryRepository {
             0,Author=stormworks-tacview-rust\n\
             0,ReferenceLongitude=180\n\
             0,ReferenceLatitude=0\n\
-            40000003,T=0|0|2000|0|0,Type=Navaid+Static+Bullseye,Color=Blue,Coalition=Allies\n\
-            #0.001\n"
+            40000003,T=0|0|2000|0|0,Type=Navaid+Static+Bullseye,Color=Blue,Coalition=Allies\n"
         )
     }
 
@@ -89,16 +103,10 @@ impl AcmiRepository for TcpRealTimeTelemetryRepository {
         // Send raw ACMI data without timestamp formatting (like original TypeScript)
         // The timestamp is only added once in the header during handshake
 
-        // Track message count and log regularly
-        let count = self.message_count.fetch_add(1, Ordering::Relaxed);
-
-        // Debug: Always log first few messages, then every 100th (reduced frequency)
-        if self.verbose && (count < 5 || count % 100 == 0) {
-            info!(
-                "Sending to Tacview: message #{}, {} bytes (raw ACMI)",
-                count + 1,
-                acmi.len()
-            );
+        // Message and byte counts are kept in the metrics registry by the
+        // output wrapper, only the size is logged here
+        if self.verbose {
+            debug!("Sending to Tacview: {} bytes (raw ACMI)", acmi.len());
         }
 
         let mut stream = self.stream.lock().await;
@@ -112,16 +120,10 @@ impl AcmiRepository for TcpRealTimeTelemetryRepository {
                     return Err(e.into());
                 }
 
-                // Log connection health less frequently
-                if self.verbose && count < 5 {
-                    info!("Message #{} sent successfully", count + 1);
-                } else if count % 500 == 0 && count > 0 {
-                    info!("Tacview connection healthy ({} messages sent)", count + 1);
-                }
                 Ok(())
             }
             Err(e) => {
-                error!("Failed to send message #{} to Tacview: {}", count + 1, e);
+                error!("Failed to send message to Tacview: {}", e);
                 self.handle_connection_error(&e);
                 Err(e.into())
             }
@@ -131,6 +133,26 @@ impl AcmiRepository for TcpRealTimeTelemetryRepository {
     fn step(&self) {
         // No periodic processing needed for TCP repository
     }
+
+    fn name(&self) -> &'static str {
+        "tacview"
+    }
+
+    fn is_live(&self) -> bool {
+        true
+    }
+
+    /// Flush the stream and end it, so Tacview sees the end of the data
+    async fn close(&self) -> Result<()> {
+        if self.closed.swap(true, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let mut stream = self.stream.lock().await;
+        stream.flush().await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -168,7 +190,7 @@ impl RealTimeTelemetryRepository for TcpRealTimeTelemetryRepository {
         }
 
         // Send ACMI header
-        let header = Self::generate_realtime_header();
+        let header = Self::generate_realtime_header(self.reference_time);
         if let Err(e) = stream.write_all(header.as_bytes()).await {
             self.handle_connection_error(&e);
             return Err(e.into());
@@ -0,0 +1,264 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::acmi::{logical_lines, parse_records, AcmiRecord, ObjectState, WorldState};
+use crate::domain::AcmiRepository;
+use crate::infra::FileAcmiRepository;
+
+/// Number of messages that may queue up for a slow client before it is dropped
+const SUBSCRIBER_QUEUE_SIZE: usize = 64;
+
+/// Live object feed for WebSocket clients
+///
+/// This repository parses the ACMI stream into object state and, on every
+/// [`step`](AcmiRepository::step), sends the objects that changed since the
+/// previous step to each subscriber as JSON. New subscribers first receive
+/// a snapshot of every known object.
+///
+/// The feed starts from the same header the recordings use, so positions
+/// are reported relative to the bridge's reference point.
+pub struct WebSocketFeedRepository {
+    state: Mutex<FeedState>,
+}
+
+#[derive(Default)]
+struct FeedState {
+    world: WorldState,
+    changed: BTreeSet<u64>,
+    removed: BTreeSet<u64>,
+    subscribers: Vec<mpsc::Sender<String>>,
+}
+
+/// JSON message sent to WebSocket clients
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum FeedMessage {
+    /// Every known object, sent once on connect
+    Snapshot {
+        time: f64,
+        objects: Vec<ObjectMessage>,
+    },
+    /// Objects changed or removed since the previous message
+    Update {
+        time: f64,
+        objects: Vec<ObjectMessage>,
+        removed: Vec<String>,
+    },
+}
+
+/// JSON representation of a single object
+#[derive(Debug, Serialize)]
+struct ObjectMessage {
+    id: String,
+    name: Option<String>,
+    #[serde(rename = "type")]
+    object_type: Option<String>,
+    coalition: Option<String>,
+    lon: Option<f64>,
+    lat: Option<f64>,
+    alt: Option<f64>,
+    heading: Option<f64>,
+}
+
+impl ObjectMessage {
+    fn new(world: &WorldState, object: &ObjectState) -> Self {
+        let position = world.absolute_position(object);
+        Self {
+            id: format!("{:x}", object.id),
+            name: object.name().map(str::to_string),
+            object_type: object.object_type().map(str::to_string),
+            coalition: object.coalition().map(str::to_string),
+            lon: position.map(|p| p.0),
+            lat: position.map(|p| p.1),
+            alt: position.map(|p| p.2),
+            heading: object.transform.heading_or_yaw(),
+        }
+    }
+}
+
+impl WebSocketFeedRepository {
+    /// Create a new feed with no subscribers
+    pub fn new() -> Self {
+        let mut state = FeedState::default();
+        for record in parse_records(&FileAcmiRepository::generate_acmi_header()) {
+            state.world.apply(&record);
+        }
+
+        Self {
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Register a new subscriber
+    ///
+    /// The returned receiver yields a snapshot of all known objects first,
+    /// followed by incremental updates.
+    pub fn subscribe(&self) -> mpsc::Receiver<String> {
+        let (tx, rx) = mpsc::channel(SUBSCRIBER_QUEUE_SIZE);
+        let mut state = self.state.lock().unwrap();
+
+        let mut objects: Vec<_> = state
+            .world
+            .objects()
+            .map(|object| ObjectMessage::new(&state.world, object))
+            .collect();
+        objects.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let snapshot = FeedMessage::Snapshot {
+            time: state.world.time,
+            objects,
+        };
+        match serde_json::to_string(&snapshot) {
+            Ok(json) => {
+                // The channel is empty, so this cannot fail
+                let _ = tx.try_send(json);
+            }
+            Err(e) => warn!("Failed to serialize WebSocket snapshot: {}", e),
+        }
+
+        state.subscribers.push(tx);
+        info!(
+            "Added WebSocket subscriber (total: {})",
+            state.subscribers.len()
+        );
+        rx
+    }
+
+    /// Number of connected subscribers
+    pub fn subscriber_count(&self) -> usize {
+        self.state.lock().unwrap().subscribers.len()
+    }
+}
+
+impl Default for WebSocketFeedRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl AcmiRepository for WebSocketFeedRepository {
+    async fn write(&self, acmi: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        for (_, line) in logical_lines(acmi) {
+            let Ok(record) = AcmiRecord::parse(&line) else {
+                continue;
+            };
+
+            match &record {
+                AcmiRecord::Update(update) if !update.is_global() => {
+                    state.changed.insert(update.id);
+                    state.removed.remove(&update.id);
+                }
+                AcmiRecord::Removal(id) => {
+                    state.changed.remove(id);
+                    state.removed.insert(*id);
+                }
+                _ => {}
+            }
+
+            state.world.apply(&record);
+        }
+
+        Ok(())
+    }
+
+    fn step(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.changed.is_empty() && state.removed.is_empty() {
+            return;
+        }
+
+        let changed = std::mem::take(&mut state.changed);
+        let removed = std::mem::take(&mut state.removed);
+
+        if state.subscribers.is_empty() {
+            return;
+        }
+
+        let message = FeedMessage::Update {
+            time: state.world.time,
+            objects: changed
+                .iter()
+                .filter_map(|id| state.world.object(*id))
+                .map(|object| ObjectMessage::new(&state.world, object))
+                .collect(),
+            removed: removed.iter().map(|id| format!("{id:x}")).collect(),
+        };
+
+        let json = match serde_json::to_string(&message) {
+            Ok(json) => json,
+            Err(e) => {
+                warn!("Failed to serialize WebSocket update: {}", e);
+                return;
+            }
+        };
+
+        state
+            .subscribers
+            .retain(|tx| match tx.try_send(json.clone()) {
+                Ok(()) => true,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    warn!("Dropping WebSocket subscriber that is not keeping up");
+                    false
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => false,
+            });
+    }
+
+    fn name(&self) -> &'static str {
+        "websocket"
+    }
+
+    fn is_live(&self) -> bool {
+        true
+    }
+
+    /// Send the last changes, then drop every subscriber so their
+    /// connections close
+    async fn close(&self) -> Result<()> {
+        self.step();
+        self.state.lock().unwrap().subscribers.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_snapshot_then_updates() {
+        let feed = WebSocketFeedRepository::new();
+        feed.write("#1\n101,T=1|2|300,Name=Heli\n").await.unwrap();
+        feed.step();
+
+        let mut rx = feed.subscribe();
+        let snapshot: serde_json::Value = serde_json::from_str(&rx.recv().await.unwrap()).unwrap();
+        assert_eq!(snapshot["type"], "snapshot");
+        assert_eq!(snapshot["objects"][0]["id"], "101");
+        assert_eq!(snapshot["objects"][0]["lon"], 181.0);
+        assert_eq!(snapshot["objects"][0]["name"], "Heli");
+        // Bullseye from the bridge's ACMI header
+        assert_eq!(snapshot["objects"][1]["id"], "40000003");
+
+        // Nothing changed since the last step
+        feed.step();
+        assert!(rx.try_recv().is_err());
+
+        feed.write("#2\n102,T=0|0|0\n-101\n").await.unwrap();
+        feed.step();
+        let update: serde_json::Value = serde_json::from_str(&rx.recv().await.unwrap()).unwrap();
+        assert_eq!(update["type"], "update");
+        assert_eq!(update["time"], 2.0);
+        assert_eq!(update["objects"][0]["id"], "102");
+        assert_eq!(update["removed"][0], "101");
+    }
+}
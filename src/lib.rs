@@ -3,14 +3,24 @@
 //! A bridge software that connects Stormworks with Tacview, enabling
 //! real-time telemetry streaming and ACMI file export.
 
+pub mod acmi;
 pub mod config;
 pub mod domain;
 pub mod handlers;
 pub mod infra;
+pub mod metrics;
+pub mod pipeline;
 pub mod server;
+pub mod testing;
+pub mod tools;
 
-pub use config::AppConfig;
+pub use config::{
+    AppConfig, ConfigChange, ConfigError, ConfigErrorKind, ConfigOverrides, ConfigWatcher,
+    LoadedConfig,
+};
 pub use domain::{AcmiFileRepository, AcmiRepository, RealTimeTelemetryRepository};
 pub use handlers::AppState;
-pub use infra::{FileAcmiRepository, TcpRealTimeTelemetryRepository};
-pub use server::{HttpServer, TcpServer};
+pub use infra::{
+    CsvAcmiRepository, FileAcmiRepository, TcpRealTimeTelemetryRepository, WebSocketFeedRepository,
+};
+pub use server::{HttpServer, ShutdownToken, TcpServer, UdpServer};
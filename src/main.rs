@@ -1,87 +1,465 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use stormworks_tacview::domain::{AcmiFileRepository, AcmiRepository};
-use stormworks_tacview::{AppConfig, AppState, FileAcmiRepository, HttpServer, TcpServer};
+use stormworks_tacview::acmi::{read_recording, validate, write_recording};
+use stormworks_tacview::domain::AcmiRepository;
+use stormworks_tacview::handlers::ServerPorts;
+use stormworks_tacview::tools::{
+    csv, cut, import, merge, parse_offset, simulate, to_geojson, to_kml, Inspection,
+    SimulationOptions, Tracks,
+};
+use stormworks_tacview::{
+    AppConfig, AppState, ConfigOverrides, ConfigWatcher, CsvAcmiRepository, FileAcmiRepository,
+    HttpServer, LoadedConfig, ShutdownToken, TcpServer, UdpServer, WebSocketFeedRepository,
+};
 use tracing::{error, info, warn};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer};
 
 /// Stormworks-Tacview Bridge
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Enable verbose debug logging
-    #[arg(short, long)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Configuration file (default: ~/.config/stormworks-tacview.yml)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Enable verbose debug logging (same as --log-level debug)
+    #[arg(short, long, global = true)]
     verbose: bool,
 
-    /// HTTP server port (default: 3000)
-    #[arg(long, default_value_t = 3000)]
-    http_port: u16,
+    /// Refuse to start if the configuration contains any invalid option
+    #[arg(long)]
+    strict_config: bool,
 
-    /// TCP server port (default: 42674)
-    #[arg(long, default_value_t = 42674)]
-    tcp_port: u16,
+    #[command(flatten)]
+    overrides: ConfigOverrides,
 }
 
-/// Application configuration
-#[derive(Debug)]
-struct Config {
-    http_port: u16,
-    tcp_port: u16,
-    verbose: bool,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Summarize a recording (.zip.acmi or .txt.acmi)
+    Inspect {
+        /// Recording to inspect
+        file: PathBuf,
+        /// Print the summary as JSON
+        #[arg(long)]
+        json: bool,
+        /// Report gaps between time frames longer than this many seconds
+        #[arg(long, default_value_t = 5.0)]
+        gap: f64,
+    },
+    /// Extract part of a recording, keeping the state of objects at the cut
+    Cut {
+        /// Recording to cut
+        file: PathBuf,
+        /// Start of the part to keep, in seconds, m:ss or h:mm:ss
+        #[arg(long, value_parser = parse_offset)]
+        from: f64,
+        /// End of the part to keep, in seconds, m:ss or h:mm:ss
+        #[arg(long, value_parser = parse_offset)]
+        to: f64,
+        /// File to write, zipped unless it ends in .txt.acmi
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Join recordings such as rotated segments into one
+    Merge {
+        /// Recordings to merge
+        #[arg(required = true, num_args = 2..)]
+        files: Vec<PathBuf>,
+        /// File to write, zipped unless it ends in .txt.acmi
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Convert ACMI from another simulator to the bridge's conventions
+    Import {
+        /// Tacview 2.x recording to import (.zip.acmi or .txt.acmi)
+        file: PathBuf,
+        /// File to write, zipped unless it ends in .txt.acmi
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Keep position offsets, moving the imported reference point onto the Stormworks one
+        #[arg(long)]
+        recenter: bool,
+    },
+    /// Export a recording to other formats
+    Export {
+        #[command(subcommand)]
+        format: ExportCommand,
+    },
+    /// Check recordings for ACMI that Tacview would drop or misread
+    Validate {
+        /// Recordings to check (.zip.acmi or .txt.acmi)
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Send synthetic Stormworks traffic to a running bridge and report throughput
+    Simulate {
+        /// HTTP address of the bridge (default: the configured HTTP address)
+        #[arg(long)]
+        target: Option<SocketAddr>,
+        /// Number of simulated aircraft
+        #[arg(long, default_value_t = 32)]
+        aircraft: usize,
+        /// Number of simulated ships
+        #[arg(long, default_value_t = 8)]
+        ships: usize,
+        /// Frames per second
+        #[arg(long, default_value_t = 10.0)]
+        rate: f64,
+        /// Seconds to run for
+        #[arg(long, default_value_t = 30.0)]
+        duration: f64,
+        /// Record the simulated traffic with /start and /stop
+        #[arg(long)]
+        record: bool,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
 }
 
-impl From<Args> for Config {
-    fn from(args: Args) -> Self {
-        Self {
-            http_port: args.http_port,
-            tcp_port: args.tcp_port,
-            verbose: args.verbose,
-        }
+#[derive(Subcommand, Debug)]
+enum ExportCommand {
+    /// Write one CSV row per object update
+    Csv {
+        /// Recording to export
+        file: PathBuf,
+        /// File to write, or directory with --per-object (default: standard output)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Comma-separated columns (default: time to yaw and every numeric property)
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
+        /// Write one file per object, named after its ID
+        #[arg(long, requires = "output")]
+        per_object: bool,
+    },
+    /// Write object tracks as GeoJSON LineStrings
+    Geojson(TrackArgs),
+    /// Write object tracks as KML gx:Tracks
+    Kml(TrackArgs),
+}
+
+/// Options of the track exports
+#[derive(clap::Args, Debug)]
+struct TrackArgs {
+    /// Recording to export
+    file: PathBuf,
+    /// File to write (default: standard output)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Only export objects whose Type has these tags, e.g. Air or Sea+Watercraft
+    #[arg(long = "type", value_delimiter = ',')]
+    types: Vec<String>,
+    /// Drop points closer than this many meters to the simplified track
+    #[arg(long, default_value_t = 0.0)]
+    simplify: f64,
+}
+
+impl TrackArgs {
+    /// Read the tracks of the recording, filtered and simplified
+    fn tracks(&self) -> Result<Tracks> {
+        let mut tracks = Tracks::from_acmi(&read_recording(&self.file)?);
+        tracks.filter_types(&self.types);
+        tracks.simplify(self.simplify);
+        Ok(tracks)
     }
 }
 
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print the effective configuration and where each option came from
+    Show,
+    /// Check the configuration file and exit with an error if it is invalid
+    Validate,
+}
+
+/// How often the processing pipeline's periodic work runs
+///
+/// Fast enough for dead reckoning at its highest output rate.
+const PIPELINE_TICK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(20);
+
+/// How often the configuration file is checked for changes
+const CONFIG_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Handle for changing the log filter after startup
+type LogHandle = reload::Handle<EnvFilter, tracing_subscriber::Registry>;
+
+/// Build the log filter for a level, unless RUST_LOG overrides it
+fn log_filter(level: &str) -> EnvFilter {
+    EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| format!("stormworks_tacview={level}").into())
+}
+
 /// Initialize logging
-fn init_logging(verbose: bool) {
-    let filter = if verbose {
-        "stormworks_tacview=debug"
+///
+/// Logging starts at the info level so that configuration loading can be
+/// reported; the configured level is applied through the returned handle.
+/// Subcommands log to stderr to keep their stdout output clean.
+fn init_logging(to_stderr: bool) -> LogHandle {
+    let (filter, handle) = reload::Layer::new(log_filter("info"));
+    let fmt_layer = if to_stderr {
+        tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .boxed()
     } else {
-        "stormworks_tacview=info"
+        tracing_subscriber::fmt::layer().boxed()
     };
 
     tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| filter.into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
+        .with(filter)
+        .with(fmt_layer)
         .init();
+
+    handle
+}
+
+/// Command line overrides, with `--verbose` folded into the log level
+fn config_overrides(args: &Args) -> ConfigOverrides {
+    let mut overrides = args.overrides.clone();
+    if args.verbose && overrides.log_level.is_none() {
+        overrides.log_level = Some("debug".to_string());
+    }
+    overrides
+}
+
+/// Load the layered configuration and apply its log level
+fn load_config(args: &Args, log_handle: &LogHandle) -> LoadedConfig {
+    let loaded = AppConfig::load_layered(args.config.as_deref(), &config_overrides(args));
+    if let Err(e) = log_handle.reload(log_filter(&loaded.config.log_level)) {
+        warn!("Failed to apply log level: {}", e);
+    }
+
+    loaded
 }
 
 /// Initialize application state with default repositories
-async fn init_app_state(verbose: bool) -> Arc<AppState> {
-    // Load configuration
-    let config = AppConfig::load();
+async fn init_app_state(loaded: &LoadedConfig) -> Arc<AppState> {
+    let config = loaded.config.clone();
 
     // Ensure output directory exists
     if let Err(e) = config.ensure_output_dir() {
         warn!("Failed to ensure output directory: {}", e);
     }
 
-    let state = Arc::new(AppState::new_with_verbose(verbose));
+    let mut state = AppState::new_with_verbose(config.is_verbose());
+    state.config_path = loaded.path.clone();
+    let websocket_feed = Arc::new(WebSocketFeedRepository::new());
+    state.websocket_feed = Some(websocket_feed.clone());
+    let csv_export = Arc::new(CsvAcmiRepository::new_with_config(config.clone()));
+    state.csv_export = Some(csv_export.clone());
+    let state = Arc::new(state);
 
     // Add file-based ACMI repository with configuration
-    let file_repo = Arc::new(FileAcmiRepository::new_with_config(config));
+    let file_repo = Arc::new(FileAcmiRepository::new_with_config(config.clone()));
     {
         let mut file_repos = state.file_repositories.lock().await;
         file_repos.push(file_repo.clone());
     }
 
+    state.apply_config(&config).await;
+    state
+        .add_output(file_repo as Arc<dyn AcmiRepository>, None)
+        .await;
+    state
+        .add_output(websocket_feed as Arc<dyn AcmiRepository>, None)
+        .await;
+    state
+        .add_output(csv_export as Arc<dyn AcmiRepository>, None)
+        .await;
+    state
+}
+
+/// Wait for Ctrl+C, or SIGTERM from service managers such as systemd and Docker
+async fn shutdown_signal() {
+    #[cfg(unix)]
     {
-        let mut acmi_repos = state.acmi_repositories.lock().await;
-        acmi_repos.push(file_repo as Arc<dyn AcmiRepository>);
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut sigterm =
+            signal(SignalKind::terminate()).expect("Failed to install SIGTERM handler");
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => {
+                result.expect("Failed to install CTRL+C signal handler");
+                info!("Received Ctrl+C");
+            }
+            _ = sigterm.recv() => info!("Received SIGTERM"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install CTRL+C signal handler");
+        info!("Received Ctrl+C");
     }
+}
 
-    state
+/// Describe why a server task ended
+fn server_error(result: Result<Result<()>, tokio::task::JoinError>) -> String {
+    match result {
+        Ok(Ok(())) => "stopped".to_string(),
+        Ok(Err(e)) => e.to_string(),
+        Err(e) => e.to_string(),
+    }
+}
+
+/// Report every problem with the configuration file
+fn validate_config(loaded: &LoadedConfig) -> Result<()> {
+    let path = loaded
+        .path
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("Unable to determine config directory"))?;
+
+    let errors = AppConfig::validate_file(path);
+    for e in &errors {
+        println!("{e}");
+    }
+
+    if errors.is_empty() {
+        println!("{}: configuration is valid", path.display());
+        Ok(())
+    } else {
+        anyhow::bail!("{} configuration error(s) found", errors.len())
+    }
+}
+
+/// Write a recording produced by a subcommand, as plain text for `.txt.acmi`
+fn save_recording(path: &Path, acmi: &str) -> Result<()> {
+    if path.to_string_lossy().ends_with(".txt.acmi") {
+        std::fs::write(path, acmi).with_context(|| format!("Failed to write {path:?}"))?;
+    } else {
+        write_recording(path, acmi.as_bytes())?;
+    }
+    println!("{}: {} bytes", path.display(), acmi.len());
+    Ok(())
+}
+
+/// Export a recording in the requested format
+fn export(format: &ExportCommand) -> Result<()> {
+    match format {
+        ExportCommand::Csv {
+            file,
+            output,
+            columns,
+            per_object,
+        } => {
+            let acmi = read_recording(file)?;
+            let columns = csv::columns_for(&acmi, columns);
+            let tables = csv::to_csv(&acmi, columns, *per_object);
+            csv::write_csv(&tables, output.as_deref())
+        }
+        ExportCommand::Geojson(args) => {
+            let geojson = serde_json::to_string_pretty(&to_geojson(&args.tracks()?))?;
+            write_output(args.output.as_deref(), &format!("{geojson}\n"))
+        }
+        ExportCommand::Kml(args) => write_output(args.output.as_deref(), &to_kml(&args.tracks()?)),
+    }
+}
+
+/// Write text to a file, or to standard output when there is none
+fn write_output(path: Option<&Path>, text: &str) -> Result<()> {
+    match path {
+        Some(path) => {
+            std::fs::write(path, text).with_context(|| format!("Failed to write {path:?}"))
+        }
+        None => {
+            print!("{text}");
+            Ok(())
+        }
+    }
+}
+
+/// Address to connect to for a server bound to `addr`, using loopback for
+/// servers bound to every interface
+fn local_addr(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        ip if ip.is_unspecified() && ip.is_ipv4() => {
+            SocketAddr::new(std::net::Ipv4Addr::LOCALHOST.into(), addr.port())
+        }
+        ip if ip.is_unspecified() => {
+            SocketAddr::new(std::net::Ipv6Addr::LOCALHOST.into(), addr.port())
+        }
+        _ => addr,
+    }
+}
+
+/// Print the violations in each recording, failing if any are found
+fn validate_recordings(files: &[PathBuf]) -> Result<()> {
+    let mut total = 0;
+    for path in files {
+        let violations = validate(&read_recording(path)?);
+        for violation in &violations {
+            println!(
+                "{}:{}: {}",
+                path.display(),
+                violation.line,
+                violation.message
+            );
+        }
+        if violations.is_empty() {
+            println!("{}: valid", path.display());
+        }
+        total += violations.len();
+    }
+
+    if total == 0 {
+        Ok(())
+    } else {
+        anyhow::bail!("{} ACMI violation(s) found", total)
+    }
+}
+
+/// Watch the configuration file and apply changes that are safe at runtime
+///
+/// Invalid edits are logged and the configuration in effect is kept.
+fn spawn_config_watcher(mut watcher: ConfigWatcher, state: Arc<AppState>, log_handle: LogHandle) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(CONFIG_POLL_INTERVAL).await;
+
+            let changes = match watcher.poll() {
+                Ok(changes) if changes.is_empty() => continue,
+                Ok(changes) => changes,
+                Err(e) => {
+                    warn!(
+                        "Rejected configuration change, keeping current configuration: {:#}",
+                        e
+                    );
+                    continue;
+                }
+            };
+
+            for change in &changes {
+                if change.requires_restart() {
+                    warn!(
+                        "Configuration changed (takes effect on restart): {}",
+                        change
+                    );
+                } else {
+                    info!("Configuration changed: {}", change);
+                }
+            }
+
+            let config = watcher.current();
+            if changes.iter().any(|change| change.key == "log_level") {
+                if let Err(e) = log_handle.reload(log_filter(&config.log_level)) {
+                    warn!("Failed to apply log level: {}", e);
+                }
+            }
+            state.apply_config(config).await;
+        }
+    });
 }
 
 /// Main application entry point
@@ -89,79 +467,232 @@ async fn init_app_state(verbose: bool) -> Arc<AppState> {
 async fn main() -> Result<()> {
     // Parse command line arguments
     let args = Args::parse();
-    let config = Config::from(args);
 
-    // Initialize logging
-    init_logging(config.verbose);
+    // Initialize logging and configuration
+    let log_handle = init_logging(args.command.is_some());
+    let loaded = load_config(&args, &log_handle);
+
+    match &args.command {
+        Some(Command::Config { action }) => {
+            match action {
+                ConfigCommand::Show => print!("{}", loaded.render()),
+                ConfigCommand::Validate => validate_config(&loaded)?,
+            }
+            return Ok(());
+        }
+        Some(Command::Inspect { file, json, gap }) => {
+            let inspection = Inspection::from_file(file, *gap)?;
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&inspection)?);
+            } else {
+                print!("{inspection}");
+            }
+            return Ok(());
+        }
+        Some(Command::Cut {
+            file,
+            from,
+            to,
+            output,
+        }) => {
+            let acmi = cut(&read_recording(file)?, *from, *to)?;
+            save_recording(output, &acmi)?;
+            return Ok(());
+        }
+        Some(Command::Merge { files, output }) => {
+            let recordings = files
+                .iter()
+                .map(|file| read_recording(file))
+                .collect::<Result<Vec<_>>>()?;
+            save_recording(output, &merge(&recordings)?)?;
+            return Ok(());
+        }
+        Some(Command::Import {
+            file,
+            output,
+            recenter,
+        }) => {
+            save_recording(output, &import(&read_recording(file)?, *recenter)?)?;
+            return Ok(());
+        }
+        Some(Command::Export { format }) => {
+            export(format)?;
+            return Ok(());
+        }
+        Some(Command::Validate { files }) => {
+            validate_recordings(files)?;
+            return Ok(());
+        }
+        Some(Command::Simulate {
+            target,
+            aircraft,
+            ships,
+            rate,
+            duration,
+            record,
+            json,
+        }) => {
+            let options = SimulationOptions {
+                target: target.unwrap_or_else(|| local_addr(loaded.config.http_addr())),
+                aircraft: *aircraft,
+                ships: *ships,
+                rate: *rate,
+                duration: std::time::Duration::try_from_secs_f64(*duration)
+                    .context("the duration must be a positive number of seconds")?,
+                record: *record,
+            };
+            let report = simulate(&options).await?;
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{report}");
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
+    if args.strict_config && !loaded.errors.is_empty() {
+        for e in &loaded.errors {
+            error!("Invalid configuration: {}", e);
+        }
+        anyhow::bail!(
+            "refusing to start with {} configuration error(s) (--strict-config)",
+            loaded.errors.len()
+        );
+    }
+
+    let config = loaded.config.clone();
 
     info!(
         "Starting Stormworks-Tacview Bridge v{}",
         env!("CARGO_PKG_VERSION")
     );
-    if config.verbose {
+    if config.is_verbose() {
         info!("Verbose logging enabled");
     }
 
     // Initialize application state
-    let state = init_app_state(config.verbose).await;
+    let state = init_app_state(&loaded).await;
 
-    // Create servers
-    let http_server = HttpServer::new(state.clone());
-    let tcp_server = TcpServer::new(state.clone());
+    // Reload the configuration file when it changes
+    if let Some(path) = loaded.path.clone() {
+        let watcher = ConfigWatcher::new(path, config_overrides(&args), config.clone());
+        spawn_config_watcher(watcher, state.clone(), log_handle.clone());
+    }
 
-    // Start servers concurrently
-    let http_handle = {
-        let http_server = http_server;
+    // Run periodic pipeline processing such as removing stale objects and
+    // dead reckoning
+    {
+        let state = state.clone();
         tokio::spawn(async move {
-            if let Err(e) = http_server.start(config.http_port).await {
-                error!("HTTP server failed: {}", e);
+            let mut interval = tokio::time::interval(PIPELINE_TICK_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                state.tick().await;
             }
-        })
-    };
+        });
+    }
 
-    let tcp_handle = {
-        let tcp_server = tcp_server;
+    // Push live feed updates to WebSocket clients at the configured rate
+    if let Some(feed) = state.websocket_feed.clone() {
+        let state = state.clone();
         tokio::spawn(async move {
-            if let Err(e) = tcp_server.start(config.tcp_port).await {
-                error!("TCP server failed: {}", e);
+            let mut period = state.ws_interval();
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                feed.step();
+
+                let current = state.ws_interval();
+                if current != period {
+                    period = current;
+                    interval = tokio::time::interval(period);
+                    interval.tick().await;
+                }
             }
-        })
-    };
+        });
+    }
 
-    // Handle graceful shutdown
-    let shutdown_signal = async {
-        tokio::signal::ctrl_c()
-            .await
-            .expect("Failed to install CTRL+C signal handler");
+    // Bind every server before running any, so a port in use fails startup
+    let http_server = HttpServer::bind(config.http_addr(), state.clone()).await?;
+    let tcp_server = TcpServer::bind(config.tcp_addr(), state.clone()).await?;
+    let udp_server = match config.udp_addr() {
+        Some(addr) => Some(UdpServer::bind(addr, state.clone()).await?),
+        None => None,
+    };
+    *state.ports.lock().unwrap() = ServerPorts {
+        http: Some(http_server.local_addr().port()),
+        tcp: Some(tcp_server.local_addr().port()),
+        udp: udp_server.as_ref().map(|server| server.local_addr().port()),
     };
 
+    // Run servers concurrently until shutdown
+    let shutdown = ShutdownToken::new();
+    let mut http_handle = tokio::spawn(http_server.run(shutdown.clone()));
+    let mut tcp_handle = tokio::spawn(tcp_server.run(shutdown.clone()));
+    let mut udp_handle = udp_server.map(|server| tokio::spawn(server.run(shutdown.clone())));
+
+    let mut failures = 0;
     tokio::select! {
-        _ = shutdown_signal => {
-            info!("Received shutdown signal, stopping servers...");
+        _ = shutdown_signal() => {}
+        result = &mut http_handle => {
+            error!("HTTP server terminated unexpectedly: {}", server_error(result));
+            failures += 1;
         }
-        _ = http_handle => {
-            error!("HTTP server terminated unexpectedly");
+        result = &mut tcp_handle => {
+            error!("TCP server terminated unexpectedly: {}", server_error(result));
+            failures += 1;
         }
-        _ = tcp_handle => {
-            error!("TCP server terminated unexpectedly");
+        result = async {
+            match udp_handle.as_mut() {
+                Some(handle) => handle.await,
+                None => std::future::pending().await,
+            }
+        } => {
+            error!("UDP server terminated unexpectedly: {}", server_error(result));
+            failures += 1;
         }
     }
 
-    // Gracefully stop all active ACMI recordings before exit
-    info!("Stopping active ACMI recordings...");
-    {
-        let file_repos = state.file_repositories.lock().await;
-        for repo in file_repos.iter() {
-            if repo.is_recording() {
-                if let Err(e) = repo.stop().await {
-                    error!("Failed to stop ACMI recording: {}", e);
-                } else {
-                    info!("Successfully stopped ACMI recording");
-                }
+    // Stop accepting connections and let requests being answered finish
+    info!("Shutting down...");
+    shutdown.cancel();
+    let timeout = config.shutdown_timeout();
+    let servers = [
+        ("HTTP", Some(http_handle)),
+        ("TCP", Some(tcp_handle)),
+        ("UDP", udp_handle),
+    ];
+    for (name, handle) in servers {
+        // A server that already ended was awaited above
+        let Some(mut handle) = handle.filter(|handle| !handle.is_finished()) else {
+            continue;
+        };
+        match tokio::time::timeout(timeout, &mut handle).await {
+            Ok(Ok(Ok(()))) => {}
+            Ok(result) => {
+                error!("{} server failed: {}", name, server_error(result));
+                failures += 1;
+            }
+            Err(_) => {
+                error!("{} server did not stop within {:?}", name, timeout);
+                handle.abort();
+                failures += 1;
             }
         }
     }
 
+    // Flush outputs, close Tacview clients and finalize recordings
+    if let Err(e) = state.shutdown(timeout).await {
+        error!("{}", e);
+        failures += 1;
+    }
+
+    if failures > 0 {
+        anyhow::bail!("stopped with {failures} failure(s)");
+    }
     info!("Stormworks-Tacview Bridge stopped");
     Ok(())
 }
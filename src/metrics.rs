@@ -0,0 +1,306 @@
+//! Runtime metrics
+//!
+//! Counters and histograms describing the bridge's traffic, rendered in the
+//! Prometheus text exposition format for `GET /metrics`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Upper bounds in seconds of the write latency histogram buckets
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.00005, 0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5,
+];
+
+/// Counters describing the health of the UDP ingest stream
+#[derive(Debug, Default)]
+pub struct UdpStats {
+    /// Datagrams received from the socket
+    pub received: AtomicU64,
+    /// Datagrams written to the repositories
+    pub forwarded: AtomicU64,
+    /// Datagrams never received, detected from gaps in the sequence numbers
+    pub lost: AtomicU64,
+    /// Datagrams that arrived after a later sequence number
+    pub reordered: AtomicU64,
+    /// Datagrams received but discarded (late, empty or undecodable)
+    pub dropped: AtomicU64,
+}
+
+/// Counter with one value per label
+#[derive(Debug, Default)]
+pub struct LabeledCounter {
+    values: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl LabeledCounter {
+    /// Add to the counter for a label, returning the new value
+    pub fn add(&self, label: &'static str, amount: u64) -> u64 {
+        let mut values = self.values.lock().unwrap();
+        let value = values.entry(label).or_default();
+        *value += amount;
+        *value
+    }
+
+    /// Increment the counter for a label, returning the new value
+    pub fn inc(&self, label: &'static str) -> u64 {
+        self.add(label, 1)
+    }
+
+    /// Current value for a label
+    pub fn get(&self, label: &str) -> u64 {
+        self.values.lock().unwrap().get(label).copied().unwrap_or(0)
+    }
+
+    fn snapshot(&self) -> BTreeMap<&'static str, u64> {
+        self.values.lock().unwrap().clone()
+    }
+}
+
+/// Latency histogram with fixed buckets
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+/// Values sampled from the application state when metrics are rendered
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeGauges {
+    /// Whether an ACMI recording is in progress
+    pub recording: bool,
+    /// Size in bytes of the current recording, before compression
+    pub recording_file_bytes: u64,
+    /// Number of registered ACMI repositories
+    pub acmi_repositories: usize,
+    /// Number of connected WebSocket feed subscribers
+    pub websocket_clients: usize,
+}
+
+/// Metrics shared across the servers and repositories
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// ACMI frames received from Stormworks, by source (`http`, `udp`)
+    pub frames_received: LabeledCounter,
+    /// Frames that could not be decoded, by source
+    pub decode_failures: LabeledCounter,
+    /// ACMI violations found in frames from Stormworks, by kind
+    pub acmi_violations: LabeledCounter,
+    /// Bytes successfully written, by repository name
+    pub bytes_written: LabeledCounter,
+    /// Failed writes, by repository name
+    pub write_errors: LabeledCounter,
+    /// Currently connected Tacview clients
+    pub tacview_clients: AtomicU64,
+    /// UDP ingest counters
+    pub udp: Arc<UdpStats>,
+    write_latency: Mutex<BTreeMap<&'static str, Histogram>>,
+}
+
+impl Metrics {
+    /// Create an empty set of metrics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the outcome of a single repository write
+    pub fn record_write(
+        &self,
+        repository: &'static str,
+        bytes: usize,
+        elapsed: Duration,
+        ok: bool,
+    ) {
+        if ok {
+            self.bytes_written.add(repository, bytes as u64);
+        } else {
+            self.write_errors.inc(repository);
+        }
+
+        self.write_latency
+            .lock()
+            .unwrap()
+            .entry(repository)
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    pub fn render(&self, gauges: &RuntimeGauges) -> String {
+        let mut out = String::new();
+
+        write_counter(
+            &mut out,
+            "stormworks_tacview_frames_received_total",
+            "ACMI frames received from Stormworks.",
+            "source",
+            &self.frames_received.snapshot(),
+        );
+        write_counter(
+            &mut out,
+            "stormworks_tacview_decode_failures_total",
+            "ACMI frames that could not be decoded.",
+            "source",
+            &self.decode_failures.snapshot(),
+        );
+        write_counter(
+            &mut out,
+            "stormworks_tacview_acmi_violations_total",
+            "ACMI violations in frames from Stormworks, when validation is enabled.",
+            "kind",
+            &self.acmi_violations.snapshot(),
+        );
+        write_counter(
+            &mut out,
+            "stormworks_tacview_bytes_written_total",
+            "Bytes written to ACMI repositories.",
+            "sink",
+            &self.bytes_written.snapshot(),
+        );
+        write_counter(
+            &mut out,
+            "stormworks_tacview_write_errors_total",
+            "Failed writes to ACMI repositories.",
+            "sink",
+            &self.write_errors.snapshot(),
+        );
+
+        let udp = [
+            ("received", self.udp.received.load(Ordering::Relaxed)),
+            ("forwarded", self.udp.forwarded.load(Ordering::Relaxed)),
+            ("lost", self.udp.lost.load(Ordering::Relaxed)),
+            ("reordered", self.udp.reordered.load(Ordering::Relaxed)),
+            ("dropped", self.udp.dropped.load(Ordering::Relaxed)),
+        ];
+        write_counter(
+            &mut out,
+            "stormworks_tacview_udp_datagrams_total",
+            "UDP ingest datagrams by outcome.",
+            "outcome",
+            &udp.into_iter().collect(),
+        );
+
+        write_gauge(
+            &mut out,
+            "stormworks_tacview_tacview_clients",
+            "Connected Tacview clients.",
+            self.tacview_clients.load(Ordering::Relaxed) as f64,
+        );
+        write_gauge(
+            &mut out,
+            "stormworks_tacview_websocket_clients",
+            "Connected WebSocket feed clients.",
+            gauges.websocket_clients as f64,
+        );
+        write_gauge(
+            &mut out,
+            "stormworks_tacview_acmi_repositories",
+            "Registered ACMI repositories.",
+            gauges.acmi_repositories as f64,
+        );
+        write_gauge(
+            &mut out,
+            "stormworks_tacview_recording",
+            "Whether an ACMI recording is in progress.",
+            if gauges.recording { 1.0 } else { 0.0 },
+        );
+        write_gauge(
+            &mut out,
+            "stormworks_tacview_recording_file_bytes",
+            "Uncompressed size of the current ACMI recording.",
+            gauges.recording_file_bytes as f64,
+        );
+
+        let latency = self.write_latency.lock().unwrap().clone();
+        let name = "stormworks_tacview_write_duration_seconds";
+        let _ = writeln!(
+            out,
+            "# HELP {name} Time spent writing to ACMI repositories."
+        );
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        for (sink, histogram) in &latency {
+            for (count, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "{name}_bucket{{sink=\"{sink}\",le=\"{bound}\"}} {count}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{sink=\"{sink}\",le=\"+Inf\"}} {}",
+                histogram.count
+            );
+            let _ = writeln!(out, "{name}_sum{{sink=\"{sink}\"}} {}", histogram.sum);
+            let _ = writeln!(out, "{name}_count{{sink=\"{sink}\"}} {}", histogram.count);
+        }
+
+        out
+    }
+}
+
+fn write_counter(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    label: &str,
+    values: &BTreeMap<&'static str, u64>,
+) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} counter");
+    for (value_label, value) in values {
+        let _ = writeln!(out, "{name}{{{label}=\"{value_label}\"}} {value}");
+    }
+}
+
+fn write_gauge(out: &mut String, name: &str, help: &str, value: f64) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    let _ = writeln!(out, "{name} {value}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_metrics() {
+        let metrics = Metrics::new();
+        assert_eq!(metrics.frames_received.inc("http"), 1);
+        assert_eq!(metrics.frames_received.inc("http"), 2);
+        metrics.record_write("file", 100, Duration::from_micros(80), true);
+        metrics.record_write("file", 50, Duration::from_millis(2), false);
+
+        let text = metrics.render(&RuntimeGauges {
+            recording: true,
+            recording_file_bytes: 1234,
+            ..RuntimeGauges::default()
+        });
+
+        assert!(text.contains("stormworks_tacview_frames_received_total{source=\"http\"} 2\n"));
+        assert!(text.contains("stormworks_tacview_bytes_written_total{sink=\"file\"} 100\n"));
+        assert!(text.contains("stormworks_tacview_write_errors_total{sink=\"file\"} 1\n"));
+        assert!(text.contains("stormworks_tacview_recording 1\n"));
+        assert!(text.contains("stormworks_tacview_recording_file_bytes 1234\n"));
+        assert!(text.contains(
+            "stormworks_tacview_write_duration_seconds_bucket{sink=\"file\",le=\"0.0001\"} 1\n"
+        ));
+        assert!(text.contains(
+            "stormworks_tacview_write_duration_seconds_bucket{sink=\"file\",le=\"+Inf\"} 2\n"
+        ));
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::acmi::{AcmiRecord, ObjectUpdate, Transform};
+use crate::config::AppConfig;
+use crate::pipeline::Stage;
+
+/// Objects slower than this in degrees or meters per second are not extrapolated
+const MIN_SPEED: f64 = 1e-9;
+
+/// Extrapolates object positions between updates for live viewers
+///
+/// When the addon throttles updates, objects jump in Tacview from one
+/// position to the next. This stage estimates each object's velocity from
+/// its last two positions and, between updates, sends predicted positions
+/// to live outputs at a fixed rate. Recordings only ever receive the real
+/// updates. Extrapolation stops once an object has gone without updates for
+/// the configured maximum time.
+///
+/// Predicted time frames advance at the pace the addon's clock has shown
+/// recently, so they stay behind the next real frame when Stormworks runs
+/// slower than real time. Should a real frame still arrive earlier than a
+/// prediction already sent, its time is held at the predicted time.
+#[derive(Debug, Default)]
+pub struct DeadReckoningStage {
+    interval: Option<Duration>,
+    max_extrapolation: f64,
+    /// Latest time frame and when the batch containing it arrived
+    last_frame: Option<(f64, Instant)>,
+    /// Addon seconds per wall clock second between the last two batches, at most 1
+    pace: f64,
+    /// Latest predicted time frame sent to live outputs
+    last_predicted: Option<f64>,
+    next_output: Option<Instant>,
+    tracks: HashMap<u64, Track>,
+}
+
+/// Motion of a single object
+#[derive(Debug, Clone, Default)]
+struct Track {
+    /// Time frame and position (longitude, latitude, altitude) of the latest update
+    last: Option<(f64, [f64; 3])>,
+    /// Change of each position component per second
+    velocity: Option<[f64; 3]>,
+    /// Number of components the addon sends in `T=`, kept in the output
+    components: usize,
+    /// Accumulated position, since components may be omitted when unchanged
+    transform: Transform,
+}
+
+impl DeadReckoningStage {
+    /// Create a disabled stage, see [`Stage::configure`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn observe(&mut self, update: &ObjectUpdate, time: f64) {
+        let Some(value) = update.property("T") else {
+            return;
+        };
+        let Ok(transform) = Transform::parse(value) else {
+            return;
+        };
+
+        let track = self.tracks.entry(update.id).or_default();
+        track.transform.merge(&transform);
+        track.components = value.split('|').count();
+
+        let t = &track.transform;
+        let (Some(lon), Some(lat), Some(alt)) = (t.longitude, t.latitude, t.altitude) else {
+            return;
+        };
+        let position = [lon, lat, alt];
+
+        match track.last {
+            Some((last_time, last)) if time > last_time => {
+                let dt = time - last_time;
+                track.velocity = Some([
+                    (position[0] - last[0]) / dt,
+                    (position[1] - last[1]) / dt,
+                    (position[2] - last[2]) / dt,
+                ]);
+            }
+            Some((last_time, _)) if time == last_time => {}
+            _ => track.velocity = None,
+        }
+        track.last = Some((time, position));
+    }
+}
+
+impl Stage for DeadReckoningStage {
+    fn name(&self) -> &'static str {
+        "dead_reckoning"
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.interval.is_some()
+    }
+
+    fn configure(&mut self, config: &AppConfig) {
+        self.interval = (config.dead_reckoning_rate > 0.0)
+            .then(|| Duration::from_secs_f64(1.0 / config.dead_reckoning_rate));
+        self.max_extrapolation = config.dead_reckoning_max_secs;
+        if self.interval.is_none() {
+            self.tracks.clear();
+            self.last_frame = None;
+            self.last_predicted = None;
+        }
+    }
+
+    fn process(&mut self, records: Vec<AcmiRecord>, now: Instant) -> Vec<AcmiRecord> {
+        let previous = self.last_frame;
+        let mut time = previous.map_or(0.0, |(time, _)| time);
+        let mut output = Vec::with_capacity(records.len());
+        for record in records {
+            match &record {
+                AcmiRecord::TimeFrame(t) => {
+                    time = *t;
+                    self.last_frame = Some((time, now));
+                    if let Some(predicted) = self.last_predicted {
+                        // Predictions run at most the maximum extrapolation ahead
+                        // of a real frame, so anything further back is a new session
+                        if time < predicted - self.max_extrapolation {
+                            self.last_predicted = None;
+                        } else if time < predicted {
+                            output.push(AcmiRecord::TimeFrame(predicted));
+                            continue;
+                        }
+                    }
+                }
+                AcmiRecord::Update(update) if !update.is_global() => self.observe(update, time),
+                AcmiRecord::Removal(id) => {
+                    self.tracks.remove(id);
+                }
+                _ => {}
+            }
+            output.push(record);
+        }
+
+        if let (Some((before, sent)), Some((after, arrived))) = (previous, self.last_frame) {
+            let wall = arrived.saturating_duration_since(sent).as_secs_f64();
+            if wall > 0.0 {
+                self.pace = ((after - before) / wall).clamp(0.0, 1.0);
+            }
+        }
+
+        // Real data was just sent, wait a full interval before predicting
+        if let Some(interval) = self.interval {
+            self.next_output = Some(now + interval);
+        }
+        output
+    }
+
+    fn live_tick(&mut self, now: Instant) -> Vec<AcmiRecord> {
+        let (Some(interval), Some((frame_time, arrived))) = (self.interval, self.last_frame) else {
+            return Vec::new();
+        };
+        if self.next_output.is_some_and(|next| now < next) {
+            return Vec::new();
+        }
+        self.next_output = Some(now + interval);
+
+        let elapsed = now.saturating_duration_since(arrived).as_secs_f64();
+        if elapsed > self.max_extrapolation {
+            return Vec::new();
+        }
+        let time = ((frame_time + elapsed * self.pace) * 1000.0).round() / 1000.0;
+        if self
+            .last_predicted
+            .is_some_and(|predicted| time <= predicted)
+        {
+            return Vec::new();
+        }
+
+        let mut ids: Vec<&u64> = self.tracks.keys().collect();
+        ids.sort_unstable();
+
+        let mut records = Vec::new();
+        for id in ids {
+            let track = &self.tracks[id];
+            let (Some((last_time, position)), Some(velocity)) = (track.last, track.velocity) else {
+                continue;
+            };
+            let ahead = time - last_time;
+            if ahead <= 0.0
+                || ahead > self.max_extrapolation
+                || velocity.iter().all(|v| v.abs() < MIN_SPEED)
+            {
+                continue;
+            }
+
+            let mut value = (0..3)
+                .map(|i| {
+                    let scale = if i < 2 { 1e7 } else { 100.0 };
+                    (((position[i] + velocity[i] * ahead) * scale).round() / scale).to_string()
+                })
+                .collect::<Vec<_>>()
+                .join("|");
+            for _ in 3..track.components {
+                value.push('|');
+            }
+            records.push(AcmiRecord::Update(ObjectUpdate::new(*id).with("T", value)));
+        }
+
+        if !records.is_empty() {
+            records.insert(0, AcmiRecord::TimeFrame(time));
+            self.last_predicted = Some(time);
+        }
+        records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acmi::parse_records;
+
+    fn lines(records: &[AcmiRecord]) -> Vec<String> {
+        records.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_extrapolates_between_updates() {
+        let mut stage = DeadReckoningStage::new();
+        stage.configure(&AppConfig {
+            dead_reckoning_rate: 10.0,
+            dead_reckoning_max_secs: 0.5,
+            ..AppConfig::default()
+        });
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        stage.process(parse_records("#10\n1,T=0|0|100|0|0|90\n2,T=5|5|0\n"), at(0));
+        let records = stage.process(
+            parse_records("#11\n1,T=0.001|0|110|||\n2,T=5|5|0\n"),
+            at(1000),
+        );
+
+        // Records pass through unchanged
+        assert_eq!(lines(&records), ["#11", "1,T=0.001|0|110|||", "2,T=5|5|0"]);
+
+        // Nothing until a full interval has passed since the real update
+        assert!(stage.live_tick(at(1050)).is_empty());
+        assert_eq!(
+            lines(&stage.live_tick(at(1200))),
+            ["#11.2", "1,T=0.0012|0|112|||"]
+        );
+        assert!(stage.live_tick(at(1250)).is_empty());
+
+        // Extrapolation stops after the maximum time without updates
+        assert_eq!(stage.live_tick(at(1400)).len(), 2);
+        assert!(stage.live_tick(at(1600)).is_empty());
+    }
+
+    #[test]
+    fn test_predictions_follow_a_lagging_addon() {
+        let mut stage = DeadReckoningStage::new();
+        stage.configure(&AppConfig {
+            dead_reckoning_rate: 10.0,
+            dead_reckoning_max_secs: 1.0,
+            ..AppConfig::default()
+        });
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        // The addon's clock advances half a second per second of real time
+        stage.process(parse_records("#10\n1,T=0|0|100\n"), at(0));
+        stage.process(parse_records("#10.5\n1,T=0|0|105\n"), at(1000));
+        assert_eq!(lines(&stage.live_tick(at(1200))), ["#10.6", "1,T=0|0|106"]);
+        assert_eq!(lines(&stage.live_tick(at(1400))), ["#10.7", "1,T=0|0|107"]);
+
+        // A real frame earlier than a prediction already sent is held at it
+        let records = stage.process(parse_records("#10.65\n1,T=0|0|106.5\n"), at(1500));
+        assert_eq!(lines(&records), ["#10.7", "1,T=0|0|106.5"]);
+        // Predictions never go back either
+        assert!(stage.live_tick(at(1600)).is_empty());
+
+        // Once the addon is ahead again its time frames pass through
+        let records = stage.process(parse_records("#10.8\n1,T=0|0|108\n"), at(1700));
+        assert_eq!(lines(&records), ["#10.8", "1,T=0|0|108"]);
+
+        // A clock reset starts over instead of holding the old time
+        let records = stage.process(parse_records("#0\n1,T=0|0|100\n"), at(1800));
+        assert_eq!(lines(&records), ["#0", "1,T=0|0|100"]);
+    }
+}
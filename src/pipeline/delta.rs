@@ -0,0 +1,269 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::acmi::{AcmiRecord, ObjectState, ObjectUpdate, Transform, WorldState};
+use crate::config::AppConfig;
+use crate::pipeline::Stage;
+
+/// Removes object properties that did not change since they were last sent
+///
+/// The addon resends every property of every object each tick. This stage
+/// keeps the state last written for each object and only forwards the
+/// properties whose value changed. Unchanged `T=` components are left empty,
+/// which Tacview reads as "keep the previous value".
+///
+/// A keyframe with the full state of every object is appended periodically,
+/// and after [`Stage::resync`] so that clients joining mid-session and new
+/// recordings get complete objects.
+#[derive(Debug, Default)]
+pub struct DeltaStage {
+    enabled: bool,
+    keyframe_interval: Duration,
+    next_keyframe: Option<Instant>,
+    /// A keyframe was requested for the next batch
+    resync: bool,
+    /// State as last written to the outputs
+    sent: WorldState,
+    /// Objects whose position live outputs received from another stage
+    diverged: HashSet<u64>,
+}
+
+impl DeltaStage {
+    /// Create a disabled stage, see [`Stage::configure`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reduce an update to the properties that changed
+    ///
+    /// Returns `None` when nothing changed.
+    fn strip(&self, update: &ObjectUpdate) -> Option<ObjectUpdate> {
+        let previous = self.sent.object(update.id);
+        let diverged = self.diverged.contains(&update.id);
+
+        let mut stripped = ObjectUpdate::new(update.id);
+        for (key, value) in &update.properties {
+            let Some(previous) = previous else {
+                stripped.properties.push((key.clone(), value.clone()));
+                continue;
+            };
+
+            if key == "T" {
+                let changed = if diverged {
+                    Some(value.clone())
+                } else {
+                    changed_components(&previous.transform, value)
+                };
+                if let Some(value) = changed {
+                    stripped.properties.push((key.clone(), value));
+                }
+            } else if previous.property(key) != Some(value.as_str()) {
+                stripped.properties.push((key.clone(), value.clone()));
+            }
+        }
+
+        (!stripped.properties.is_empty()).then_some(stripped)
+    }
+
+    /// Full state of every object, ordered by ID
+    fn keyframe(&self) -> Vec<AcmiRecord> {
+        let mut objects: Vec<&ObjectState> = self.sent.objects().collect();
+        objects.sort_unstable_by_key(|object| object.id);
+
+        objects
+            .into_iter()
+            .map(|object| AcmiRecord::Update(object.to_update()))
+            .collect()
+    }
+}
+
+/// The value for a `T=` property with unchanged components left empty
+///
+/// The layout of the incoming value is kept so the remaining components keep
+/// their meaning. Returns `None` when no component changed, or the value
+/// unchanged when it cannot be parsed.
+fn changed_components(previous: &Transform, value: &str) -> Option<String> {
+    let parts: Vec<&str> = value.split('|').collect();
+    let (Ok(current), Some(previous)) = (Transform::parse(value), layout(previous, parts.len()))
+    else {
+        return Some(value.to_string());
+    };
+    let current = layout(&current, parts.len())?;
+
+    let mut changed = false;
+    let stripped: Vec<&str> = parts
+        .iter()
+        .zip(current.iter().zip(&previous))
+        .map(|(part, (current, previous))| match current {
+            Some(_) if current != previous => {
+                changed = true;
+                part.trim()
+            }
+            _ => "",
+        })
+        .collect();
+
+    changed.then(|| stripped.join("|"))
+}
+
+/// Components of a transform in the order of the `T=` layout with `count` components
+fn layout(t: &Transform, count: usize) -> Option<Vec<Option<f64>>> {
+    Some(match count {
+        3 => vec![t.longitude, t.latitude, t.altitude],
+        5 => vec![t.longitude, t.latitude, t.altitude, t.u, t.v],
+        6 => vec![t.longitude, t.latitude, t.altitude, t.roll, t.pitch, t.yaw],
+        9 => vec![
+            t.longitude,
+            t.latitude,
+            t.altitude,
+            t.roll,
+            t.pitch,
+            t.yaw,
+            t.u,
+            t.v,
+            t.heading,
+        ],
+        _ => return None,
+    })
+}
+
+impl Stage for DeltaStage {
+    fn name(&self) -> &'static str {
+        "delta"
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn configure(&mut self, config: &AppConfig) {
+        let interval = Duration::from_secs_f64(config.delta_keyframe_secs);
+        if interval != self.keyframe_interval {
+            self.keyframe_interval = interval;
+            self.next_keyframe = None;
+        }
+        if config.delta_compression != self.enabled {
+            // Outputs have seen full updates while disabled, start from scratch
+            self.enabled = config.delta_compression;
+            self.sent = WorldState::new();
+            self.diverged.clear();
+        }
+    }
+
+    fn process(&mut self, records: Vec<AcmiRecord>, now: Instant) -> Vec<AcmiRecord> {
+        let mut output = Vec::with_capacity(records.len());
+        for record in records {
+            match &record {
+                AcmiRecord::Update(update) if !update.is_global() => {
+                    if let Some(stripped) = self.strip(update) {
+                        output.push(AcmiRecord::Update(stripped));
+                    }
+                    self.diverged.remove(&update.id);
+                    self.sent.apply(&record);
+                }
+                AcmiRecord::Removal(id) => {
+                    self.diverged.remove(id);
+                    self.sent.apply(&record);
+                    output.push(record);
+                }
+                _ => {
+                    self.sent.apply(&record);
+                    output.push(record);
+                }
+            }
+        }
+
+        // Objects are sent in full when first seen, so the first keyframe can wait
+        let next_keyframe = *self
+            .next_keyframe
+            .get_or_insert(now + self.keyframe_interval);
+        if self.resync || now >= next_keyframe {
+            debug!("Writing keyframe with {} objects", self.sent.object_count());
+            output.extend(self.keyframe());
+            self.next_keyframe = Some(now + self.keyframe_interval);
+            self.resync = false;
+        }
+        output
+    }
+
+    fn resync(&mut self) {
+        self.resync = true;
+    }
+
+    fn live_sent(&mut self, records: &[AcmiRecord]) {
+        for record in records {
+            if let AcmiRecord::Update(update) = record {
+                if update.property("T").is_some() {
+                    self.diverged.insert(update.id);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acmi::parse_records;
+
+    fn run(stage: &mut DeltaStage, acmi: &str, now: Instant) -> Vec<String> {
+        stage
+            .process(parse_records(acmi), now)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_strips_unchanged_properties() {
+        let mut stage = DeltaStage::new();
+        stage.configure(&AppConfig {
+            delta_compression: true,
+            delta_keyframe_secs: 10.0,
+            ..AppConfig::default()
+        });
+        let start = Instant::now();
+
+        // New objects are sent in full
+        assert_eq!(
+            run(
+                &mut stage,
+                "#1\n1,T=1|2|300|0|5|90,Name=Heli,Fuel=0.9\n",
+                start
+            ),
+            ["#1", "1,T=1|2|300|0|5|90,Name=Heli,Fuel=0.9"]
+        );
+
+        // Only changed properties and components remain
+        let later = start + Duration::from_secs(1);
+        assert_eq!(
+            run(
+                &mut stage,
+                "#2\n1,T=1|2.5|300.0|0|5|91,Name=Heli,Fuel=0.9\n1,T=1|2.5|300|0|5|91,Name=Heli\n-2\n",
+                later
+            ),
+            ["#2", "1,T=|2.5||||91", "-2"]
+        );
+
+        // After live outputs received other positions, T is sent in full
+        stage.live_sent(&parse_records("#2.5\n1,T=1|2.7|300\n"));
+        assert_eq!(
+            run(&mut stage, "#3\n1,T=1|2.5|300|0|5|91,Fuel=0.8\n", later),
+            ["#3", "1,T=1|2.5|300|0|5|91,Fuel=0.8"]
+        );
+
+        // A resync appends the full state of every object
+        stage.resync();
+        assert_eq!(
+            run(&mut stage, "#4\n1,Fuel=0.8\n", later),
+            ["#4", "1,T=1|2.5|300|0|5|91,Fuel=0.8,Name=Heli"]
+        );
+
+        // Keyframes also come periodically
+        assert_eq!(
+            run(&mut stage, "#15\n", start + Duration::from_secs(11)).len(),
+            2
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Instant;
+use tracing::debug;
+
+use crate::acmi::{AcmiRecord, ObjectState, ObjectUpdate, WorldState};
+use crate::config::{AppConfig, FlightThresholds};
+use crate::pipeline::Stage;
+
+/// Approximate length of one degree of latitude in meters
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// Speed in meters per second below which an object counts as standing still
+const STATIONARY_SPEED: f64 = 1.0;
+
+/// Derives Tacview flight events from object updates
+///
+/// The Stormworks addon does not emit `TakenOff`, `Landed` or `Destroyed`
+/// events, so they are detected here and injected right after the update
+/// that confirmed them:
+///
+/// - Takeoff and landing when an object's height above ground and speed
+///   (`TAS`, `IAS`, or the ground speed between updates) cross the thresholds
+///   for its Type. The height is the `AGL` property if sent. Otherwise it is
+///   the climb above the lowest altitude the object was seen at while slower
+///   than its minimum speed (or standing still, when that is 0), since the
+///   altitude is above sea level and runways and decks may be far above it
+/// - Destruction when an object is removed while airborne
+#[derive(Debug, Default)]
+pub struct FlightEventStage {
+    enabled: bool,
+    thresholds: BTreeMap<String, FlightThresholds>,
+    world: WorldState,
+    flights: HashMap<u64, FlightTrack>,
+}
+
+/// Airborne state of a single object
+#[derive(Debug, Clone, Default)]
+struct FlightTrack {
+    /// Last confirmed state, `None` until the first complete sample
+    airborne: Option<bool>,
+    /// Unconfirmed state change and the time it was first seen
+    pending: Option<(bool, f64)>,
+    /// Time and horizontal position in meters of the previous sample
+    last_sample: Option<(f64, f64, f64)>,
+    /// Lowest altitude seen while moving too slowly to fly, without `AGL`
+    ground_altitude: Option<f64>,
+}
+
+impl FlightEventStage {
+    /// Create a disabled stage, see [`Stage::configure`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Thresholds for an object, from the most specific matching Type entry
+    ///
+    /// An entry matches when every tag in its key is part of the object's
+    /// Type, so `Air+Rotorcraft` wins over `Air` for helicopters.
+    fn thresholds_for(&self, object: &ObjectState) -> Option<&FlightThresholds> {
+        self.thresholds
+            .iter()
+            .filter(|(types, _)| types.split('+').all(|tag| object.has_type_tag(tag)))
+            .max_by_key(|(types, _)| types.split('+').count())
+            .map(|(_, thresholds)| thresholds)
+    }
+
+    /// Update the flight state of an object, returning an event if one was confirmed
+    fn evaluate(&mut self, id: u64, speed_hint: Option<f64>) -> Option<&'static str> {
+        let time = self.world.time;
+        let object = self.world.object(id)?;
+        let thresholds = self.thresholds_for(object)?.clone();
+
+        let reference_lat = self.world.reference_latitude();
+        let t = object.transform;
+        let agl = object.property("AGL").and_then(|v| v.parse::<f64>().ok());
+        let altitude = t.altitude;
+        if agl.is_none() && altitude.is_none() {
+            return None;
+        }
+        let position = match (t.u, t.v, t.longitude, t.latitude) {
+            (Some(u), Some(v), _, _) => Some((u, v)),
+            (_, _, Some(lon), Some(lat)) => {
+                let lat_scale = (reference_lat + lat).to_radians().cos();
+                Some((lon * METERS_PER_DEGREE * lat_scale, lat * METERS_PER_DEGREE))
+            }
+            _ => None,
+        };
+
+        let track = self.flights.entry(id).or_default();
+        let ground_speed = match (track.last_sample, position) {
+            (Some((last_time, x0, y0)), Some((x, y))) if time > last_time => {
+                Some((x - x0).hypot(y - y0) / (time - last_time))
+            }
+            _ => None,
+        };
+        if let Some((x, y)) = position {
+            if track
+                .last_sample
+                .is_none_or(|(last_time, _, _)| time > last_time)
+            {
+                track.last_sample = Some((time, x, y));
+            }
+        }
+
+        let speed = match speed_hint.or(ground_speed) {
+            Some(speed) => speed,
+            None if thresholds.min_speed <= 0.0 => 0.0,
+            None => return None,
+        };
+        let height = match (agl, altitude) {
+            (Some(agl), _) => agl,
+            (None, Some(altitude)) => {
+                let slow = if thresholds.min_speed > 0.0 {
+                    speed < thresholds.min_speed
+                } else {
+                    speed < STATIONARY_SPEED
+                };
+                if slow {
+                    track.ground_altitude =
+                        Some(track.ground_altitude.map_or(altitude, |g| g.min(altitude)));
+                }
+                match (track.ground_altitude, track.airborne) {
+                    (Some(ground), _) => altitude - ground,
+                    // Not seen on the ground yet, so the first guess uses the
+                    // altitude and later samples keep it until it was
+                    (None, None) => altitude,
+                    (None, Some(_)) => return None,
+                }
+            }
+            (None, None) => return None,
+        };
+        let airborne = height >= thresholds.min_agl && speed >= thresholds.min_speed;
+
+        let Some(current) = track.airborne else {
+            // Objects spawned in the air or on the ground produce no event
+            track.airborne = Some(airborne);
+            return None;
+        };
+
+        if airborne == current {
+            track.pending = None;
+            return None;
+        }
+
+        let since = match track.pending {
+            Some((state, since)) if state == airborne => since,
+            _ => {
+                track.pending = Some((airborne, time));
+                time
+            }
+        };
+        if time - since < thresholds.settle_secs {
+            return None;
+        }
+
+        track.airborne = Some(airborne);
+        track.pending = None;
+        Some(if airborne { "TakenOff" } else { "Landed" })
+    }
+}
+
+/// Explicit speed property of an update, in meters per second
+fn reported_speed(update: &ObjectUpdate) -> Option<f64> {
+    update
+        .property("TAS")
+        .or_else(|| update.property("IAS"))
+        .and_then(|v| v.parse().ok())
+}
+
+/// Global event record for an object
+fn event(name: &str, id: u64) -> AcmiRecord {
+    AcmiRecord::Update(ObjectUpdate::event(name, &[id], ""))
+}
+
+/// Object ID an event record refers to, if it is an event of the given kind
+fn event_object(record: &AcmiRecord, name: &str) -> Option<u64> {
+    let AcmiRecord::Update(update) = record else {
+        return None;
+    };
+    let (kind, rest) = update.property("Event")?.split_once('|')?;
+    if kind != name || !update.is_global() {
+        return None;
+    }
+    u64::from_str_radix(rest.split('|').next()?, 16).ok()
+}
+
+impl Stage for FlightEventStage {
+    fn name(&self) -> &'static str {
+        "flight_events"
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn configure(&mut self, config: &AppConfig) {
+        self.enabled = config.flight_events;
+        self.thresholds = config.flight_event_thresholds.clone();
+        if !self.enabled {
+            self.world = WorldState::new();
+            self.flights.clear();
+        }
+    }
+
+    fn process(&mut self, records: Vec<AcmiRecord>, _now: Instant) -> Vec<AcmiRecord> {
+        // Objects that left the area were not destroyed
+        let left_area: HashSet<u64> = records
+            .iter()
+            .filter_map(|record| event_object(record, "LeftArea"))
+            .collect();
+
+        let mut output = Vec::with_capacity(records.len());
+        for record in records {
+            match &record {
+                AcmiRecord::Update(update) if !update.is_global() => {
+                    let id = update.id;
+                    let speed = reported_speed(update);
+                    self.world.apply(&record);
+                    output.push(record);
+
+                    if let Some(name) = self.evaluate(id, speed) {
+                        debug!("Object {:x} {}", id, name);
+                        output.push(event(name, id));
+                    }
+                }
+                AcmiRecord::Removal(id) => {
+                    let airborne = self
+                        .flights
+                        .remove(id)
+                        .and_then(|track| track.airborne)
+                        .unwrap_or(false);
+                    if airborne && !left_area.contains(id) {
+                        debug!("Object {:x} destroyed", id);
+                        output.push(event("Destroyed", *id));
+                    }
+                    self.world.apply(&record);
+                    output.push(record);
+                }
+                _ => {
+                    self.world.apply(&record);
+                    output.push(record);
+                }
+            }
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acmi::parse_records;
+
+    fn run(stage: &mut FlightEventStage, acmi: &str) -> Vec<String> {
+        stage
+            .process(parse_records(acmi), Instant::now())
+            .iter()
+            .map(ToString::to_string)
+            .filter(|line| line.starts_with("0,Event="))
+            .collect()
+    }
+
+    #[test]
+    fn test_takeoff_landing_and_destruction() {
+        let mut stage = FlightEventStage::new();
+        stage.configure(&AppConfig {
+            flight_events: true,
+            ..AppConfig::default()
+        });
+
+        // On the runway, then accelerating and climbing
+        assert!(run(&mut stage, "#0\n1,T=0|0|0,Type=Air+FixedWing\n").is_empty());
+        assert!(run(&mut stage, "#1\n1,T=0|0.0001|0\n").is_empty());
+        assert!(run(&mut stage, "#2\n1,T=0|0.0005|10\n").is_empty());
+        assert!(run(&mut stage, "#3\n1,T=0|0.0009|30\n").is_empty());
+        assert_eq!(
+            run(&mut stage, "#4\n1,T=0|0.0013|50\n"),
+            ["0,Event=TakenOff|1|"]
+        );
+
+        // Touching down and rolling to a stop, reported with TAS
+        assert!(run(&mut stage, "#10\n1,T=0|0.01|0,TAS=10\n").is_empty());
+        assert_eq!(
+            run(&mut stage, "#12\n1,T=0|0.0101|0,TAS=0\n"),
+            ["0,Event=Landed|1|"]
+        );
+
+        // Helicopter hovering with no speed is airborne
+        run(&mut stage, "#20\n2,T=0|0|0,Type=Air+Rotorcraft\n");
+        run(&mut stage, "#21\n2,T=0|0|3\n");
+        assert_eq!(run(&mut stage, "#23\n2,T=0|0|3\n"), ["0,Event=TakenOff|2|"]);
+        assert_eq!(run(&mut stage, "#24\n-2\n"), ["0,Event=Destroyed|2|"]);
+
+        // Ground vehicles have no thresholds and produce no events
+        run(&mut stage, "#30\n3,T=0|0|0,Type=Ground+Vehicle\n");
+        assert!(run(&mut stage, "#40\n3,T=0|0.1|100\n").is_empty());
+    }
+
+    #[test]
+    fn test_elevated_runway_without_agl() {
+        let mut stage = FlightEventStage::new();
+        stage.configure(&AppConfig {
+            flight_events: true,
+            ..AppConfig::default()
+        });
+
+        // Parked, then taxiing fast on a runway 60 m above sea level
+        run(&mut stage, "#0\n1,T=0|0|60,Type=Air+FixedWing\n");
+        run(&mut stage, "#1\n1,T=0|0|60\n");
+        for (i, lat) in ["0.0003", "0.0006", "0.0009", "0.0012"].iter().enumerate() {
+            let frame = format!("#{}\n1,T=0|{lat}|60\n", i + 2);
+            assert!(run(&mut stage, &frame).is_empty(), "{frame}");
+        }
+
+        // Climbing away from the runway is a takeoff
+        run(&mut stage, "#6\n1,T=0|0.0015|70\n");
+        run(&mut stage, "#7\n1,T=0|0.0018|80\n");
+        assert_eq!(
+            run(&mut stage, "#8\n1,T=0|0.0021|90\n"),
+            ["0,Event=TakenOff|1|"]
+        );
+
+        // An aircraft first seen flying is airborne until seen slow
+        run(&mut stage, "#10\n2,T=0|0|800,Type=Air+FixedWing\n");
+        run(&mut stage, "#11\n2,T=0|0.001|790\n");
+        assert!(run(&mut stage, "#14\n2,T=0|0.004|700\n").is_empty());
+        assert_eq!(run(&mut stage, "#15\n-2\n"), ["0,Event=Destroyed|2|"]);
+    }
+
+    #[test]
+    fn test_left_area_is_not_destroyed() {
+        let mut stage = FlightEventStage::new();
+        stage.configure(&AppConfig {
+            flight_events: true,
+            ..AppConfig::default()
+        });
+
+        run(&mut stage, "#0\n2,T=0|0|10,Type=Air+Rotorcraft,AGL=10\n");
+        assert_eq!(
+            run(&mut stage, "#1\n0,Event=LeftArea|2|\n-2\n"),
+            ["0,Event=LeftArea|2|"]
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::acmi::{AcmiRecord, ObjectUpdate};
+use crate::config::AppConfig;
+use crate::pipeline::Stage;
+
+/// Removes objects that stopped receiving updates
+///
+/// If the Stormworks addon crashes or a vehicle despawns without a `-id`
+/// line, the object would otherwise stay in Tacview forever. Objects silent
+/// for longer than the timeout get a `LeftArea` event and a removal line.
+#[derive(Debug, Default)]
+pub struct LifecycleStage {
+    timeout: Option<Duration>,
+    last_update: HashMap<u64, Instant>,
+}
+
+impl LifecycleStage {
+    /// Create a disabled stage, see [`Stage::configure`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a stage removing objects silent for longer than `timeout`
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            last_update: HashMap::new(),
+        }
+    }
+
+    /// Number of objects being tracked
+    pub fn tracked_objects(&self) -> usize {
+        self.last_update.len()
+    }
+}
+
+impl Stage for LifecycleStage {
+    fn name(&self) -> &'static str {
+        "lifecycle"
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.timeout.is_some()
+    }
+
+    fn configure(&mut self, config: &AppConfig) {
+        self.timeout = (config.object_timeout_secs > 0.0)
+            .then(|| Duration::from_secs_f64(config.object_timeout_secs));
+        if self.timeout.is_none() {
+            self.last_update.clear();
+        }
+    }
+
+    fn process(&mut self, records: Vec<AcmiRecord>, now: Instant) -> Vec<AcmiRecord> {
+        for record in &records {
+            match record {
+                AcmiRecord::Update(update) if !update.is_global() => {
+                    self.last_update.insert(update.id, now);
+                }
+                AcmiRecord::Removal(id) => {
+                    self.last_update.remove(id);
+                }
+                _ => {}
+            }
+        }
+        records
+    }
+
+    fn tick(&mut self, now: Instant) -> Vec<AcmiRecord> {
+        let Some(timeout) = self.timeout else {
+            return Vec::new();
+        };
+
+        let mut stale: Vec<u64> = self
+            .last_update
+            .iter()
+            .filter(|(_, last)| now.saturating_duration_since(**last) > timeout)
+            .map(|(id, _)| *id)
+            .collect();
+        stale.sort_unstable();
+
+        let mut records = Vec::with_capacity(stale.len() * 2);
+        for id in stale {
+            self.last_update.remove(&id);
+            info!(
+                "Removing object {:x} after {:.1}s without updates",
+                id,
+                timeout.as_secs_f64()
+            );
+            records.push(AcmiRecord::Update(ObjectUpdate::event(
+                "LeftArea",
+                &[id],
+                "",
+            )));
+            records.push(AcmiRecord::Removal(id));
+        }
+
+        records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acmi::parse_records;
+
+    #[test]
+    fn test_stale_objects_are_removed() {
+        let mut stage = LifecycleStage::with_timeout(Duration::from_secs(5));
+        let start = Instant::now();
+
+        stage.process(parse_records("#0\n1a,T=1|2|3\n2b,T=4|5|6\n"), start);
+        stage.process(
+            parse_records("#4\n2b,T=4|5|7\n"),
+            start + Duration::from_secs(4),
+        );
+        assert!(stage.tick(start + Duration::from_secs(5)).is_empty());
+
+        let records = stage.tick(start + Duration::from_secs(6));
+        let lines: Vec<String> = records.iter().map(ToString::to_string).collect();
+        assert_eq!(lines, ["0,Event=LeftArea|1a|", "-1a"]);
+        assert_eq!(stage.tracked_objects(), 1);
+
+        // Objects removed by the addon are no longer tracked
+        stage.process(parse_records("-2b\n"), start + Duration::from_secs(7));
+        assert!(stage.tick(start + Duration::from_secs(60)).is_empty());
+        assert_eq!(stage.tracked_objects(), 0);
+    }
+}
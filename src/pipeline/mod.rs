@@ -0,0 +1,220 @@
+//! Processing stages between ingest and the ACMI repositories
+//!
+//! Incoming ACMI text is parsed into records and passed through each enabled
+//! stage in order before being written to the repositories. Stages may also
+//! inject records on a periodic tick. When no stage is enabled the text is
+//! forwarded untouched, exactly as received from Stormworks.
+
+pub mod dead_reckoning;
+pub mod delta;
+pub mod events;
+pub mod lifecycle;
+pub mod timeline;
+
+pub use dead_reckoning::DeadReckoningStage;
+pub use delta::DeltaStage;
+pub use events::FlightEventStage;
+pub use lifecycle::LifecycleStage;
+pub use timeline::TimelineStage;
+
+use std::borrow::Cow;
+use std::time::Instant;
+use tracing::debug;
+
+use crate::acmi::{logical_lines, AcmiRecord};
+use crate::config::AppConfig;
+
+/// A processing step applied to ACMI records
+pub trait Stage: Send {
+    /// Short name of the stage, used in logs
+    fn name(&self) -> &'static str;
+
+    /// Whether the stage does anything with the current configuration
+    fn is_enabled(&self) -> bool;
+
+    /// Apply the stage's options from the configuration
+    fn configure(&mut self, config: &AppConfig);
+
+    /// Process records received from Stormworks, returning the records to forward
+    fn process(&mut self, records: Vec<AcmiRecord>, now: Instant) -> Vec<AcmiRecord>;
+
+    /// Periodic processing, returning records to inject into the stream
+    fn tick(&mut self, _now: Instant) -> Vec<AcmiRecord> {
+        Vec::new()
+    }
+
+    /// Periodic processing, returning records written only to live outputs
+    fn live_tick(&mut self, _now: Instant) -> Vec<AcmiRecord> {
+        Vec::new()
+    }
+
+    /// Records from any stage's [`Stage::live_tick`] were written to live outputs
+    fn live_sent(&mut self, _records: &[AcmiRecord]) {}
+
+    /// An output was added that has not seen the earlier records
+    fn resync(&mut self) {}
+
+    /// A recording was started, so the addon's clock may have been reset
+    ///
+    /// Unlike [`Stage::resync`], which runs whenever a live client connects,
+    /// this is where state tied to the previous session's clock is dropped.
+    fn new_session(&mut self) {}
+
+    /// When the time frames written count from, if this stage assigns them
+    ///
+    /// Called when a header is written, so a session not started yet starts
+    /// at `now`.
+    fn session_start(&mut self, _now: Instant) -> Option<Instant> {
+        None
+    }
+}
+
+/// Ordered list of processing stages
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn Stage>>,
+}
+
+impl Pipeline {
+    /// Create a pipeline without stages, forwarding everything untouched
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create the standard pipeline configured from the application config
+    pub fn from_config(config: &AppConfig) -> Self {
+        let mut pipeline = Self::new();
+        pipeline.push(Box::new(TimelineStage::new()));
+        pipeline.push(Box::new(LifecycleStage::new()));
+        pipeline.push(Box::new(FlightEventStage::new()));
+        pipeline.push(Box::new(DeadReckoningStage::new()));
+        pipeline.push(Box::new(DeltaStage::new()));
+        pipeline.configure(config);
+        pipeline
+    }
+
+    /// Append a stage to the end of the pipeline
+    pub fn push(&mut self, stage: Box<dyn Stage>) {
+        self.stages.push(stage);
+    }
+
+    /// Apply configuration changes to every stage, keeping their state
+    pub fn configure(&mut self, config: &AppConfig) {
+        for stage in &mut self.stages {
+            stage.configure(config);
+        }
+    }
+
+    /// Whether any stage is enabled
+    pub fn is_active(&self) -> bool {
+        self.stages.iter().any(|stage| stage.is_enabled())
+    }
+
+    /// Run ACMI text received from Stormworks through the enabled stages
+    ///
+    /// Lines that cannot be parsed are dropped when any stage is enabled.
+    pub fn process<'a>(&mut self, acmi: &'a str, now: Instant) -> Cow<'a, str> {
+        if !self.is_active() {
+            return Cow::Borrowed(acmi);
+        }
+
+        let mut records = Vec::new();
+        for (line_number, line) in logical_lines(acmi) {
+            match AcmiRecord::parse(&line) {
+                Ok(record) => records.push(record),
+                Err(e) => debug!("Dropping unparseable ACMI line {}: {}", line_number, e),
+            }
+        }
+
+        for stage in self.stages.iter_mut().filter(|stage| stage.is_enabled()) {
+            records = stage.process(records, now);
+        }
+
+        Cow::Owned(render(&records))
+    }
+
+    /// Collect records injected by the enabled stages
+    ///
+    /// Records injected by a stage pass through the stages after it. Returns
+    /// `None` when there is nothing to write.
+    pub fn tick(&mut self, now: Instant) -> Option<String> {
+        let mut records = Vec::new();
+        for stage in self.stages.iter_mut().filter(|stage| stage.is_enabled()) {
+            if !records.is_empty() {
+                records = stage.process(records, now);
+            }
+            records.extend(stage.tick(now));
+        }
+
+        (!records.is_empty()).then(|| render(&records))
+    }
+
+    /// Collect records meant only for live outputs, see [`Stage::live_tick`]
+    pub fn live_tick(&mut self, now: Instant) -> Option<String> {
+        let records: Vec<AcmiRecord> = self
+            .stages
+            .iter_mut()
+            .filter(|stage| stage.is_enabled())
+            .flat_map(|stage| stage.live_tick(now))
+            .collect();
+        if records.is_empty() {
+            return None;
+        }
+
+        for stage in self.stages.iter_mut().filter(|stage| stage.is_enabled()) {
+            stage.live_sent(&records);
+        }
+        Some(render(&records))
+    }
+
+    /// Let the stages bring a newly added output up to date, see [`Stage::resync`]
+    pub fn resync(&mut self) {
+        for stage in &mut self.stages {
+            stage.resync();
+        }
+    }
+
+    /// Tell the stages a new session started, see [`Stage::new_session`]
+    pub fn new_session(&mut self) {
+        for stage in &mut self.stages {
+            stage.new_session();
+        }
+    }
+
+    /// When the time frames written count from, see [`Stage::session_start`]
+    pub fn session_start(&mut self, now: Instant) -> Option<Instant> {
+        self.stages
+            .iter_mut()
+            .filter(|stage| stage.is_enabled())
+            .find_map(|stage| stage.session_start(now))
+    }
+}
+
+/// Render records as ACMI text, one per line
+pub(crate) fn render(records: &[AcmiRecord]) -> String {
+    let mut out = String::new();
+    for record in records {
+        out.push_str(&record.to_string());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline_passthrough() {
+        let mut pipeline = Pipeline::from_config(&AppConfig::default());
+        assert!(!pipeline.is_active());
+
+        // Forwarded untouched, including lines the parser would reject
+        let acmi = "#1.5\n1A,T=1|2|3\nnot acmi\n";
+        assert!(matches!(
+            pipeline.process(acmi, Instant::now()),
+            Cow::Borrowed(text) if text == acmi
+        ));
+        assert_eq!(pipeline.tick(Instant::now()), None);
+    }
+}
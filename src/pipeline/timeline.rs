@@ -0,0 +1,256 @@
+use std::time::Instant;
+use tracing::debug;
+
+use crate::acmi::AcmiRecord;
+use crate::config::{AppConfig, TimeMode};
+use crate::pipeline::Stage;
+
+/// Seconds the addon's clock may go back before it counts as a new session
+const SESSION_RESET_SECS: f64 = 10.0;
+
+/// Assigns the time frames written to the repositories
+///
+/// In [`TimeMode::Monotonic`] the addon's `#t` lines are kept but never go
+/// backwards, except when the addon's clock resets: a jump back of more than
+/// [`SESSION_RESET_SECS`], or a new recording, starts over from its time.
+///
+/// In [`TimeMode::WallClock`] every batch is stamped with the time since the
+/// session started, so that file and live outputs share a steady timeline
+/// even when Stormworks lags and frames arrive in clusters. The addon's time
+/// frames within a batch keep their spacing, anchored so the latest one
+/// lands on the arrival time. Headers written in this mode get a
+/// ReferenceTime matching the session start, see [`Stage::session_start`].
+#[derive(Debug, Default)]
+pub struct TimelineStage {
+    mode: TimeMode,
+    session_start: Option<Instant>,
+    last_time: Option<f64>,
+    clamped: u64,
+    resets: u64,
+}
+
+impl TimelineStage {
+    /// Create a disabled stage, see [`Stage::configure`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of time frames moved forward to keep the timeline monotonic
+    pub fn clamped(&self) -> u64 {
+        self.clamped
+    }
+
+    /// Number of addon clock resets followed
+    pub fn resets(&self) -> u64 {
+        self.resets
+    }
+
+    /// Start over from an addon time far behind the last time frame written
+    fn follow_reset(&mut self, time: f64) {
+        if self
+            .last_time
+            .is_some_and(|last| time < last - SESSION_RESET_SECS)
+        {
+            self.resets += 1;
+            debug!(
+                "Addon clock went back from {} to {}, starting a new session",
+                self.last_time.unwrap_or_default(),
+                time
+            );
+            self.last_time = None;
+        }
+    }
+
+    /// Never go back before the last time frame written
+    fn monotonic(&mut self, time: f64) -> f64 {
+        let time = (time * 1000.0).round() / 1000.0;
+        match self.last_time {
+            Some(last) if time < last => {
+                self.clamped += 1;
+                debug!(
+                    "Clamped out-of-order time frame {} to {} ({} so far)",
+                    time, last, self.clamped
+                );
+                last
+            }
+            _ => {
+                self.last_time = Some(time);
+                time
+            }
+        }
+    }
+}
+
+impl Stage for TimelineStage {
+    fn name(&self) -> &'static str {
+        "timeline"
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.mode != TimeMode::Addon
+    }
+
+    fn configure(&mut self, config: &AppConfig) {
+        if config.time_mode != self.mode {
+            self.mode = config.time_mode;
+            self.session_start = None;
+            self.last_time = None;
+        }
+    }
+
+    fn new_session(&mut self) {
+        // Wall clock time keeps running so live clients never see it go back,
+        // recordings get a ReferenceTime matching the session start instead
+        self.last_time = None;
+    }
+
+    fn session_start(&mut self, now: Instant) -> Option<Instant> {
+        (self.mode == TimeMode::WallClock).then(|| *self.session_start.get_or_insert(now))
+    }
+
+    fn process(&mut self, records: Vec<AcmiRecord>, now: Instant) -> Vec<AcmiRecord> {
+        match self.mode {
+            TimeMode::Addon => records,
+            TimeMode::Monotonic => records
+                .into_iter()
+                .map(|record| match record {
+                    AcmiRecord::TimeFrame(time) => {
+                        self.follow_reset(time);
+                        AcmiRecord::TimeFrame(self.monotonic(time))
+                    }
+                    record => record,
+                })
+                .collect(),
+            TimeMode::WallClock => {
+                let start = *self.session_start.get_or_insert(now);
+                let arrival = now.saturating_duration_since(start).as_secs_f64();
+
+                let latest = records
+                    .iter()
+                    .filter_map(|record| match record {
+                        AcmiRecord::TimeFrame(time) => Some(*time),
+                        _ => None,
+                    })
+                    .reduce(f64::max);
+
+                let mut output = Vec::with_capacity(records.len() + 1);
+                if latest.is_none() && !records.is_empty() {
+                    output.push(AcmiRecord::TimeFrame(self.monotonic(arrival)));
+                }
+                for record in records {
+                    output.push(match (record, latest) {
+                        (AcmiRecord::TimeFrame(time), Some(latest)) => {
+                            AcmiRecord::TimeFrame(self.monotonic(arrival - (latest - time)))
+                        }
+                        (record, _) => record,
+                    });
+                }
+                output
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acmi::parse_records;
+    use std::time::Duration;
+
+    fn times(records: &[AcmiRecord]) -> Vec<f64> {
+        records
+            .iter()
+            .filter_map(|record| match record {
+                AcmiRecord::TimeFrame(time) => Some(*time),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn stage(mode: TimeMode) -> TimelineStage {
+        let mut stage = TimelineStage::new();
+        stage.configure(&AppConfig {
+            time_mode: mode,
+            ..AppConfig::default()
+        });
+        stage
+    }
+
+    #[test]
+    fn test_monotonic_clamps_out_of_order_frames() {
+        let mut stage = stage(TimeMode::Monotonic);
+        let now = Instant::now();
+
+        let records = stage.process(parse_records("#1\n1,T=1|2|3\n#3\n#2\n#4\n"), now);
+        assert_eq!(times(&records), [1.0, 3.0, 3.0, 4.0]);
+        assert_eq!(stage.clamped(), 1);
+    }
+
+    #[test]
+    fn test_monotonic_follows_addon_clock_resets() {
+        let mut stage = stage(TimeMode::Monotonic);
+        let now = Instant::now();
+
+        // A large jump back is a script reload, not an out-of-order frame
+        let records = stage.process(parse_records("#500\n#495\n#0\n#1\n"), now);
+        assert_eq!(times(&records), [500.0, 500.0, 0.0, 1.0]);
+        assert_eq!((stage.clamped(), stage.resets()), (1, 1));
+
+        // A new recording starts over from the addon's time, however close
+        stage.process(parse_records("#8\n"), now);
+        stage.new_session();
+        let records = stage.process(parse_records("#2\n#3\n"), now);
+        assert_eq!(times(&records), [2.0, 3.0]);
+        assert_eq!(stage.clamped(), 1);
+    }
+
+    #[test]
+    fn test_wall_clock_stamps_batches() {
+        let mut stage = stage(TimeMode::WallClock);
+        let start = Instant::now();
+
+        // Addon times are replaced by the time since the session started
+        let records = stage.process(parse_records("#500\n1,T=1|2|3\n"), start);
+        assert_eq!(times(&records), [0.0]);
+
+        // A clustered batch keeps its spacing, ending at the arrival time
+        let records = stage.process(
+            parse_records("#500.1\n1,T=1|2|3\n#500.2\n1,T=1|2|4\n#500.3\n1,T=1|2|5\n"),
+            start + Duration::from_secs(2),
+        );
+        assert_eq!(times(&records), [1.8, 1.9, 2.0]);
+
+        // Batches without a time frame get one, and time never goes backwards
+        let records = stage.process(
+            parse_records("1,T=1|2|6\n"),
+            start + Duration::from_millis(2500),
+        );
+        assert_eq!(times(&records), [2.5]);
+        let records = stage.process(
+            parse_records("#400\n#510\n"),
+            start + Duration::from_millis(2600),
+        );
+        assert_eq!(times(&records), [2.5, 2.6]);
+    }
+
+    #[test]
+    fn test_wall_clock_session_start() {
+        let start = Instant::now();
+        // The addon's clock has no known start
+        assert_eq!(stage(TimeMode::Monotonic).session_start(start), None);
+
+        let mut stage = stage(TimeMode::WallClock);
+
+        // A header written before the first batch starts the session
+        assert_eq!(stage.session_start(start), Some(start));
+        let records = stage.process(parse_records("#500\n"), start + Duration::from_secs(3));
+        assert_eq!(times(&records), [3.0]);
+
+        // New recordings share the timeline of the live clients
+        stage.new_session();
+        assert_eq!(
+            stage.session_start(start + Duration::from_secs(5)),
+            Some(start)
+        );
+    }
+}
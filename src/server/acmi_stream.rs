@@ -0,0 +1,166 @@
+use anyhow::Result;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::info;
+
+use crate::domain::AcmiRepository;
+use crate::handlers::AppState;
+use crate::infra::{ChannelAcmiRepository, TcpRealTimeTelemetryRepository};
+use crate::server::http_simple::{header_value, query_params};
+
+/// Wire format used for an ACMI stream response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamFormat {
+    /// Plain text with chunked transfer encoding
+    Chunked,
+    /// Server-Sent Events, one `data:` field per ACMI line
+    EventStream,
+}
+
+impl StreamFormat {
+    fn from_request(request: &str) -> Self {
+        let sse_query = query_params(request)
+            .iter()
+            .any(|(key, value)| key == "format" && value == "sse");
+        let sse_accept =
+            header_value(request, "Accept").is_some_and(|v| v.contains("text/event-stream"));
+
+        if sse_query || sse_accept {
+            Self::EventStream
+        } else {
+            Self::Chunked
+        }
+    }
+
+    /// Sent after the last data when the stream ends
+    fn trailer(self) -> &'static str {
+        match self {
+            Self::Chunked => "0\r\n\r\n",
+            Self::EventStream => "",
+        }
+    }
+
+    fn response_head(self) -> &'static str {
+        match self {
+            Self::Chunked => {
+                "HTTP/1.1 200 OK\r\n\
+                Content-Type: text/plain; charset=utf-8\r\n\
+                Transfer-Encoding: chunked\r\n\
+                Cache-Control: no-cache\r\n\r\n"
+            }
+            Self::EventStream => {
+                "HTTP/1.1 200 OK\r\n\
+                Content-Type: text/event-stream\r\n\
+                Cache-Control: no-cache\r\n\
+                Connection: keep-alive\r\n\r\n"
+            }
+        }
+    }
+
+    /// Wrap ACMI text for sending in this format
+    fn encode(self, acmi: &str) -> String {
+        match self {
+            Self::Chunked => format!("{:x}\r\n{acmi}\r\n", acmi.len()),
+            Self::EventStream => {
+                let mut event = String::with_capacity(acmi.len() + 16);
+                for line in acmi.lines() {
+                    event.push_str("data: ");
+                    event.push_str(line);
+                    event.push('\n');
+                }
+                event.push('\n');
+                event
+            }
+        }
+    }
+}
+
+/// Stream the live ACMI feed to an HTTP client
+///
+/// The client first receives the same header Tacview clients get, followed
+/// by the raw ACMI data as it arrives. Runs until the client disconnects or
+/// the output is closed, which ends the stream after the queued data.
+pub(crate) async fn serve(socket: TcpStream, request: &str, state: Arc<AppState>) -> Result<()> {
+    let format = StreamFormat::from_request(request);
+    let peer = socket.peer_addr().ok().map(|addr| addr.to_string());
+    let (mut reader, mut writer) = socket.into_split();
+
+    writer.write_all(format.response_head().as_bytes()).await?;
+    let header =
+        TcpRealTimeTelemetryRepository::generate_realtime_header(state.reference_time().await);
+    writer.write_all(format.encode(&header).as_bytes()).await?;
+
+    let (repo, mut receiver) = ChannelAcmiRepository::new();
+    let repo = Arc::new(repo);
+    let output = state
+        .add_output(repo.clone() as Arc<dyn AcmiRepository>, peer)
+        .await;
+    info!(
+        "Added ACMI stream client to repositories as output {} (total: {})",
+        output.id(),
+        state.acmi_repositories.lock().await.len()
+    );
+    state.resync().await;
+
+    let result = async {
+        let mut discard = [0u8; 512];
+        loop {
+            tokio::select! {
+                acmi = receiver.recv() => match acmi {
+                    // Queued by close after everything else
+                    Some(acmi) if acmi.is_empty() => {
+                        writer.write_all(format.trailer().as_bytes()).await?;
+                        writer.shutdown().await?;
+                        break;
+                    }
+                    Some(acmi) => writer.write_all(format.encode(&acmi).as_bytes()).await?,
+                    None => break,
+                },
+                read = reader.read(&mut discard) => match read {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {}
+                },
+            }
+
+            if repo.is_closed() {
+                break;
+            }
+        }
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+
+    state.remove_output(&output).await;
+
+    info!("ACMI stream client disconnected");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_format() {
+        let request = "GET /stream.acmi HTTP/1.1\r\nAccept: text/event-stream\r\n\r\n";
+        assert_eq!(
+            StreamFormat::from_request(request),
+            StreamFormat::EventStream
+        );
+        let request = "GET /stream.acmi?format=sse HTTP/1.1\r\n\r\n";
+        assert_eq!(
+            StreamFormat::from_request(request),
+            StreamFormat::EventStream
+        );
+        let request = "GET /stream.acmi HTTP/1.1\r\n\r\n";
+        assert_eq!(StreamFormat::from_request(request), StreamFormat::Chunked);
+
+        assert_eq!(StreamFormat::Chunked.encode("#1\n"), "3\r\n#1\n\r\n");
+        assert_eq!(StreamFormat::Chunked.trailer(), "0\r\n\r\n");
+        assert_eq!(
+            StreamFormat::EventStream.encode("#1\n-2\n"),
+            "data: #1\ndata: -2\n\n"
+        );
+    }
+}
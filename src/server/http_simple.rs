@@ -1,97 +1,210 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
 use tracing::{error, info};
 
-use crate::domain::AcmiFileRepository;
-use crate::handlers::AppState;
+use crate::domain::{AcmiFileRepository, AcmiRepository};
+use crate::handlers::{
+    parse_rate_override, AppState, EventRequest, HealthReport, OutputReport, StatusReport,
+};
+use crate::server::{acmi_stream, websocket, InFlight, InFlightGuard, ShutdownToken};
 
 /// Simple HTTP server for Stormworks integration
 pub struct HttpServer {
     state: Arc<AppState>,
+    listener: TcpListener,
+    /// Requests being answered, not counting streaming connections
+    requests: InFlight,
 }
 
 impl HttpServer {
-    pub fn new(state: Arc<AppState>) -> Self {
-        Self { state }
+    /// Bind the HTTP server to an address
+    ///
+    /// Port 0 picks a free port, see [`HttpServer::local_addr`].
+    pub async fn bind(addr: SocketAddr, state: Arc<AppState>) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to bind HTTP server to {addr}"))?;
+        Ok(Self {
+            state,
+            listener,
+            requests: InFlight::new(),
+        })
     }
 
-    pub async fn start(&self, port: u16) -> Result<()> {
-        let listener = TcpListener::bind(format!("127.0.0.1:{port}")).await?;
-        info!("HTTP server listening on port {}", port);
+    /// Address the server is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener
+            .local_addr()
+            .expect("bound HTTP listener has a local address")
+    }
+
+    /// Accept requests until `shutdown` is cancelled, then wait for the
+    /// requests being answered
+    ///
+    /// WebSocket and ACMI stream connections are not waited for; they end
+    /// when their outputs are closed by [`AppState::shutdown`].
+    pub async fn run(self, shutdown: ShutdownToken) -> Result<()> {
+        info!("HTTP server listening on {}", self.local_addr());
 
         loop {
-            let (mut socket, addr) = listener.accept().await?;
+            let (socket, addr) = tokio::select! {
+                accepted = self.listener.accept() => accepted?,
+                _ = shutdown.cancelled() => break,
+            };
             let state_clone = self.state.clone();
-            if state_clone.verbose {
+            if state_clone.is_verbose() {
                 info!("New HTTP connection from: {}", addr);
             }
 
-            tokio::spawn(async move {
-                let mut buffer = [0; 16384]; // Increased buffer size for large ACMI data (16KB)
+            let in_flight = self.requests.start();
+            tokio::spawn(handle_connection(socket, addr, state_clone, in_flight));
+        }
+
+        if self.requests.count() > 0 {
+            info!(
+                "Waiting for {} HTTP request(s) to finish",
+                self.requests.count()
+            );
+        }
+        self.requests.wait_idle().await;
+        info!("HTTP server stopped");
+        Ok(())
+    }
+}
+
+/// Handle a single HTTP connection
+///
+/// Most requests are answered with a single response, but WebSocket
+/// upgrades keep the connection open for streaming. `in_flight` keeps the
+/// connection counted as a request until it turns into a stream.
+async fn handle_connection(
+    mut socket: TcpStream,
+    addr: SocketAddr,
+    state: Arc<AppState>,
+    in_flight: InFlightGuard,
+) {
+    let mut buffer = [0; 16384]; // Increased buffer size for large ACMI data (16KB)
+
+    match socket.read(&mut buffer).await {
+        Ok(0) => {
+            if state.is_verbose() {
+                info!("HTTP connection closed by client: {}", addr);
+            }
+        }
+        Ok(n) => {
+            let request = String::from_utf8_lossy(&buffer[..n]).into_owned();
+            if state.is_verbose() {
+                info!(
+                    "HTTP request from {}: {}",
+                    addr,
+                    request.lines().next().unwrap_or("(empty)")
+                );
+            }
 
-                match socket.read(&mut buffer).await {
-                    Ok(0) => {
-                        if state_clone.verbose {
-                            info!("HTTP connection closed by client: {}", addr);
+            if request_path(&request) == Some("/ws") && websocket::is_upgrade_request(&request) {
+                match &state.websocket_feed {
+                    Some(feed) => {
+                        info!("New WebSocket client from: {}", addr);
+                        drop(in_flight);
+                        let _client = state.clients.start();
+                        if let Err(e) = websocket::serve(socket, &request, feed.clone()).await {
+                            error!("WebSocket connection from {} failed: {}", addr, e);
                         }
                     }
-                    Ok(n) => {
-                        let request = String::from_utf8_lossy(&buffer[..n]);
-                        if state_clone.verbose {
-                            info!(
-                                "HTTP request from {}: {}",
-                                addr,
-                                request.lines().next().unwrap_or("(empty)")
-                            );
-                        }
-
-                        let response = if request.contains("GET /start") {
-                            info!("Processing /start command");
-                            handle_start(&state_clone).await
-                        } else if request.contains("GET /stop") {
-                            info!("Processing /stop command");
-                            handle_stop(&state_clone).await
-                        } else if request.contains("GET /acmi/") {
-                            if let Some(data) = extract_acmi_data(&request) {
-                                if state_clone.verbose {
-                                    info!("Extracted ACMI data length: {}", data.len());
-                                }
-                                handle_acmi(&state_clone, &data).await
-                            } else {
-                                error!("Invalid ACMI data in request");
-                                error!(
-                                    "Request first line: {}",
-                                    request.lines().next().unwrap_or("(empty)")
-                                );
-                                "HTTP/1.1 400 Bad Request\r\n\r\nBad Request".to_string()
-                            }
-                        } else {
-                            // Unknown request は発生する前提なのでログ不要
-                            "HTTP/1.1 404 Not Found\r\n\r\nNot Found".to_string()
-                        };
-
-                        let _ = socket.write_all(response.as_bytes()).await;
+                    None => {
+                        let _ = socket
+                            .write_all(b"HTTP/1.1 404 Not Found\r\n\r\nNot Found")
+                            .await;
                     }
-                    Err(e) => {
-                        error!("Failed to read from socket {}: {}", addr, e);
+                }
+                return;
+            }
+
+            if request.starts_with("GET ") && request_path(&request) == Some("/stream.acmi") {
+                info!("New ACMI stream client from: {}", addr);
+                drop(in_flight);
+                let _client = state.clients.start();
+                if let Err(e) = acmi_stream::serve(socket, &request, state).await {
+                    error!("ACMI stream to {} failed: {}", addr, e);
+                }
+                return;
+            }
+
+            let response = if request.starts_with("GET ")
+                && request_path(&request) == Some("/metrics")
+            {
+                let gauges = state.runtime_gauges().await;
+                let body = state.metrics.render(&gauges);
+                text_response("200 OK", "text/plain; version=0.0.4", &body)
+            } else if request.starts_with("GET ") && request_path(&request) == Some("/status") {
+                let status = StatusReport::collect(&state).await;
+                json_response("200 OK", &status)
+            } else if request.starts_with("GET ") && request_path(&request) == Some("/healthz") {
+                let health = HealthReport::collect(&state).await;
+                let status = if health.healthy {
+                    "200 OK"
+                } else {
+                    "503 Service Unavailable"
+                };
+                json_response(status, &health)
+            } else if request.starts_with("GET ") && request_path(&request) == Some("/event") {
+                handle_event(&state, &request).await
+            } else if request_path(&request).is_some_and(|path| path.starts_with("/admin/")) {
+                handle_admin(&state, addr, &request)
+            } else if request.contains("GET /start") {
+                info!("Processing /start command");
+                handle_start(&state).await
+            } else if request.contains("GET /stop") {
+                info!("Processing /stop command");
+                handle_stop(&state).await
+            } else if request.contains("GET /acmi/") {
+                if let Some(data) = extract_acmi_data(&request) {
+                    if state.is_verbose() {
+                        info!("Extracted ACMI data length: {}", data.len());
                     }
+                    handle_acmi(&state, &data).await
+                } else {
+                    error!("Invalid ACMI data in request");
+                    error!(
+                        "Request first line: {}",
+                        request.lines().next().unwrap_or("(empty)")
+                    );
+                    "HTTP/1.1 400 Bad Request\r\n\r\nBad Request".to_string()
                 }
-            });
+            } else {
+                // Unknown request は発生する前提なのでログ不要
+                "HTTP/1.1 404 Not Found\r\n\r\nNot Found".to_string()
+            };
+
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+        Err(e) => {
+            error!("Failed to read from socket {}: {}", addr, e);
         }
     }
 }
 
 async fn handle_start(state: &AppState) -> String {
+    let reference_time = state.reference_time().await;
     let repos = state.file_repositories.lock().await;
 
     for repo in repos.iter() {
+        repo.set_reference_time(reference_time);
         if let Err(e) = repo.start() {
             error!("Failed to start ACMI recording: {}", e);
             return "HTTP/1.1 500 Internal Server Error\r\n\r\nInternal Server Error".to_string();
         }
     }
+    if let Some(Err(e)) = state.csv_export.as_ref().map(|csv| csv.start()) {
+        error!("Failed to start CSV export: {}", e);
+    }
+
+    drop(repos);
+    state.new_session().await;
+    state.resync().await;
 
     info!("Started ACMI recording");
     "HTTP/1.1 200 OK\r\n\r\nOK".to_string()
@@ -106,61 +219,227 @@ async fn handle_stop(state: &AppState) -> String {
             return "HTTP/1.1 500 Internal Server Error\r\n\r\nInternal Server Error".to_string();
         }
     }
+    if let Some(csv) = &state.csv_export {
+        if let Err(e) = csv.stop().await {
+            error!("Failed to stop CSV export: {}", e);
+        }
+    }
 
     info!("Stopped ACMI recording");
     "HTTP/1.1 200 OK\r\n\r\nOK".to_string()
 }
 
 async fn handle_acmi(state: &AppState, data: &str) -> String {
-    // Message counter for periodic logging
-    static MESSAGE_COUNT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
-    let count = MESSAGE_COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-
-    // Log every 100 messages to verify Stormworks is still sending (reduced frequency)
-    if count % 100 == 0 && count > 0 {
-        info!("Received message #{} from Stormworks", count);
-    }
-
     // Simple base64 decode (simplified implementation)
     let decoded = match decode_base64_simple(data) {
         Ok(decoded) => decoded,
         Err(_) => {
             error!("Failed to decode base64 data (length: {})", data.len());
+            state.metrics.decode_failures.inc("http");
             return "HTTP/1.1 400 Bad Request\r\n\r\nBad Request".to_string();
         }
     };
 
+    // Only frames that decode count as received
+    let count = state.record_frame("http") - 1;
+
+    // Log every 100 messages to verify Stormworks is still sending (reduced frequency)
+    if count.is_multiple_of(100) && count > 0 {
+        info!("Received message #{} from Stormworks", count);
+    }
+
     let acmi_data = format!("{decoded}\n");
+    state.validate_frame("http", &acmi_data);
 
     // Write to all repositories
-    let repos = state.acmi_repositories.lock().await;
+    let repo_count = state.ingest(&acmi_data).await;
 
     // Log repository count only once
     static FIRST_CALL: std::sync::Once = std::sync::Once::new();
     FIRST_CALL.call_once(|| {
         info!(
             "Total repositories: {} (file + TCP connections)",
-            repos.len()
+            repo_count
         );
     });
 
     // Log every 100 repository writes (reduced frequency)
-    if state.verbose && count % 100 == 0 && count > 0 {
-        info!("Writing to {} repositories", repos.len());
+    if state.is_verbose() && count.is_multiple_of(100) && count > 0 {
+        info!("Wrote to {} repositories", repo_count);
     }
 
-    for (i, repo) in repos.iter().enumerate() {
-        match repo.write(&acmi_data).await {
-            Ok(_) => {
-                // Successfully wrote to repository
-            }
-            Err(e) => {
-                error!("Failed to write ACMI data to repository {}: {}", i, e);
+    "HTTP/1.1 200 OK\r\n\r\nOK".to_string()
+}
+
+/// Inject a bookmark or message event into every repository
+async fn handle_event(state: &AppState, request: &str) -> String {
+    match EventRequest::from_query(&query_params(request)) {
+        Ok(event) => {
+            info!("Injecting {} event: {}", event.kind, event.text);
+            let record = event.to_record();
+            state.ingest(&format!("{record}\n")).await;
+            text_response("200 OK", "text/plain", "OK")
+        }
+        Err(message) => text_response("400 Bad Request", "text/plain", &message),
+    }
+}
+
+/// Answer the admin API, which is only open to loopback clients unless an
+/// admin token is configured
+///
+/// Listing outputs uses `GET`, changing them `POST`.
+fn handle_admin(state: &AppState, addr: SocketAddr, request: &str) -> String {
+    let token = state.admin_token.lock().unwrap().clone();
+    if !admin_allowed(addr, token.as_deref(), request) {
+        info!("Rejected admin request from {}", addr);
+        return text_response("403 Forbidden", "text/plain", "Forbidden");
+    }
+
+    let method = request.split_whitespace().next().unwrap_or_default();
+    let path = request_path(request).unwrap_or_default();
+    let rate_id = path
+        .strip_prefix("/admin/outputs/")
+        .and_then(|path| path.strip_suffix("/rate"));
+    match (method, path, rate_id) {
+        ("GET", "/admin/outputs", _) => json_response("200 OK", &OutputReport::collect(state)),
+        ("POST", _, Some(id)) => handle_output_rate(state, id, request),
+        (_, "/admin/outputs", _) | (_, _, Some(_)) => {
+            text_response("405 Method Not Allowed", "text/plain", "Method Not Allowed")
+        }
+        _ => text_response("404 Not Found", "text/plain", "Not Found"),
+    }
+}
+
+/// Whether a client may use the admin API
+///
+/// Loopback clients always may. Others need `Authorization: Bearer <token>`
+/// with the configured token, and are refused when none is configured.
+fn admin_allowed(addr: SocketAddr, token: Option<&str>, request: &str) -> bool {
+    if addr.ip().is_loopback() {
+        return true;
+    }
+    let Some(token) = token else {
+        return false;
+    };
+    header_value(request, "Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| {
+            // Compare every byte so the time taken does not reveal the token
+            given.len() == token.len()
+                && given
+                    .bytes()
+                    .zip(token.bytes())
+                    .fold(0, |diff, (a, b)| diff | (a ^ b))
+                    == 0
+        })
+}
+
+/// Override the update rate of a single output
+fn handle_output_rate(state: &AppState, id: &str, request: &str) -> String {
+    let Some(output) = id.parse().ok().and_then(|id| state.output(id)) else {
+        return text_response("404 Not Found", "text/plain", "Unknown output");
+    };
+
+    let params = query_params(request);
+    let Some((_, value)) = params.iter().find(|(key, _)| key == "rate") else {
+        return text_response("400 Bad Request", "text/plain", "missing `rate` parameter");
+    };
+    match parse_rate_override(value) {
+        Ok(rate) => {
+            output.set_rate_override(rate);
+            info!(
+                "Set update rate of {} output {} to {} Hz",
+                output.name(),
+                output.id(),
+                output.rate()
+            );
+            json_response("200 OK", &OutputReport::new(&output))
+        }
+        Err(message) => text_response("400 Bad Request", "text/plain", &message),
+    }
+}
+
+/// Build a complete response with a body and the headers describing it
+pub(crate) fn text_response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Build a JSON response from a serializable value
+pub(crate) fn json_response<T: serde::Serialize>(status: &str, value: &T) -> String {
+    match serde_json::to_string(value) {
+        Ok(body) => text_response(status, "application/json", &body),
+        Err(e) => {
+            error!("Failed to serialize JSON response: {}", e);
+            "HTTP/1.1 500 Internal Server Error\r\n\r\nInternal Server Error".to_string()
+        }
+    }
+}
+
+/// Get the path of the request line, without the query string
+pub(crate) fn request_path(request: &str) -> Option<&str> {
+    let target = request.lines().next()?.split_whitespace().nth(1)?;
+    Some(target.split('?').next().unwrap_or(target))
+}
+
+/// Get the percent-decoded query parameters of the request line
+pub(crate) fn query_params(request: &str) -> Vec<(String, String)> {
+    let query = request
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|target| target.split_once('?'))
+        .map(|(_, query)| query)
+        .unwrap_or("");
+
+    query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+/// Decode `%XX` escapes and `+` as space
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
             }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
         }
+        i += 1;
     }
 
-    "HTTP/1.1 200 OK\r\n\r\nOK".to_string()
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Get the value of a request header, matching the name case-insensitively
+pub(crate) fn header_value<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+    request
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.trim().is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
 }
 
 fn extract_acmi_data(request: &str) -> Option<String> {
@@ -191,7 +470,7 @@ fn extract_acmi_data(request: &str) -> Option<String> {
     Some(data)
 }
 
-fn decode_base64_simple(input: &str) -> Result<String, ()> {
+pub(crate) fn decode_base64_simple(input: &str) -> Result<String, ()> {
     // Simple base64 decoding implementation
     const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
 
@@ -219,3 +498,108 @@ fn decode_base64_simple(input: &str) -> Result<String, ()> {
 
     String::from_utf8(result).map_err(|_| ())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_params() {
+        let request =
+            "GET /event?type=Bookmark&text=Gear+up%2C%20flaps%0A50%25&object= HTTP/1.1\r\n\r\n";
+        assert_eq!(
+            query_params(request),
+            [
+                ("type".to_string(), "Bookmark".to_string()),
+                ("text".to_string(), "Gear up, flaps\n50%".to_string()),
+                ("object".to_string(), String::new()),
+            ]
+        );
+        assert!(query_params("GET /event HTTP/1.1\r\n\r\n").is_empty());
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+
+    #[test]
+    fn test_admin_allowed() {
+        let local: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let remote: SocketAddr = "192.168.1.20:5000".parse().unwrap();
+        let plain = "POST /admin/outputs/1/rate?rate=0 HTTP/1.1\r\n\r\n";
+        let bearer = |token| {
+            format!("POST /admin/outputs/1/rate?rate=0 HTTP/1.1\r\nAuthorization: Bearer {token}\r\n\r\n")
+        };
+
+        assert!(admin_allowed(local, None, plain));
+        assert!(!admin_allowed(remote, None, plain));
+        assert!(!admin_allowed(remote, None, &bearer("secret")));
+        assert!(!admin_allowed(remote, Some("secret"), plain));
+        assert!(!admin_allowed(remote, Some("secret"), &bearer("secreT")));
+        assert!(!admin_allowed(remote, Some("secret"), &bearer("secret2")));
+        assert!(admin_allowed(remote, Some("secret"), &bearer("secret")));
+    }
+
+    #[tokio::test]
+    async fn test_admin_output_routes() {
+        let state = AppState::new();
+        let (channel, _receiver) = crate::infra::ChannelAcmiRepository::new();
+        let output = state.add_output(Arc::new(channel), None).await;
+        let local: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let status = |method: &str, path: &str| {
+            let request = format!("{method} {path} HTTP/1.1\r\n\r\n");
+            let response = handle_admin(&state, local, &request);
+            response.lines().next().unwrap_or_default().to_string()
+        };
+        let rate = |value: &str| format!("/admin/outputs/{}/rate?rate={value}", output.id());
+
+        let list = handle_admin(&state, local, "GET /admin/outputs HTTP/1.1\r\n\r\n");
+        assert!(list.starts_with("HTTP/1.1 200 OK"));
+        assert!(list.contains(&format!("\"id\":{}", output.id())));
+        assert!(list.contains("\"kind\":\"stream\""));
+
+        assert_eq!(status("POST", &rate("2")), "HTTP/1.1 200 OK");
+        assert_eq!(output.rate_override(), Some(2.0));
+
+        // Invalid, negative and missing rates leave the override alone
+        for path in [
+            rate("fast"),
+            rate("-1"),
+            format!("/admin/outputs/{}/rate", output.id()),
+        ] {
+            assert_eq!(status("POST", &path), "HTTP/1.1 400 Bad Request");
+        }
+        assert_eq!(output.rate_override(), Some(2.0));
+
+        // Going back to the configured rate
+        assert_eq!(status("POST", &rate("default")), "HTTP/1.1 200 OK");
+        assert_eq!(output.rate_override(), None);
+
+        assert_eq!(
+            status("POST", "/admin/outputs/999/rate?rate=1"),
+            "HTTP/1.1 404 Not Found"
+        );
+        assert_eq!(
+            status("DELETE", "/admin/outputs"),
+            "HTTP/1.1 405 Method Not Allowed"
+        );
+        assert_eq!(status("GET", "/admin/other"), "HTTP/1.1 404 Not Found");
+
+        let remote: SocketAddr = "192.168.1.20:5000".parse().unwrap();
+        let response = handle_admin(&state, remote, "GET /admin/outputs HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden"));
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_frames_are_not_received() {
+        let state = AppState::new();
+
+        let response = handle_acmi(&state, "not base64!").await;
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+        assert_eq!(state.metrics.frames_received.get("http"), 0);
+        assert_eq!(state.metrics.decode_failures.get("http"), 1);
+        assert!(state.since_last_frame().is_none());
+
+        let response =
+            handle_acmi(&state, "MCxSZWZlcmVuY2VUaW1lPTIwMjQtMDEtMDFUMDA6MDA6MDBa").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert_eq!(state.metrics.frames_received.get("http"), 1);
+    }
+}
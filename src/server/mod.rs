@@ -1,10 +1,16 @@
 //! Server implementations for HTTP and TCP
 //!
 //! This module contains server implementations for handling HTTP requests
-//! from Stormworks and TCP connections from Tacview.
+//! and UDP datagrams from Stormworks and TCP connections from Tacview.
 
+mod acmi_stream;
 pub mod http_simple;
+mod shutdown;
 pub mod tcp;
+pub mod udp;
+mod websocket;
 
 pub use http_simple::HttpServer;
+pub use shutdown::{InFlight, InFlightGuard, ShutdownToken};
 pub use tcp::TcpServer;
+pub use udp::UdpServer;
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Signal telling servers to stop
+///
+/// Clones share the same signal, so one token can be handed to every server
+/// and cancelled once from wherever shutdown is decided.
+#[derive(Debug, Clone)]
+pub struct ShutdownToken {
+    sender: Arc<watch::Sender<bool>>,
+    receiver: watch::Receiver<bool>,
+}
+
+impl Default for ShutdownToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownToken {
+    pub fn new() -> Self {
+        let (sender, receiver) = watch::channel(false);
+        Self {
+            sender: Arc::new(sender),
+            receiver,
+        }
+    }
+
+    /// Tell every holder of the token to stop
+    pub fn cancel(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Wait until the token is cancelled
+    pub async fn cancelled(&self) {
+        let mut receiver = self.receiver.clone();
+        // The sender lives as long as any token, so this only returns once cancelled
+        let _ = receiver.wait_for(|&cancelled| cancelled).await;
+    }
+}
+
+/// Count of running tasks that shutdown waits for, such as requests
+/// being answered or clients being streamed to
+#[derive(Debug)]
+pub struct InFlight {
+    count: Arc<watch::Sender<usize>>,
+}
+
+impl Default for InFlight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InFlight {
+    pub fn new() -> Self {
+        Self {
+            count: Arc::new(watch::Sender::new(0)),
+        }
+    }
+
+    /// Count a task until the returned guard is dropped
+    pub fn start(&self) -> InFlightGuard {
+        self.count.send_modify(|count| *count += 1);
+        InFlightGuard {
+            count: self.count.clone(),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        *self.count.borrow()
+    }
+
+    /// Wait until no task is counted
+    pub async fn wait_idle(&self) {
+        let mut receiver = self.count.subscribe();
+        let _ = receiver.wait_for(|&count| count == 0).await;
+    }
+}
+
+/// Keeps a task counted by [`InFlight`] while alive
+#[derive(Debug)]
+pub struct InFlightGuard {
+    count: Arc<watch::Sender<usize>>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.count.send_modify(|count| *count -= 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_and_in_flight() {
+        let token = ShutdownToken::new();
+        let clone = token.clone();
+        let waiter = tokio::spawn(async move { clone.cancelled().await });
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiter.is_finished());
+        assert!(!token.is_cancelled());
+
+        token.cancel();
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(token.is_cancelled());
+        // Waiting on a cancelled token returns immediately
+        token.cancelled().await;
+
+        let in_flight = InFlight::new();
+        let guard = in_flight.start();
+        let second = in_flight.start();
+        assert_eq!(in_flight.count(), 2);
+        drop(second);
+        let idle = tokio::time::timeout(Duration::from_millis(20), in_flight.wait_idle()).await;
+        assert!(idle.is_err());
+        drop(guard);
+        in_flight.wait_idle().await;
+        assert_eq!(in_flight.count(), 0);
+    }
+}
@@ -1,4 +1,6 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use tokio::net::TcpListener;
 use tracing::{error, info};
@@ -6,6 +8,7 @@ use tracing::{error, info};
 use crate::domain::{AcmiRepository, RealTimeTelemetryRepository};
 use crate::handlers::AppState;
 use crate::infra::TcpRealTimeTelemetryRepository;
+use crate::server::ShutdownToken;
 
 /// TCP server for Tacview integration
 ///
@@ -13,27 +16,44 @@ use crate::infra::TcpRealTimeTelemetryRepository;
 /// performs the necessary handshake, and streams ACMI data in real-time.
 pub struct TcpServer {
     state: Arc<AppState>,
+    listener: TcpListener,
 }
 
 impl TcpServer {
-    /// Create a new TCP server
-    pub fn new(state: Arc<AppState>) -> Self {
-        Self { state }
+    /// Bind the TCP server to an address
+    ///
+    /// Port 0 picks a free port, see [`TcpServer::local_addr`].
+    pub async fn bind(addr: SocketAddr, state: Arc<AppState>) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to bind TCP server to {addr}"))?;
+        Ok(Self { state, listener })
     }
 
-    /// Start the TCP server
-    pub async fn start(&self, port: u16) -> Result<()> {
-        let listener = TcpListener::bind(format!("0.0.0.0:{port}")).await?;
-        info!("TCP server listening on port {}", port);
+    /// Address the server is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener
+            .local_addr()
+            .expect("bound TCP listener has a local address")
+    }
+
+    /// Accept Tacview connections until `shutdown` is cancelled
+    pub async fn run(self, shutdown: ShutdownToken) -> Result<()> {
+        info!("TCP server listening on {}", self.local_addr());
 
         loop {
-            match listener.accept().await {
+            let accepted = tokio::select! {
+                accepted = self.listener.accept() => accepted,
+                _ = shutdown.cancelled() => break,
+            };
+            match accepted {
                 Ok((stream, addr)) => {
                     info!("New Tacview connection from: {}", addr);
 
                     let state = self.state.clone();
+                    let shutdown = shutdown.clone();
                     tokio::spawn(async move {
-                        if let Err(e) = Self::handle_connection(stream, state).await {
+                        if let Err(e) = Self::handle_connection(stream, state, shutdown).await {
                             error!("Error handling Tacview connection: {}", e);
                         }
                     });
@@ -43,29 +63,63 @@ impl TcpServer {
                 }
             }
         }
+
+        info!("TCP server stopped");
+        Ok(())
     }
 
     /// Handle a single TCP connection
-    async fn handle_connection(stream: tokio::net::TcpStream, state: Arc<AppState>) -> Result<()> {
-        let repo = Arc::new(TcpRealTimeTelemetryRepository::new_with_verbose(
-            stream,
-            state.verbose,
-        ));
+    ///
+    /// A handshake still in progress when `shutdown` is cancelled is
+    /// abandoned, since the outputs may already have been closed.
+    async fn handle_connection(
+        stream: tokio::net::TcpStream,
+        state: Arc<AppState>,
+        shutdown: ShutdownToken,
+    ) -> Result<()> {
+        let peer = stream.peer_addr()?;
+        let repo = Arc::new(
+            TcpRealTimeTelemetryRepository::new_with_verbose(stream, state.is_verbose())
+                .with_reference_time(state.reference_time().await),
+        );
 
         // Perform handshake
-        if state.verbose {
+        if state.is_verbose() {
             info!("Starting Tacview handshake...");
         }
-        repo.handshake().await?;
+        tokio::select! {
+            result = repo.handshake() => result?,
+            _ = shutdown.cancelled() => {
+                info!("Shutting down, dropping Tacview connection from {}", peer);
+                return Ok(());
+            }
+        }
+        // Shutdown waits for the client until its output is closed
+        let _client = state.clients.start();
 
         // Add to repositories list
-        {
-            let mut repos = state.acmi_repositories.lock().await;
-            repos.push(repo.clone() as Arc<dyn AcmiRepository>);
-            info!(
-                "Added Tacview client to repositories (total: {})",
-                repos.len()
-            );
+        let output = state
+            .add_output(
+                repo.clone() as Arc<dyn AcmiRepository>,
+                Some(peer.to_string()),
+            )
+            .await;
+        state
+            .metrics
+            .tacview_clients
+            .fetch_add(1, Ordering::Relaxed);
+        info!(
+            "Added Tacview client to repositories as output {} (total: {})",
+            output.id(),
+            state.acmi_repositories.lock().await.len()
+        );
+        state.resync().await;
+
+        // An output added after shutdown closed the others is closed here
+        if shutdown.is_cancelled() {
+            if let Err(e) = output.close().await {
+                error!("Failed to close Tacview output {}: {}", output.id(), e);
+            }
         }
 
         // Wait for connection to close
@@ -74,19 +128,17 @@ impl TcpServer {
         }
 
         // Remove from repositories list
-        {
-            let mut repos = state.acmi_repositories.lock().await;
-            let initial_count = repos.len();
-            repos.retain(|r| {
-                // Check if this is the same repository by comparing memory addresses
-                !Arc::ptr_eq(r, &(repo.clone() as Arc<dyn AcmiRepository>))
-            });
-            info!(
-                "Removed Tacview client from repositories ({} -> {})",
-                initial_count,
-                repos.len()
-            );
-        }
+        let initial_count = state.acmi_repositories.lock().await.len();
+        state.remove_output(&output).await;
+        state
+            .metrics
+            .tacview_clients
+            .fetch_sub(1, Ordering::Relaxed);
+        info!(
+            "Removed Tacview client from repositories ({} -> {})",
+            initial_count,
+            state.acmi_repositories.lock().await.len()
+        );
 
         info!("Tacview connection closed");
         Ok(())
@@ -0,0 +1,369 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeSet, HashMap};
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tracing::{error, info, warn};
+
+use crate::handlers::AppState;
+pub use crate::metrics::UdpStats;
+use crate::server::http_simple::decode_base64_simple;
+use crate::server::ShutdownToken;
+
+/// Largest payload a single UDP datagram can carry
+const MAX_DATAGRAM_SIZE: usize = 65507;
+
+/// How far behind the expected sequence number a datagram may arrive before
+/// the sender is assumed to have restarted its counter
+const REORDER_WINDOW: u64 = 64;
+
+/// How long a sender may stay silent before its sequence tracking is dropped
+const IDLE_SENDER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// UDP ingest server for Stormworks telemetry
+///
+/// This server accepts ACMI data as UDP datagrams, avoiding the per-frame
+/// HTTP request/response round trip. Each datagram may start with a header
+/// line carrying a sequence number and an optional encoding:
+///
+/// ```text
+/// <sequence> [b64]
+/// <ACMI lines, or base64 of them when "b64" is given>
+/// ```
+///
+/// Datagrams without a header line are treated as plain ACMI and are not
+/// checked for loss or reordering. Late datagrams are dropped so that stale
+/// positions never overwrite newer ones.
+pub struct UdpServer {
+    state: Arc<AppState>,
+    socket: UdpSocket,
+}
+
+/// Outcome of checking a sequence number against the expected one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SequenceOutcome {
+    /// The datagram is the next one expected
+    InOrder,
+    /// The datagram skipped ahead; the given number of datagrams are missing
+    Gap(u64),
+    /// The datagram is older than one already received, and may be one
+    /// that was counted as missing
+    Late { filled_gap: bool },
+    /// The sender restarted its counter
+    Reset,
+}
+
+/// Per-sender sequence number tracking
+#[derive(Debug)]
+struct SequenceTracker {
+    /// Highest sequence number received
+    last: Option<u64>,
+    /// Sequence numbers counted as lost that may still arrive late
+    missing: BTreeSet<u64>,
+    last_seen: Instant,
+}
+
+impl Default for SequenceTracker {
+    fn default() -> Self {
+        Self {
+            last: None,
+            missing: BTreeSet::new(),
+            last_seen: Instant::now(),
+        }
+    }
+}
+
+impl SequenceTracker {
+    fn observe(&mut self, seq: u64) -> SequenceOutcome {
+        if self.is_idle() {
+            *self = Self::default();
+        }
+        self.last_seen = Instant::now();
+        let outcome = match self.last {
+            None => SequenceOutcome::InOrder,
+            Some(last) if last.checked_add(1) == Some(seq) => SequenceOutcome::InOrder,
+            Some(last) if seq > last => {
+                // Only numbers within the window can still arrive late
+                self.missing
+                    .extend((last + 1).max(seq.saturating_sub(REORDER_WINDOW - 1))..seq);
+                SequenceOutcome::Gap(seq - last - 1)
+            }
+            Some(last) if seq == 0 || last - seq >= REORDER_WINDOW => {
+                self.missing.clear();
+                SequenceOutcome::Reset
+            }
+            Some(_) => {
+                return SequenceOutcome::Late {
+                    filled_gap: self.missing.remove(&seq),
+                }
+            }
+        };
+
+        self.last = Some(seq);
+        self.missing = self
+            .missing
+            .split_off(&seq.saturating_sub(REORDER_WINDOW - 1));
+        outcome
+    }
+
+    fn is_idle(&self) -> bool {
+        self.last_seen.elapsed() > IDLE_SENDER_TIMEOUT
+    }
+}
+
+/// A decoded datagram
+#[derive(Debug, PartialEq, Eq)]
+struct Datagram {
+    sequence: Option<u64>,
+    acmi: String,
+}
+
+impl UdpServer {
+    /// Bind the UDP ingest server to an address
+    ///
+    /// Port 0 picks a free port, see [`UdpServer::local_addr`].
+    pub async fn bind(addr: SocketAddr, state: Arc<AppState>) -> Result<Self> {
+        let socket = UdpSocket::bind(addr)
+            .await
+            .with_context(|| format!("Failed to bind UDP ingest server to {addr}"))?;
+        Ok(Self { state, socket })
+    }
+
+    /// Address the server is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.socket
+            .local_addr()
+            .expect("bound UDP socket has a local address")
+    }
+
+    /// Counters for datagrams handled by this server
+    pub fn stats(&self) -> Arc<UdpStats> {
+        self.state.metrics.udp.clone()
+    }
+
+    /// Receive datagrams until `shutdown` is cancelled
+    pub async fn run(self, shutdown: ShutdownToken) -> Result<()> {
+        info!("UDP ingest server listening on {}", self.local_addr());
+
+        let mut trackers: HashMap<SocketAddr, SequenceTracker> = HashMap::new();
+        let mut buffer = vec![0u8; MAX_DATAGRAM_SIZE];
+
+        loop {
+            let received = tokio::select! {
+                received = self.socket.recv_from(&mut buffer) => received,
+                _ = shutdown.cancelled() => break,
+            };
+            let (len, addr) = match received {
+                Ok(received) => received,
+                Err(e) => {
+                    error!("Failed to receive UDP datagram: {}", e);
+                    continue;
+                }
+            };
+
+            let count = self
+                .state
+                .metrics
+                .udp
+                .received
+                .fetch_add(1, Ordering::Relaxed)
+                + 1;
+            if count.is_multiple_of(500) {
+                self.log_stats();
+                trackers.retain(|_, tracker| !tracker.is_idle());
+            }
+
+            let datagram = match decode_datagram(&buffer[..len]) {
+                Some(datagram) => datagram,
+                None => {
+                    warn!(
+                        "Dropping undecodable UDP datagram from {} ({} bytes)",
+                        addr, len
+                    );
+                    self.state
+                        .metrics
+                        .udp
+                        .dropped
+                        .fetch_add(1, Ordering::Relaxed);
+                    self.state.metrics.decode_failures.inc("udp");
+                    continue;
+                }
+            };
+
+            if let Some(seq) = datagram.sequence {
+                match trackers.entry(addr).or_default().observe(seq) {
+                    SequenceOutcome::InOrder => {}
+                    SequenceOutcome::Gap(missing) => {
+                        if self.state.is_verbose() {
+                            info!(
+                                "Lost {} UDP datagram(s) from {} before #{}",
+                                missing, addr, seq
+                            );
+                        }
+                        self.state
+                            .metrics
+                            .udp
+                            .lost
+                            .fetch_add(missing, Ordering::Relaxed);
+                    }
+                    SequenceOutcome::Late { filled_gap } => {
+                        if self.state.is_verbose() {
+                            info!("Dropping late UDP datagram #{} from {}", seq, addr);
+                        }
+                        if filled_gap {
+                            // It was not lost after all, only too late to use
+                            self.state.metrics.udp.lost.fetch_sub(1, Ordering::Relaxed);
+                        }
+                        self.state
+                            .metrics
+                            .udp
+                            .reordered
+                            .fetch_add(1, Ordering::Relaxed);
+                        self.state
+                            .metrics
+                            .udp
+                            .dropped
+                            .fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                    SequenceOutcome::Reset => {
+                        info!("UDP sender {} restarted its sequence at #{}", addr, seq);
+                    }
+                }
+            }
+
+            if datagram.acmi.trim().is_empty() {
+                self.state
+                    .metrics
+                    .udp
+                    .dropped
+                    .fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+
+            self.state.record_frame("udp");
+            self.state.validate_frame("udp", &datagram.acmi);
+            self.state.ingest(&datagram.acmi).await;
+            self.state
+                .metrics
+                .udp
+                .forwarded
+                .fetch_add(1, Ordering::Relaxed);
+        }
+
+        info!("UDP ingest server stopped");
+        Ok(())
+    }
+
+    /// Log the current datagram counters
+    fn log_stats(&self) {
+        info!(
+            "UDP ingest: {} received, {} forwarded, {} lost, {} reordered, {} dropped",
+            self.state.metrics.udp.received.load(Ordering::Relaxed),
+            self.state.metrics.udp.forwarded.load(Ordering::Relaxed),
+            self.state.metrics.udp.lost.load(Ordering::Relaxed),
+            self.state.metrics.udp.reordered.load(Ordering::Relaxed),
+            self.state.metrics.udp.dropped.load(Ordering::Relaxed),
+        );
+    }
+}
+
+/// Split a datagram into its optional header and ACMI payload
+///
+/// The returned ACMI always ends with a newline, matching the HTTP endpoint.
+fn decode_datagram(bytes: &[u8]) -> Option<Datagram> {
+    let text = std::str::from_utf8(bytes).ok()?;
+
+    let (first_line, rest) = text.split_once('\n').unwrap_or((text, ""));
+    let mut header = first_line.trim_end_matches('\r').split_whitespace();
+
+    let sequence = header.next().and_then(|s| s.parse::<u64>().ok());
+    let (sequence, payload) = match sequence {
+        Some(seq) => match header.next() {
+            None => (Some(seq), rest.to_string()),
+            Some("b64") if header.next().is_none() => {
+                (Some(seq), decode_base64_simple(rest.trim()).ok()?)
+            }
+            Some(_) => return None,
+        },
+        None => (None, text.to_string()),
+    };
+
+    let mut acmi = payload;
+    if !acmi.ends_with('\n') {
+        acmi.push('\n');
+    }
+
+    Some(Datagram { sequence, acmi })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_plain_datagram() {
+        let datagram = decode_datagram(b"#1.5\n101,T=1|2|3").unwrap();
+        assert_eq!(datagram.sequence, None);
+        assert_eq!(datagram.acmi, "#1.5\n101,T=1|2|3\n");
+    }
+
+    #[test]
+    fn test_decode_sequenced_datagrams() {
+        let datagram = decode_datagram(b"42\n#1.5\n").unwrap();
+        assert_eq!(datagram.sequence, Some(42));
+        assert_eq!(datagram.acmi, "#1.5\n");
+
+        // "#1.5" in base64
+        let datagram = decode_datagram(b"43 b64\nIzEuNQ==").unwrap();
+        assert_eq!(datagram.sequence, Some(43));
+        assert_eq!(datagram.acmi, "#1.5\n");
+
+        assert!(decode_datagram(b"44 gzip\n....").is_none());
+        assert!(decode_datagram(b"45 b64\n#not-base64").is_none());
+    }
+
+    #[test]
+    fn test_sequence_tracker() {
+        let mut tracker = SequenceTracker::default();
+        assert_eq!(tracker.observe(10), SequenceOutcome::InOrder);
+        assert_eq!(tracker.observe(11), SequenceOutcome::InOrder);
+        assert_eq!(tracker.observe(14), SequenceOutcome::Gap(2));
+        assert_eq!(
+            tracker.observe(12),
+            SequenceOutcome::Late { filled_gap: true }
+        );
+        // A duplicate only fills the gap once
+        assert_eq!(
+            tracker.observe(12),
+            SequenceOutcome::Late { filled_gap: false }
+        );
+        assert_eq!(
+            tracker.observe(11),
+            SequenceOutcome::Late { filled_gap: false }
+        );
+        assert_eq!(tracker.observe(15), SequenceOutcome::InOrder);
+        assert_eq!(tracker.observe(0), SequenceOutcome::Reset);
+        assert_eq!(tracker.observe(1), SequenceOutcome::InOrder);
+        assert!(!tracker.is_idle());
+    }
+
+    #[test]
+    fn test_sequence_tracker_near_the_end_of_the_counter() {
+        let mut tracker = SequenceTracker::default();
+        assert_eq!(tracker.observe(u64::MAX - 2), SequenceOutcome::InOrder);
+        assert_eq!(tracker.observe(u64::MAX), SequenceOutcome::Gap(1));
+        assert_eq!(
+            tracker.observe(u64::MAX - 1),
+            SequenceOutcome::Late { filled_gap: true }
+        );
+
+        // A long gap only remembers the numbers that can still arrive late
+        let mut tracker = SequenceTracker::default();
+        tracker.observe(0);
+        assert_eq!(tracker.observe(1_000_000), SequenceOutcome::Gap(999_999));
+        assert_eq!(tracker.missing.len(), REORDER_WINDOW as usize - 1);
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use sha1::{Digest, Sha1};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tracing::info;
+
+use crate::infra::WebSocketFeedRepository;
+use crate::server::http_simple::header_value;
+
+/// GUID appended to the client key when computing the accept key (RFC 6455)
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest client frame accepted; clients only send control frames
+const MAX_CLIENT_FRAME: u64 = 4096;
+
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// Check whether an HTTP request asks for a WebSocket upgrade
+pub(crate) fn is_upgrade_request(request: &str) -> bool {
+    header_value(request, "Upgrade").is_some_and(|v| v.eq_ignore_ascii_case("websocket"))
+}
+
+/// Compute the `Sec-WebSocket-Accept` value for a client key
+fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.trim().as_bytes());
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+    base64::engine::general_purpose::STANDARD.encode(hasher.finalize())
+}
+
+/// Encode an unmasked server-to-client frame
+fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Read a single client-to-server frame, returning its opcode and payload
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header).await?;
+
+    let opcode = header[0] & 0x0F;
+    let masked = header[1] & 0x80 != 0;
+    let len = match header[1] & 0x7F {
+        126 => reader.read_u16().await? as u64,
+        127 => reader.read_u64().await?,
+        len => len as u64,
+    };
+
+    if len > MAX_CLIENT_FRAME {
+        anyhow::bail!("WebSocket client frame too large: {len} bytes");
+    }
+
+    let mut mask = [0u8; 4];
+    if masked {
+        reader.read_exact(&mut mask).await?;
+    }
+
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload).await?;
+    if masked {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+
+    Ok((opcode, payload))
+}
+
+/// Complete the WebSocket handshake and stream the live feed to the client
+///
+/// Runs until the client closes the connection or falls too far behind.
+pub(crate) async fn serve(
+    socket: TcpStream,
+    request: &str,
+    feed: Arc<WebSocketFeedRepository>,
+) -> Result<()> {
+    let key = header_value(request, "Sec-WebSocket-Key")
+        .context("WebSocket request without Sec-WebSocket-Key")?;
+
+    let (mut reader, mut writer) = socket.into_split();
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+        Upgrade: websocket\r\n\
+        Connection: Upgrade\r\n\
+        Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    );
+    writer.write_all(response.as_bytes()).await?;
+
+    let mut updates = feed.subscribe();
+
+    // Read client frames on a separate task so a partially received frame
+    // is never lost when an update is ready to be sent
+    let (control_tx, mut control) = mpsc::channel::<(u8, Vec<u8>)>(8);
+    let reader_task = tokio::spawn(async move {
+        while let Ok(frame) = read_frame(&mut reader).await {
+            let opcode = frame.0;
+            if control_tx.send(frame).await.is_err() || opcode == OPCODE_CLOSE {
+                break;
+            }
+        }
+    });
+
+    let result = async {
+        loop {
+            tokio::select! {
+                update = updates.recv() => match update {
+                    Some(json) => writer.write_all(&encode_frame(OPCODE_TEXT, json.as_bytes())).await?,
+                    None => {
+                        writer.write_all(&encode_frame(OPCODE_CLOSE, &[])).await?;
+                        break;
+                    }
+                },
+                frame = control.recv() => match frame {
+                    Some((OPCODE_PING, payload)) => {
+                        writer.write_all(&encode_frame(OPCODE_PONG, &payload)).await?;
+                    }
+                    Some((OPCODE_CLOSE, _)) | None => {
+                        let _ = writer.write_all(&encode_frame(OPCODE_CLOSE, &[])).await;
+                        break;
+                    }
+                    Some(_) => {}
+                },
+            }
+        }
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+
+    reader_task.abort();
+    info!("WebSocket client disconnected");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key() {
+        // Example from RFC 6455 section 1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[tokio::test]
+    async fn test_frame_round_trip() {
+        let frame = encode_frame(OPCODE_TEXT, &[b'x'; 300]);
+        assert_eq!(&frame[..4], &[0x81, 126, 0x01, 0x2C]);
+
+        let (opcode, payload) = read_frame(&mut frame.as_slice()).await.unwrap();
+        assert_eq!(opcode, OPCODE_TEXT);
+        assert_eq!(payload.len(), 300);
+    }
+}
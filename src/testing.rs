@@ -0,0 +1,277 @@
+//! Stand-ins for Stormworks and Tacview
+//!
+//! These talk to the bridge over the network the way the Stormworks addon
+//! and Tacview do, for end-to-end tests and the `simulate` load generator.
+
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::acmi::{parse_records, AcmiRecord};
+
+/// Handshake a Tacview client sends after the server's, ending in a NUL byte
+pub const CLIENT_HANDSHAKE: &str =
+    "XtraLib.Stream.0\nTacview.RealTimeTelemetry.0\nMock Tacview\n0\0";
+
+/// How long to wait for the server's handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A Tacview client connected to the bridge's TCP server
+///
+/// The client performs the XtraLib.Stream handshake and keeps everything
+/// the server sends afterwards, so tests can check what Tacview would see.
+#[derive(Debug)]
+pub struct MockTacviewClient {
+    stream: TcpStream,
+    host: String,
+    received: String,
+    /// Bytes of a character split across reads, waiting for the rest
+    partial: Vec<u8>,
+}
+
+impl MockTacviewClient {
+    /// Connect to a Tacview real-time telemetry server and complete the
+    /// handshake
+    pub async fn connect(addr: SocketAddr) -> Result<Self> {
+        let mut stream = TcpStream::connect(addr)
+            .await
+            .with_context(|| format!("Failed to connect to {addr}"))?;
+
+        let mut handshake = Vec::new();
+        tokio::time::timeout(HANDSHAKE_TIMEOUT, async {
+            let mut buffer = [0u8; 1024];
+            while !handshake.contains(&0) {
+                let n = stream.read(&mut buffer).await?;
+                if n == 0 {
+                    bail!("server closed the connection during the handshake");
+                }
+                handshake.extend_from_slice(&buffer[..n]);
+            }
+            Ok(())
+        })
+        .await
+        .context("Timed out waiting for the server handshake")??;
+
+        // Anything after the NUL byte is already ACMI
+        let end = handshake.iter().position(|&b| b == 0).unwrap();
+        let mut received = String::new();
+        let mut partial = handshake[end + 1..].to_vec();
+        decode_utf8(&mut partial, &mut received);
+        let handshake = String::from_utf8_lossy(&handshake[..end]).into_owned();
+        let lines: Vec<&str> = handshake.lines().collect();
+        let host = match lines.as_slice() {
+            ["XtraLib.Stream.0", "Tacview.RealTimeTelemetry.0", host, ..] => {
+                host.strip_prefix("Host ").unwrap_or(host).to_string()
+            }
+            _ => bail!("unexpected server handshake: {handshake:?}"),
+        };
+
+        stream.write_all(CLIENT_HANDSHAKE.as_bytes()).await?;
+        Ok(Self {
+            stream,
+            host,
+            received,
+            partial,
+        })
+    }
+
+    /// Host name the server sent in its handshake
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// ACMI received since the handshake
+    pub fn received(&self) -> &str {
+        &self.received
+    }
+
+    /// Records received since the handshake, skipping lines that do not parse
+    pub fn records(&self) -> Vec<AcmiRecord> {
+        parse_records(&self.received)
+    }
+
+    /// Read until the received ACMI contains `text`
+    ///
+    /// Fails if the server closes the connection or `timeout` passes first.
+    pub async fn read_until(&mut self, text: &str, timeout: Duration) -> Result<()> {
+        let read = async {
+            let mut buffer = [0u8; 4096];
+            while !self.received.contains(text) {
+                let n = self.stream.read(&mut buffer).await?;
+                if n == 0 {
+                    bail!("server closed the connection");
+                }
+                self.partial.extend_from_slice(&buffer[..n]);
+                decode_utf8(&mut self.partial, &mut self.received);
+            }
+            Ok(())
+        };
+        match tokio::time::timeout(timeout, read).await {
+            Ok(result) => result.with_context(|| format!("Waiting for {text:?}")),
+            Err(_) => bail!(
+                "timed out waiting for {text:?}, received {:?}",
+                self.received
+            ),
+        }
+    }
+
+    /// Read until the server ends the stream
+    ///
+    /// Fails if `timeout` passes first.
+    pub async fn read_to_end(&mut self, timeout: Duration) -> Result<()> {
+        let mut rest = Vec::new();
+        let read = tokio::time::timeout(timeout, self.stream.read_to_end(&mut rest)).await;
+        // Nothing follows, so an incomplete character stays incomplete
+        self.partial.extend_from_slice(&rest);
+        self.received
+            .push_str(&String::from_utf8_lossy(&std::mem::take(&mut self.partial)));
+        match read {
+            Ok(result) => result
+                .map(|_| ())
+                .context("Reading to the end of the stream"),
+            Err(_) => bail!("timed out waiting for the server to end the stream"),
+        }
+    }
+
+    /// Check that every line in `lines` was received, in that order
+    ///
+    /// Other lines may come between them.
+    #[track_caller]
+    pub fn assert_in_order(&self, lines: &[&str]) {
+        let mut remaining = self.received.lines();
+        for line in lines {
+            assert!(
+                remaining.any(|received| received == *line),
+                "{line:?} not received after the previous lines, received:\n{}",
+                self.received
+            );
+        }
+    }
+
+    /// Check that time frames never go backwards
+    #[track_caller]
+    pub fn assert_time_ordered(&self) {
+        let mut previous = f64::NEG_INFINITY;
+        for record in self.records() {
+            if let AcmiRecord::TimeFrame(time) = record {
+                assert!(
+                    time >= previous,
+                    "time frame #{time} after #{previous}, received:\n{}",
+                    self.received
+                );
+                previous = time;
+            }
+        }
+    }
+}
+
+/// Move the complete UTF-8 text at the start of `bytes` to `text`
+///
+/// A character cut off at the end stays in `bytes` until the rest arrives.
+/// Invalid bytes are replaced with U+FFFD.
+fn decode_utf8(bytes: &mut Vec<u8>, text: &mut String) {
+    let mut start = 0;
+    loop {
+        match std::str::from_utf8(&bytes[start..]) {
+            Ok(valid) => {
+                text.push_str(valid);
+                start = bytes.len();
+                break;
+            }
+            Err(e) => {
+                let valid_end = start + e.valid_up_to();
+                text.push_str(&String::from_utf8_lossy(&bytes[start..valid_end]));
+                let Some(invalid) = e.error_len() else {
+                    start = valid_end;
+                    break;
+                };
+                text.push(char::REPLACEMENT_CHARACTER);
+                start = valid_end + invalid;
+            }
+        }
+    }
+    bytes.drain(..start);
+}
+
+/// Send a GET request and return the response status code
+pub async fn http_get(addr: SocketAddr, path: &str) -> Result<u16> {
+    http_request(addr, "GET", path).await
+}
+
+/// Send a request without a body and return the response status code
+pub async fn http_request(addr: SocketAddr, method: &str, path: &str) -> Result<u16> {
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .with_context(|| format!("Failed to connect to {addr}"))?;
+    let request = format!("{method} {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    let status = String::from_utf8_lossy(&response)
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .context("Invalid HTTP response")?;
+    Ok(status)
+}
+
+/// Send ACMI to the bridge's HTTP server as the Stormworks addon does,
+/// returning the response status code
+pub async fn send_acmi(addr: SocketAddr, acmi: &str) -> Result<u16> {
+    http_get(addr, &format!("/acmi/{}", STANDARD.encode(acmi))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{AcmiRepository, RealTimeTelemetryRepository};
+    use crate::infra::TcpRealTimeTelemetryRepository;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_decode_utf8_across_reads() {
+        let name = "Name=Möwe ✈\n".as_bytes();
+        let mut text = String::new();
+        let mut partial = Vec::new();
+
+        // Feed one byte at a time, splitting every multi-byte character
+        for byte in name {
+            partial.push(*byte);
+            decode_utf8(&mut partial, &mut text);
+        }
+        assert_eq!(text, "Name=Möwe ✈\n");
+        assert!(partial.is_empty());
+
+        partial.extend_from_slice(b"a\xffb\xe2\x9c");
+        decode_utf8(&mut partial, &mut text);
+        assert!(text.ends_with("a\u{fffd}b"));
+        assert_eq!(partial, b"\xe2\x9c");
+    }
+
+    #[tokio::test]
+    async fn test_handshake_with_telemetry_repository() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let repo = TcpRealTimeTelemetryRepository::new(stream);
+            repo.handshake().await.unwrap();
+            repo.write("#1\n101,T=1|2|3\n#2\n").await.unwrap();
+        });
+
+        let mut client = MockTacviewClient::connect(addr).await.unwrap();
+        assert_eq!(client.host(), "stormworks");
+        client.read_until("#2\n", HANDSHAKE_TIMEOUT).await.unwrap();
+        assert!(client
+            .received()
+            .starts_with("FileType=text/acmi/tacview\n"));
+        client.assert_in_order(&["0,ReferenceLongitude=180", "#1", "101,T=1|2|3", "#2"]);
+        client.assert_time_ordered();
+        server.await.unwrap();
+    }
+}
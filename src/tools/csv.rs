@@ -0,0 +1,290 @@
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::acmi::{logical_lines, parse_records, AcmiRecord, ObjectState, WorldState};
+
+/// Columns written when none are selected
+pub const DEFAULT_COLUMNS: [&str; 11] = [
+    "time",
+    "id",
+    "name",
+    "type",
+    "coalition",
+    "lon",
+    "lat",
+    "alt",
+    "roll",
+    "pitch",
+    "yaw",
+];
+
+/// Properties covered by the default columns, left out of the numeric ones
+const NAMED_PROPERTIES: [&str; 3] = ["Name", "Type", "Coalition"];
+
+/// Turns object updates into CSV rows
+///
+/// Each row holds the state of the object after the update, so properties
+/// sent earlier are repeated on every row. Columns other than the default
+/// ones are looked up as object properties by name, e.g. `IAS` or `Pilot`.
+#[derive(Debug, Default)]
+pub struct CsvWriter {
+    columns: Vec<String>,
+    world: WorldState,
+}
+
+impl CsvWriter {
+    pub fn new(columns: Vec<String>) -> Self {
+        Self {
+            columns,
+            world: WorldState::new(),
+        }
+    }
+
+    /// Change the columns of the rows returned from now on
+    pub fn set_columns(&mut self, columns: Vec<String>) {
+        self.columns = columns;
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// The header line, ending with a newline
+    pub fn header(&self) -> String {
+        let fields: Vec<_> = self.columns.iter().map(|c| escape_field(c)).collect();
+        format!("{}\n", fields.join(","))
+    }
+
+    /// Apply ACMI text and return a row for each object update in it
+    ///
+    /// Rows end with a newline and are paired with the ID of their object.
+    pub fn rows(&mut self, acmi: &str) -> Vec<(u64, String)> {
+        let mut rows = Vec::new();
+        for (_, line) in logical_lines(acmi) {
+            let Ok(record) = AcmiRecord::parse(&line) else {
+                continue;
+            };
+            self.world.apply(&record);
+
+            if let AcmiRecord::Update(update) = &record {
+                if let Some(object) = self.world.object(update.id) {
+                    rows.push((update.id, self.row(object)));
+                }
+            }
+        }
+        rows
+    }
+
+    fn row(&self, object: &ObjectState) -> String {
+        let t = &object.transform;
+        let fields: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| {
+                let number = match column.as_str() {
+                    "time" => Some(self.world.time),
+                    "lon" => t
+                        .longitude
+                        .map(|lon| round_degrees(lon + self.world.reference_longitude())),
+                    "lat" => t
+                        .latitude
+                        .map(|lat| round_degrees(lat + self.world.reference_latitude())),
+                    "alt" => t.altitude,
+                    "roll" => t.roll,
+                    "pitch" => t.pitch,
+                    "yaw" => t.yaw,
+                    _ => None,
+                };
+                if let Some(number) = number {
+                    return number.to_string();
+                }
+
+                let value = match column.as_str() {
+                    "id" => return format!("{:x}", object.id),
+                    "name" => object.name(),
+                    "type" => object.object_type(),
+                    "coalition" => object.coalition(),
+                    "time" | "lon" | "lat" | "alt" | "roll" | "pitch" | "yaw" => None,
+                    property => object.property(property),
+                };
+                value
+                    .map(|v| escape_field(v).into_owned())
+                    .unwrap_or_default()
+            })
+            .collect();
+        format!("{}\n", fields.join(","))
+    }
+}
+
+/// Round an absolute longitude or latitude to about a centimeter, hiding
+/// the noise from adding the reference offset
+fn round_degrees(degrees: f64) -> f64 {
+    (degrees * 1e7).round() / 1e7
+}
+
+/// Quote a field containing separators, quotes or line breaks
+fn escape_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// Object properties whose values are all numbers, sorted by name
+pub fn numeric_properties(acmi: &str) -> Vec<String> {
+    let mut properties = NumericProperties::default();
+    properties.observe(acmi);
+    properties.names()
+}
+
+/// Tracks which object properties only ever had numeric values
+#[derive(Debug, Default)]
+pub struct NumericProperties {
+    numeric: BTreeSet<String>,
+    other: HashSet<String>,
+}
+
+impl NumericProperties {
+    /// Learn the properties of the object updates in ACMI text
+    pub fn observe(&mut self, acmi: &str) {
+        for record in parse_records(acmi) {
+            let AcmiRecord::Update(update) = record else {
+                continue;
+            };
+            if update.is_global() {
+                continue;
+            }
+            for (key, value) in &update.properties {
+                if key == "T" || NAMED_PROPERTIES.contains(&key.as_str()) {
+                    continue;
+                }
+                if value.parse::<f64>().is_ok_and(f64::is_finite) {
+                    if !self.numeric.contains(key) {
+                        self.numeric.insert(key.clone());
+                    }
+                } else if !self.other.contains(key) {
+                    self.other.insert(key.clone());
+                }
+            }
+        }
+    }
+
+    /// Properties seen so far whose values were all numbers, sorted by name
+    pub fn names(&self) -> Vec<String> {
+        self.numeric
+            .iter()
+            .filter(|key| !self.other.contains(*key))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Columns for a recording: the selection if given, otherwise the default
+/// columns followed by every numeric property
+pub fn columns_for(acmi: &str, selection: &[String]) -> Vec<String> {
+    if !selection.is_empty() {
+        return selection.to_vec();
+    }
+    DEFAULT_COLUMNS
+        .iter()
+        .map(|c| c.to_string())
+        .chain(numeric_properties(acmi))
+        .collect()
+}
+
+/// Path of the CSV file for one object in one-file-per-object mode
+pub fn object_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{id:x}.csv"))
+}
+
+/// Convert a recording to CSV
+///
+/// Without `per_object` a single table is returned under `None`; with it,
+/// one table per object keyed by its ID.
+pub fn to_csv(acmi: &str, columns: Vec<String>, per_object: bool) -> BTreeMap<Option<u64>, String> {
+    let mut writer = CsvWriter::new(columns);
+    let header = writer.header();
+    let mut tables: BTreeMap<Option<u64>, String> = BTreeMap::new();
+
+    for (id, row) in writer.rows(acmi) {
+        let key = per_object.then_some(id);
+        tables
+            .entry(key)
+            .or_insert_with(|| header.clone())
+            .push_str(&row);
+    }
+    if !per_object {
+        tables.entry(None).or_insert(header);
+    }
+    tables
+}
+
+/// Write CSV tables returned by [`to_csv`]
+///
+/// A single table goes to `output`, or to standard output when there is
+/// none. Tables per object go to `<id>.csv` files in the `output` directory.
+pub fn write_csv(tables: &BTreeMap<Option<u64>, String>, output: Option<&Path>) -> Result<()> {
+    for (id, table) in tables {
+        match (id, output) {
+            (None, None) => print!("{table}"),
+            (None, Some(path)) => {
+                std::fs::write(path, table).with_context(|| format!("Failed to write {path:?}"))?
+            }
+            (Some(id), Some(dir)) => {
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create directory {dir:?}"))?;
+                let path = object_path(dir, *id);
+                std::fs::write(&path, table)
+                    .with_context(|| format!("Failed to write {path:?}"))?;
+            }
+            (Some(_), None) => {
+                anyhow::bail!("an output directory is needed for one file per object")
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_rows_per_update() {
+        let acmi = "FileType=text/acmi/tacview\n\
+            0,ReferenceLongitude=180\n\
+            #0\n\
+            101,T=0.5|1|100|1|2|90,Name=Heli\\, Mk2,Type=Air+Rotorcraft,Fuel=0.5\n\
+            102,T=1|1|0,Name=Boat,Callsign=Alpha\n\
+            #1.5\n\
+            101,T=||150,Fuel=0.4\n";
+
+        let columns = columns_for(acmi, &[]);
+        assert_eq!(columns.last().map(String::as_str), Some("Fuel"));
+
+        let tables = to_csv(acmi, columns, false);
+        assert_eq!(
+            tables[&None],
+            "time,id,name,type,coalition,lon,lat,alt,roll,pitch,yaw,Fuel\n\
+             0,101,\"Heli, Mk2\",Air+Rotorcraft,,180.5,1,100,1,2,90,0.5\n\
+             0,102,Boat,,,181,1,0,,,,\n\
+             1.5,101,\"Heli, Mk2\",Air+Rotorcraft,,180.5,1,150,1,2,90,0.4\n"
+        );
+
+        let selection = vec![
+            "time".to_string(),
+            "alt".to_string(),
+            "Callsign".to_string(),
+        ];
+        let tables = to_csv(acmi, selection, true);
+        assert_eq!(tables.len(), 2);
+        assert_eq!(
+            tables[&Some(0x101)],
+            "time,alt,Callsign\n0,100,\n1.5,150,\n"
+        );
+        assert_eq!(tables[&Some(0x102)], "time,alt,Callsign\n0,0,Alpha\n");
+    }
+}
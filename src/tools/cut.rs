@@ -0,0 +1,162 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Utc};
+use std::fmt::Write;
+
+use crate::acmi::{logical_lines, AcmiRecord, ObjectUpdate, WorldState, GLOBAL_OBJECT_ID};
+
+/// Extract the part of a recording between two time frames
+///
+/// The result starts at `#0` with ReferenceTime moved forward by `from`
+/// seconds, so the clock shown in Tacview is unchanged. The state of every
+/// object at the cut point is written first so objects that were last
+/// updated before `from` still appear. Events before `from` are dropped.
+pub fn cut(acmi: &str, from: f64, to: f64) -> Result<String> {
+    if from >= to {
+        bail!("the start of the cut (#{from}) must be before its end (#{to})");
+    }
+
+    let mut world = WorldState::new();
+    let mut out: Option<String> = None;
+    let mut last_frame = None;
+
+    for (_, line) in logical_lines(acmi) {
+        let record = AcmiRecord::parse(&line).ok();
+
+        if let Some(AcmiRecord::TimeFrame(time)) = record {
+            if time > to {
+                break;
+            }
+            if time >= from {
+                let out = out.get_or_insert_with(|| snapshot(&world, from));
+                let time = shift_time(time, -from);
+                if last_frame != Some(time) {
+                    writeln!(out, "#{time}")?;
+                    last_frame = Some(time);
+                }
+                continue;
+            }
+        }
+
+        match (&mut out, record) {
+            (Some(out), _) => writeln!(out, "{line}")?,
+            (None, Some(record)) => world.apply(&record),
+            // Invalid lines before the cut are dropped with the rest
+            (None, None) => {}
+        }
+    }
+
+    match out {
+        Some(out) => Ok(out),
+        None => bail!("no time frames between #{from} and #{to}"),
+    }
+}
+
+/// Headers, global properties and objects at the cut point, at `#0`
+fn snapshot(world: &WorldState, from: f64) -> String {
+    let mut out = String::new();
+    for (key, value) in &world.headers {
+        out.push_str(&format!("{key}={value}\n"));
+    }
+    for (key, value) in &world.global {
+        let value = match key.as_str() {
+            "ReferenceTime" => shift_reference_time(value, from).unwrap_or_else(|| value.clone()),
+            _ => value.clone(),
+        };
+        let update = ObjectUpdate::new(GLOBAL_OBJECT_ID).with(key, value);
+        out.push_str(&format!("{}\n", AcmiRecord::Update(update)));
+    }
+
+    out.push_str("#0\n");
+    let mut objects: Vec<_> = world.objects().collect();
+    objects.sort_unstable_by_key(|object| object.id);
+    for object in objects {
+        out.push_str(&format!("{}\n", AcmiRecord::Update(object.to_update())));
+    }
+    out
+}
+
+/// Add an offset to a time frame, rounded to the microsecond so that
+/// rebased frames do not pick up floating point noise
+pub(crate) fn shift_time(time: f64, offset: f64) -> f64 {
+    ((time + offset) * 1e6).round() / 1e6
+}
+
+/// Parse a ReferenceTime such as `2023-01-01T00:00:00.000Z`
+pub(crate) fn parse_reference_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Move a ReferenceTime forward by a number of seconds
+///
+/// Returns `None` when the value is not an RFC 3339 timestamp.
+pub(crate) fn shift_reference_time(value: &str, seconds: f64) -> Option<String> {
+    let time = parse_reference_time(value)?;
+    let shifted = time + Duration::microseconds((seconds * 1e6).round() as i64);
+    Some(shifted.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+}
+
+/// Parse a position in a recording given as seconds, `m:ss` or `h:mm:ss`
+pub fn parse_offset(value: &str) -> Result<f64, String> {
+    let mut seconds = 0.0;
+    for part in value.split(':') {
+        let part: f64 = part
+            .parse()
+            .map_err(|_| format!("expected seconds, m:ss or h:mm:ss, got {value:?}"))?;
+        seconds = seconds * 60.0 + part;
+    }
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(format!("offset must not be negative, got {value:?}"));
+    }
+    Ok(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cut_carries_state_and_rebases_time() {
+        let acmi = "FileType=text/acmi/tacview\n\
+            FileVersion=2.2\n\
+            0,ReferenceTime=2023-01-01T00:00:00.000Z\n\
+            #0\n\
+            101,T=1|2|100,Name=Heli,Type=Air+Rotorcraft\n\
+            102,T=3|4|0,Name=Boat\n\
+            #60\n\
+            101,T=||150\n\
+            0,Event=Bookmark|Early\n\
+            -102\n\
+            #300.25\n\
+            101,T=||200\n\
+            0,Event=Bookmark|Engaged\n\
+            #400\n\
+            101,T=||300\n";
+
+        let result = cut(acmi, 300.0, 360.0).unwrap();
+        assert_eq!(
+            result,
+            "FileType=text/acmi/tacview\n\
+             FileVersion=2.2\n\
+             0,ReferenceTime=2023-01-01T00:05:00.000Z\n\
+             #0\n\
+             101,T=1|2|150,Name=Heli,Type=Air+Rotorcraft\n\
+             #0.25\n\
+             101,T=||200\n\
+             0,Event=Bookmark|Engaged\n"
+        );
+
+        assert!(cut(acmi, 500.0, 600.0).is_err());
+        assert!(cut(acmi, 60.0, 10.0).is_err());
+    }
+
+    #[test]
+    fn test_parse_offset() {
+        assert_eq!(parse_offset("90"), Ok(90.0));
+        assert_eq!(parse_offset("1:30"), Ok(90.0));
+        assert_eq!(parse_offset("1:02:03.5"), Ok(3723.5));
+        assert!(parse_offset("-5").is_err());
+        assert!(parse_offset("1:xx").is_err());
+    }
+}
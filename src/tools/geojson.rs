@@ -0,0 +1,131 @@
+use serde_json::{json, Map, Value};
+
+use super::tracks::{Track, Tracks};
+
+/// Convert tracks to a GeoJSON FeatureCollection
+///
+/// Each object becomes a feature with a LineString of `[lon, lat, alt]`
+/// positions, or a Point when it never moved. A track crossing the
+/// antimeridian becomes a MultiLineString, split as [`Track::segments`]
+/// does. Its properties are the object's ACMI properties, its hexadecimal
+/// `id` and `coordTimes`, the time of every position as used by common GPS
+/// track tools, with one list per line of a MultiLineString.
+pub fn to_geojson(tracks: &Tracks) -> Value {
+    let features: Vec<Value> = tracks
+        .tracks
+        .iter()
+        .map(|track| feature(tracks, track))
+        .collect();
+
+    let mut collection = json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    if let Some(title) = &tracks.title {
+        collection["name"] = json!(title);
+    }
+    collection
+}
+
+fn feature(tracks: &Tracks, track: &Track) -> Value {
+    let segments = track.segments();
+    let coordinates: Vec<Vec<Value>> = segments
+        .iter()
+        .map(|points| {
+            points
+                .iter()
+                .map(|p| json!([p.longitude, p.latitude, p.altitude]))
+                .collect()
+        })
+        .collect();
+    let times: Vec<Vec<String>> = segments
+        .iter()
+        .map(|points| points.iter().map(|p| tracks.timestamp(p)).collect())
+        .collect();
+    let (geometry, times) = match (coordinates.as_slice(), times.as_slice()) {
+        ([line], [times]) => match line.as_slice() {
+            [point] => (
+                json!({ "type": "Point", "coordinates": point }),
+                json!(times),
+            ),
+            _ => (
+                json!({ "type": "LineString", "coordinates": line }),
+                json!(times),
+            ),
+        },
+        _ => (
+            json!({ "type": "MultiLineString", "coordinates": coordinates }),
+            json!(times),
+        ),
+    };
+
+    let mut properties = Map::new();
+    properties.insert("id".to_string(), json!(format!("{:x}", track.id)));
+    for (key, value) in &track.properties {
+        properties.insert(key.clone(), json!(value));
+    }
+    properties.insert("coordTimes".to_string(), times);
+
+    json!({
+        "type": "Feature",
+        "geometry": geometry,
+        "properties": properties,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geojson_features() {
+        let acmi = "0,ReferenceTime=2023-01-01T00:00:00Z\n\
+            0,Title=Sortie\n\
+            #0\n\
+            101,T=1|2|100,Name=Heli\n\
+            102,T=3|4|0,Name=Base\n\
+            #1\n\
+            101,T=1.5||\n";
+
+        let geojson = to_geojson(&Tracks::from_acmi(acmi));
+        assert_eq!(geojson["name"], "Sortie");
+
+        let heli = &geojson["features"][0];
+        assert_eq!(heli["geometry"]["type"], "LineString");
+        assert_eq!(heli["geometry"]["coordinates"][1], json!([1.5, 2.0, 100.0]));
+        assert_eq!(heli["properties"]["id"], "101");
+        assert_eq!(heli["properties"]["Name"], "Heli");
+        assert_eq!(
+            heli["properties"]["coordTimes"][1],
+            "2023-01-01T00:00:01.000Z"
+        );
+
+        let base = &geojson["features"][1];
+        assert_eq!(base["geometry"]["type"], "Point");
+        assert_eq!(base["geometry"]["coordinates"], json!([3.0, 4.0, 0.0]));
+    }
+
+    #[test]
+    fn test_geojson_splits_at_the_antimeridian() {
+        let acmi = "0,ReferenceLongitude=180\n\
+            #0\n\
+            101,T=-0.5|0|100\n\
+            #1\n\
+            101,T=0.5|0|100\n";
+
+        let geojson = to_geojson(&Tracks::from_acmi(acmi));
+        let heli = &geojson["features"][0];
+        assert_eq!(heli["geometry"]["type"], "MultiLineString");
+        assert_eq!(
+            heli["geometry"]["coordinates"],
+            json!([
+                [[179.5, 0.0, 100.0], [180.0, 0.0, 100.0]],
+                [[-180.0, 0.0, 100.0], [-179.5, 0.0, 100.0]]
+            ])
+        );
+        assert_eq!(
+            heli["properties"]["coordTimes"][1][0],
+            "1970-01-01T00:00:00.500Z"
+        );
+    }
+}
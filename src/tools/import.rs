@@ -0,0 +1,260 @@
+use anyhow::{bail, Result};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::acmi::{
+    logical_lines, parse_object_id, parse_records, AcmiRecord, ObjectUpdate, WorldState,
+    GLOBAL_OBJECT_ID,
+};
+use crate::infra::FileAcmiRepository;
+
+/// Global properties taken from the imported file rather than the bridge header
+const KEPT_PROPERTIES: [&str; 3] = ["ReferenceTime", "RecordingTime", "Title"];
+
+/// Global properties set by the bridge header, dropped from the imported file
+const HEADER_PROPERTIES: [&str; 7] = [
+    "ReferenceLongitude",
+    "ReferenceLatitude",
+    "ReferenceTime",
+    "RecordingTime",
+    "Title",
+    "DataRecorder",
+    "Author",
+];
+
+/// Object properties whose value is the ID of another object
+const REFERENCE_PROPERTIES: [&str; 3] = ["Parent", "Next", "FocusedTarget"];
+
+/// Rewrite an ACMI file from another simulator in the bridge's conventions
+///
+/// The result starts with the header the bridge writes to its recordings,
+/// keeping the ReferenceTime, RecordingTime and Title of the imported file.
+/// Objects whose ID is reserved by the bridge, such as the bullseye, get a
+/// free ID, and references to them in properties and events follow.
+///
+/// Longitude and latitude offsets are rebased onto the bridge's
+/// ReferenceLongitude and ReferenceLatitude so objects keep their absolute
+/// position. With `recenter`, the offsets are kept instead, which moves the
+/// imported reference point onto the Stormworks one.
+pub fn import(acmi: &str, recenter: bool) -> Result<String> {
+    let header = FileAcmiRepository::generate_acmi_header();
+    let mut ours = WorldState::new();
+    let mut reserved = BTreeSet::new();
+    for record in parse_records(&header) {
+        if let AcmiRecord::Update(update) = &record {
+            reserved.insert(update.id);
+        }
+        ours.apply(&record);
+    }
+
+    let mut theirs = WorldState::new();
+    let mut used = BTreeSet::new();
+    for record in parse_records(acmi) {
+        match &record {
+            AcmiRecord::Update(update) if !update.is_global() => {
+                used.insert(update.id);
+            }
+            AcmiRecord::Removal(id) => {
+                used.insert(*id);
+            }
+            _ => {}
+        }
+        // Only the first value of each global property matters for the header
+        if let AcmiRecord::Update(update) = &record {
+            if update.is_global() {
+                for (key, value) in &update.properties {
+                    theirs.global.entry(key.clone()).or_insert(value.clone());
+                }
+                continue;
+            }
+        }
+        theirs.apply(&record);
+    }
+    if !theirs.headers.contains_key("FileType") {
+        bail!("not an ACMI file: FileType header missing");
+    }
+
+    let remap = remap_ids(&used, &reserved);
+    let offset = if recenter {
+        (0.0, 0.0)
+    } else {
+        (
+            theirs.reference_longitude() - ours.reference_longitude(),
+            theirs.reference_latitude() - ours.reference_latitude(),
+        )
+    };
+
+    let mut out = String::new();
+    for (_, line) in logical_lines(&header) {
+        let kept = AcmiRecord::parse(&line)
+            .ok()
+            .and_then(|record| match record {
+                AcmiRecord::Update(update) if update.is_global() => {
+                    let (key, _) = update.properties.first()?;
+                    let value = theirs.global.get(key)?;
+                    KEPT_PROPERTIES.contains(&key.as_str()).then(|| {
+                        AcmiRecord::Update(ObjectUpdate::new(GLOBAL_OBJECT_ID).with(key, value))
+                    })
+                }
+                _ => None,
+            });
+        match kept {
+            Some(record) => out.push_str(&format!("{record}\n")),
+            None => out.push_str(&format!("{line}\n")),
+        }
+    }
+
+    for (_, line) in logical_lines(acmi) {
+        let record = match AcmiRecord::parse(&line) {
+            Ok(AcmiRecord::Header { .. }) => continue,
+            Ok(AcmiRecord::Update(mut update)) => {
+                rewrite_update(&mut update, &remap, offset);
+                if update.is_global() && update.properties.is_empty() {
+                    continue;
+                }
+                AcmiRecord::Update(update)
+            }
+            Ok(AcmiRecord::Removal(id)) => AcmiRecord::Removal(*remap.get(&id).unwrap_or(&id)),
+            Ok(record) => record,
+            // Lines the parser does not understand are dropped, as Tacview would
+            Err(_) => continue,
+        };
+        out.push_str(&format!("{record}\n"));
+    }
+    Ok(out)
+}
+
+/// New IDs for the used IDs that collide with reserved ones
+fn remap_ids(used: &BTreeSet<u64>, reserved: &BTreeSet<u64>) -> HashMap<u64, u64> {
+    let mut next = used.iter().chain(reserved).max().copied().unwrap_or(0) + 1;
+    used.intersection(reserved)
+        .map(|&id| {
+            let new_id = next;
+            next += 1;
+            (id, new_id)
+        })
+        .collect()
+}
+
+/// Apply the ID remapping and position offset to an update
+fn rewrite_update(update: &mut ObjectUpdate, remap: &HashMap<u64, u64>, offset: (f64, f64)) {
+    if update.is_global() {
+        update
+            .properties
+            .retain(|(key, _)| !HEADER_PROPERTIES.contains(&key.as_str()));
+    }
+    update.id = *remap.get(&update.id).unwrap_or(&update.id);
+
+    for (key, value) in &mut update.properties {
+        match key.as_str() {
+            "T" => *value = shift_transform(value, offset),
+            "Event" => *value = remap_event(value, remap),
+            key if is_reference_property(key) => {
+                if let Some(id) = parse_object_id(value).ok().and_then(|id| remap.get(&id)) {
+                    *value = format!("{id:x}");
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Check for properties holding an object ID, such as `LockedTarget3`
+fn is_reference_property(key: &str) -> bool {
+    REFERENCE_PROPERTIES.contains(&key)
+        || key
+            .strip_prefix("LockedTarget")
+            .is_some_and(|n| n.is_empty() || n.parse::<u8>().is_ok())
+}
+
+/// Add an offset to the longitude and latitude of a `T=` value, keeping
+/// its layout and every other component as written
+fn shift_transform(value: &str, (longitude, latitude): (f64, f64)) -> String {
+    if longitude == 0.0 && latitude == 0.0 {
+        return value.to_string();
+    }
+    let mut parts: Vec<String> = value.split('|').map(str::to_string).collect();
+    for (index, offset) in [(0, longitude), (1, latitude)] {
+        if let Some(part) = parts.get_mut(index) {
+            if let Ok(degrees) = part.parse::<f64>() {
+                *part = (((degrees + offset) * 1e7).round() / 1e7).to_string();
+            }
+        }
+    }
+    parts.join("|")
+}
+
+/// Remap the object IDs of an event such as `Message|3000102|Hello`
+///
+/// IDs are the fields between the event type and the text.
+fn remap_event(value: &str, remap: &HashMap<u64, u64>) -> String {
+    let parts: Vec<&str> = value.split('|').collect();
+    if parts.len() < 3 {
+        return value.to_string();
+    }
+    let last = parts.len() - 1;
+    parts
+        .iter()
+        .enumerate()
+        .map(|(index, part)| {
+            let id = parse_object_id(part)
+                .ok()
+                .filter(|_| index > 0 && index < last);
+            match id.and_then(|id| remap.get(&id)) {
+                Some(new_id) => format!("{new_id:x}"),
+                None => part.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_remaps_ids_and_reference() {
+        let acmi = "FileType=text/acmi/tacview\n\
+            FileVersion=2.1\n\
+            0,ReferenceTime=2011-06-02T05:00:00Z\n\
+            0,ReferenceLongitude=-129\n\
+            0,ReferenceLatitude=1\n\
+            0,Title=Other sim\n\
+            0,Category=Training\n\
+            #0\n\
+            40000003,T=1.5|2.5|3000|0|0|90,Name=F-16C\n\
+            40000004,T=-0.5|0|100,Name=AIM-120,Parent=40000003,LockedTarget2=40000003\n\
+            #1\n\
+            0,Event=Destroyed|40000003|Splash\n\
+            -40000003\n";
+
+        let imported = import(acmi, false).unwrap();
+        let lines: Vec<&str> = imported.lines().collect();
+        assert!(lines.contains(&"0,ReferenceTime=2011-06-02T05:00:00Z"));
+        assert!(lines.contains(&"0,Title=Other sim"));
+        assert!(lines.contains(&"0,ReferenceLongitude=180"));
+        assert!(lines.contains(&"0,Category=Training"));
+        assert!(!lines.contains(&"0,ReferenceLongitude=-129"));
+        assert!(!lines.contains(&"FileVersion=2.1"));
+
+        // Absolute longitude -127.5 is 307.5 degrees west of the bridge's 180
+        assert!(lines.contains(&"40000005,T=-307.5|3.5|3000|0|0|90,Name=F-16C"));
+        assert!(lines.contains(
+            &"40000004,T=-309.5|1|100,Name=AIM-120,Parent=40000005,LockedTarget2=40000005"
+        ));
+        assert!(lines.contains(&"0,Event=Destroyed|40000005|Splash"));
+        assert!(lines.contains(&"-40000005"));
+
+        // The bridge's bullseye is left alone
+        assert!(lines.iter().any(|l| l.starts_with("40000003,T=0|0|2000")));
+
+        // Tacview's own text files start with a byte order mark
+        let with_bom = format!("\u{feff}{acmi}");
+        assert_eq!(import(&with_bom, false).unwrap(), imported);
+
+        let recentered = import(acmi, true).unwrap();
+        assert!(recentered.contains("\n40000005,T=1.5|2.5|3000|0|0|90,Name=F-16C\n"));
+
+        assert!(import("not acmi\n", false).is_err());
+    }
+}
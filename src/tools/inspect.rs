@@ -0,0 +1,342 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::acmi::{logical_lines, read_recording, AcmiRecord, WorldState};
+
+/// Summary of a recording, printed by the `inspect` subcommand
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Inspection {
+    pub file: PathBuf,
+    /// Size of the ACMI text, uncompressed
+    pub bytes: usize,
+    pub title: Option<String>,
+    pub reference_time: Option<String>,
+    /// First and last time frame in seconds
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    /// Seconds covered by the time frames, adding up the parts between
+    /// clock resets
+    pub duration: f64,
+    /// Number of distinct objects, including removed ones
+    pub objects: usize,
+    pub objects_by_type: BTreeMap<String, usize>,
+    pub objects_by_coalition: BTreeMap<String, usize>,
+    pub max_altitude: Option<MaxAltitude>,
+    /// Number of events by type, e.g. `Bookmark`
+    pub events: BTreeMap<String, usize>,
+    /// Time frames further apart than the gap threshold
+    pub gaps: Vec<Gap>,
+    /// Time frames earlier than the one before them, taken as the clock
+    /// resetting and followed from the new time
+    pub clock_resets: usize,
+    /// Lines that could not be parsed
+    pub invalid_lines: usize,
+    /// Most recent time frame, which differs from `end` after a reset
+    #[serde(skip)]
+    last_frame: Option<f64>,
+    /// First time frame since the last clock reset
+    #[serde(skip)]
+    part_start: Option<f64>,
+}
+
+/// Highest altitude reached by any moving object
+#[derive(Debug, Clone, Serialize)]
+pub struct MaxAltitude {
+    /// Hexadecimal object ID
+    pub object: String,
+    pub name: Option<String>,
+    /// Altitude in meters
+    pub altitude: f64,
+    pub time: f64,
+}
+
+/// A stretch of the timeline without time frames
+#[derive(Debug, Clone, Serialize)]
+pub struct Gap {
+    pub from: f64,
+    pub to: f64,
+    pub seconds: f64,
+}
+
+/// Type and coalition of an object, as last reported
+#[derive(Debug, Default)]
+struct ObjectSummary {
+    object_type: Option<String>,
+    coalition: Option<String>,
+}
+
+impl Inspection {
+    /// Inspect a recording, zipped or plain text
+    ///
+    /// Time frames more than `gap_threshold` seconds apart are reported as gaps.
+    pub fn from_file(path: &Path, gap_threshold: f64) -> Result<Self> {
+        let text = read_recording(path)?;
+        Ok(Self {
+            file: path.to_path_buf(),
+            ..Self::from_acmi(&text, gap_threshold)
+        })
+    }
+
+    /// Inspect ACMI text
+    pub fn from_acmi(text: &str, gap_threshold: f64) -> Self {
+        let mut inspection = Self {
+            bytes: text.len(),
+            ..Self::default()
+        };
+        let mut world = WorldState::new();
+        let mut objects: HashMap<u64, ObjectSummary> = HashMap::new();
+
+        for (_, line) in logical_lines(text) {
+            let Ok(record) = AcmiRecord::parse(&line) else {
+                inspection.invalid_lines += 1;
+                continue;
+            };
+
+            match &record {
+                AcmiRecord::TimeFrame(time) => inspection.add_time_frame(*time, gap_threshold),
+                AcmiRecord::Update(update) if update.is_global() => {
+                    if let Some(event) = update.property("Event") {
+                        let kind = event.split('|').next().unwrap_or(event);
+                        *inspection.events.entry(kind.to_string()).or_default() += 1;
+                    }
+                }
+                AcmiRecord::Update(update) => {
+                    let summary = objects.entry(update.id).or_default();
+                    if let Some(t) = update.property("Type") {
+                        summary.object_type = Some(t.to_string());
+                    }
+                    if let Some(c) = update.property("Coalition") {
+                        summary.coalition = Some(c.to_string());
+                    }
+                }
+                _ => {}
+            }
+            world.apply(&record);
+
+            if let AcmiRecord::Update(update) = &record {
+                inspection.check_altitude(&world, update.id);
+            }
+        }
+
+        inspection.title = world.global.get("Title").cloned();
+        inspection.reference_time = world.global.get("ReferenceTime").cloned();
+        if let (Some(start), Some(last)) = (inspection.part_start, inspection.last_frame) {
+            inspection.duration += last - start;
+        }
+        inspection.objects = objects.len();
+        for summary in objects.into_values() {
+            let object_type = summary.object_type.unwrap_or_else(|| "(none)".to_string());
+            *inspection.objects_by_type.entry(object_type).or_default() += 1;
+            let coalition = summary.coalition.unwrap_or_else(|| "(none)".to_string());
+            *inspection
+                .objects_by_coalition
+                .entry(coalition)
+                .or_default() += 1;
+        }
+
+        inspection
+    }
+
+    fn add_time_frame(&mut self, time: f64, gap_threshold: f64) {
+        if let Some(last) = self.last_frame {
+            if time < last {
+                self.clock_resets += 1;
+                self.duration += last - self.part_start.unwrap_or(last);
+                self.part_start = Some(time);
+            } else if time - last > gap_threshold {
+                self.gaps.push(Gap {
+                    from: last,
+                    to: time,
+                    seconds: time - last,
+                });
+            }
+        }
+        self.part_start.get_or_insert(time);
+        self.start = Some(self.start.map_or(time, |start| start.min(time)));
+        self.end = Some(self.end.map_or(time, |end| end.max(time)));
+        self.last_frame = Some(time);
+    }
+
+    /// Static objects such as the bullseye are not counted
+    fn check_altitude(&mut self, world: &WorldState, id: u64) {
+        let Some(object) = world.object(id).filter(|o| !o.has_type_tag("Static")) else {
+            return;
+        };
+        let Some(altitude) = object.transform.altitude else {
+            return;
+        };
+        if self
+            .max_altitude
+            .as_ref()
+            .is_some_and(|max| max.altitude >= altitude)
+        {
+            return;
+        }
+
+        self.max_altitude = Some(MaxAltitude {
+            object: format!("{id:x}"),
+            name: object.name().map(str::to_string),
+            altitude,
+            time: world.time,
+        });
+    }
+}
+
+/// Format seconds as `h:mm:ss.s`
+fn format_duration(seconds: f64) -> String {
+    let tenths = (seconds * 10.0).round() as u64;
+    format!(
+        "{}:{:02}:{:02}.{}",
+        tenths / 36_000,
+        tenths / 600 % 60,
+        tenths / 10 % 60,
+        tenths % 10
+    )
+}
+
+/// Format counts as `key count, key count`, largest first
+fn format_counts(counts: &BTreeMap<String, usize>) -> String {
+    let mut counts: Vec<_> = counts.iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(a.1));
+    counts
+        .iter()
+        .map(|(key, count)| format!("{key} {count}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl fmt::Display for Inspection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "File:           {}", self.file.display())?;
+        writeln!(f, "Size:           {} bytes uncompressed", self.bytes)?;
+        if let Some(title) = &self.title {
+            writeln!(f, "Title:          {title}")?;
+        }
+        if let Some(reference_time) = &self.reference_time {
+            writeln!(f, "Reference time: {reference_time}")?;
+        }
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => writeln!(
+                f,
+                "Duration:       {} (#{start} to #{end})",
+                format_duration(self.duration)
+            )?,
+            _ => writeln!(f, "Duration:       no time frames")?,
+        }
+
+        writeln!(f, "Objects:        {}", self.objects)?;
+        if self.objects > 0 {
+            writeln!(
+                f,
+                "  by type:      {}",
+                format_counts(&self.objects_by_type)
+            )?;
+            writeln!(
+                f,
+                "  by coalition: {}",
+                format_counts(&self.objects_by_coalition)
+            )?;
+        }
+        if let Some(max) = &self.max_altitude {
+            writeln!(
+                f,
+                "Max altitude:   {:.1} m by {} ({}) at #{}",
+                max.altitude,
+                max.name.as_deref().unwrap_or("unnamed"),
+                max.object,
+                max.time
+            )?;
+        }
+
+        let events: usize = self.events.values().sum();
+        if events > 0 {
+            writeln!(
+                f,
+                "Events:         {events} ({})",
+                format_counts(&self.events)
+            )?;
+        } else {
+            writeln!(f, "Events:         0")?;
+        }
+
+        writeln!(f, "Gaps:           {}", self.gaps.len())?;
+        for gap in &self.gaps {
+            writeln!(f, "  #{} to #{} ({:.1} s)", gap.from, gap.to, gap.seconds)?;
+        }
+        if self.clock_resets > 0 {
+            writeln!(f, "Clock resets:   {}", self.clock_resets)?;
+        }
+        if self.invalid_lines > 0 {
+            writeln!(f, "Invalid lines:  {}", self.invalid_lines)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_recording() {
+        let acmi = "FileType=text/acmi/tacview\n\
+            FileVersion=2.2\n\
+            0,ReferenceTime=2024-01-01T00:00:00Z\n\
+            0,Title=Sortie\n\
+            #0\n\
+            101,T=1|2|100,Name=Heli,Type=Air+Rotorcraft,Coalition=Allies\n\
+            102,T=1|2|0,Type=Sea+Watercraft,Coalition=Enemies\n\
+            #1\n\
+            101,T=||350\n\
+            0,Event=Bookmark|Hover\n\
+            #20\n\
+            101,T=||200\n\
+            0,Event=Bookmark|Land\n\
+            0,Event=Message|101|Hello\n\
+            -102\n\
+            #19\n\
+            not acmi\n\
+            #18\n\
+            #30\n";
+
+        let inspection = Inspection::from_acmi(acmi, 5.0);
+        assert_eq!(inspection.title.as_deref(), Some("Sortie"));
+        assert_eq!((inspection.start, inspection.end), (Some(0.0), Some(30.0)));
+        // #0 to #20, then #19, then #18 to #30 after the clock resets
+        assert_eq!(inspection.duration, 32.0);
+        assert_eq!(inspection.objects, 2);
+        assert_eq!(inspection.objects_by_type["Air+Rotorcraft"], 1);
+        assert_eq!(inspection.objects_by_coalition["Enemies"], 1);
+
+        let max = inspection.max_altitude.as_ref().unwrap();
+        assert_eq!(
+            (max.object.as_str(), max.altitude, max.time),
+            ("101", 350.0, 1.0)
+        );
+        assert_eq!(max.name.as_deref(), Some("Heli"));
+
+        assert_eq!(inspection.events["Bookmark"], 2);
+        assert_eq!(inspection.events["Message"], 1);
+        // Each jump back is one reset, and time continues from there
+        assert_eq!(inspection.gaps.len(), 2);
+        assert_eq!(inspection.gaps[0].seconds, 19.0);
+        assert_eq!(
+            (inspection.gaps[1].from, inspection.gaps[1].to),
+            (18.0, 30.0)
+        );
+        assert_eq!(inspection.clock_resets, 2);
+        assert_eq!(inspection.invalid_lines, 1);
+
+        assert!(inspection.to_string().contains("Duration:       0:00:32.0"));
+        assert!(inspection.to_string().contains("Clock resets:   2"));
+
+        // Two sessions of 30 seconds each, the second starting over at #0
+        let inspection = Inspection::from_acmi("#0\n#15\n#30\n#0\n#15\n#30\n", 20.0);
+        assert_eq!(inspection.clock_resets, 1);
+        assert_eq!(inspection.duration, 60.0);
+        assert!(inspection.gaps.is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+use super::tracks::{Track, TrackPoint, Tracks};
+
+/// Convert tracks to a KML document
+///
+/// Each object becomes a placemark with a `gx:Track` holding a timestamp
+/// and an absolute position for every point, so Google Earth and other KML
+/// viewers can replay the recording with their time slider. The object's
+/// hexadecimal ID and ACMI properties are added as extended data. A track
+/// crossing the antimeridian becomes a `gx:MultiTrack` of the parts
+/// returned by [`Track::segments`].
+pub fn to_kml(tracks: &Tracks) -> String {
+    let mut kml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <kml xmlns=\"http://www.opengis.net/kml/2.2\" xmlns:gx=\"http://www.google.com/kml/ext/2.2\">\n\
+         <Document>\n",
+    );
+    if let Some(title) = &tracks.title {
+        kml.push_str(&format!("<name>{}</name>\n", escape_xml(title)));
+    }
+    for track in &tracks.tracks {
+        placemark(&mut kml, tracks, track);
+    }
+    kml.push_str("</Document>\n</kml>\n");
+    kml
+}
+
+fn placemark(kml: &mut String, tracks: &Tracks, track: &Track) {
+    let id = format!("{:x}", track.id);
+    let name = track.name().unwrap_or(&id);
+
+    kml.push_str(&format!("<Placemark>\n<name>{}</name>\n", escape_xml(name)));
+    kml.push_str("<ExtendedData>\n");
+    kml.push_str(&format!("<Data name=\"id\"><value>{id}</value></Data>\n"));
+    for (key, value) in &track.properties {
+        kml.push_str(&format!(
+            "<Data name=\"{}\"><value>{}</value></Data>\n",
+            escape_xml(key),
+            escape_xml(value)
+        ));
+    }
+    kml.push_str("</ExtendedData>\n");
+
+    match track.segments().as_slice() {
+        [points] => gx_track(kml, tracks, points),
+        segments => {
+            kml.push_str("<gx:MultiTrack>\n");
+            for points in segments {
+                gx_track(kml, tracks, points);
+            }
+            kml.push_str("</gx:MultiTrack>\n");
+        }
+    }
+    kml.push_str("</Placemark>\n");
+}
+
+fn gx_track(kml: &mut String, tracks: &Tracks, points: &[TrackPoint]) {
+    kml.push_str("<gx:Track>\n<altitudeMode>absolute</altitudeMode>\n");
+    for point in points {
+        kml.push_str(&format!("<when>{}</when>\n", tracks.timestamp(point)));
+    }
+    for point in points {
+        kml.push_str(&format!(
+            "<gx:coord>{} {} {}</gx:coord>\n",
+            point.longitude, point.latitude, point.altitude
+        ));
+    }
+    kml.push_str("</gx:Track>\n");
+}
+
+/// Escape text for XML element content and attribute values
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kml_track() {
+        let acmi = "0,ReferenceTime=2023-01-01T00:00:00Z\n\
+            #0\n\
+            101,T=1|2|100,Name=Heli <1> & co,Type=Air+Rotorcraft\n\
+            #1.5\n\
+            101,T=1.5||\n";
+
+        let kml = to_kml(&Tracks::from_acmi(acmi));
+        assert!(kml.contains("<Placemark>\n<name>Heli &lt;1&gt; &amp; co</name>"));
+        assert!(kml.contains("<Data name=\"id\"><value>101</value></Data>"));
+        assert!(kml.contains("<Data name=\"Type\"><value>Air+Rotorcraft</value></Data>"));
+        assert!(kml.contains(
+            "<when>2023-01-01T00:00:00.000Z</when>\n\
+             <when>2023-01-01T00:00:01.500Z</when>\n\
+             <gx:coord>1 2 100</gx:coord>\n\
+             <gx:coord>1.5 2 100</gx:coord>\n"
+        ));
+        assert!(kml.ends_with("</Document>\n</kml>\n"));
+
+        // Crossing the antimeridian splits the track
+        let acmi = "0,ReferenceLongitude=180\n#0\n101,T=-0.5|0|100\n#1\n101,T=0.5|0|100\n";
+        let kml = to_kml(&Tracks::from_acmi(acmi));
+        assert_eq!(kml.matches("<gx:Track>").count(), 2);
+        assert!(kml.contains("<gx:coord>180 0 100</gx:coord>\n</gx:Track>\n<gx:Track>"));
+        assert!(kml.contains("<gx:coord>-179.5 0 100</gx:coord>\n</gx:Track>\n</gx:MultiTrack>"));
+    }
+}
@@ -0,0 +1,173 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+use super::cut::{parse_reference_time, shift_time};
+use crate::acmi::{logical_lines, AcmiRecord};
+
+/// Global properties that describe the file as a whole, kept from the first
+/// recording only
+const FILE_PROPERTIES: [&str; 2] = ["ReferenceTime", "RecordingTime"];
+
+/// A recording to merge, with its place on the timeline
+struct Segment<'a> {
+    acmi: &'a str,
+    reference_time: Option<DateTime<Utc>>,
+    start: f64,
+    end: f64,
+}
+
+impl<'a> Segment<'a> {
+    fn new(acmi: &'a str) -> Self {
+        let mut segment = Self {
+            acmi,
+            reference_time: None,
+            start: 0.0,
+            end: 0.0,
+        };
+        let mut has_frames = false;
+
+        for (_, line) in logical_lines(acmi) {
+            match AcmiRecord::parse(&line) {
+                Ok(AcmiRecord::TimeFrame(time)) => {
+                    if !has_frames {
+                        segment.start = time;
+                        has_frames = true;
+                    }
+                    segment.end = segment.end.max(time);
+                }
+                Ok(AcmiRecord::Update(update)) if update.is_global() => {
+                    if let Some(value) = update.property("ReferenceTime") {
+                        segment.reference_time =
+                            segment.reference_time.or(parse_reference_time(value));
+                    }
+                }
+                _ => {}
+            }
+        }
+        segment
+    }
+
+    /// Absolute time of the first time frame, when the ReferenceTime is known
+    fn absolute_start(&self) -> Option<DateTime<Utc>> {
+        let start = chrono::Duration::microseconds((self.start * 1e6).round() as i64);
+        self.reference_time.map(|reference| reference + start)
+    }
+}
+
+/// Join recordings into one, such as segments of a rotated recording
+///
+/// Recordings are ordered by their start when they all have a ReferenceTime,
+/// and their time frames are rebased onto the ReferenceTime of the first. A
+/// recording that would start before the previous one ends, for example
+/// because both use the same fixed ReferenceTime, is placed right after it.
+/// Headers and file properties such as ReferenceTime are taken from the first
+/// recording only.
+pub fn merge(recordings: &[String]) -> Result<String> {
+    if recordings.is_empty() {
+        bail!("no recordings to merge");
+    }
+
+    let mut segments: Vec<Segment> = recordings.iter().map(|acmi| Segment::new(acmi)).collect();
+    if segments.iter().all(|s| s.reference_time.is_some()) {
+        segments.sort_by_key(Segment::absolute_start);
+    }
+    let base = segments[0].reference_time;
+
+    let mut out = String::new();
+    let mut previous_end: Option<f64> = None;
+    let mut last_frame: Option<f64> = None;
+    for (index, segment) in segments.iter().enumerate() {
+        let mut offset = match (base, segment.reference_time) {
+            (Some(base), Some(reference)) => {
+                (reference - base).num_microseconds().unwrap_or(0) as f64 / 1e6
+            }
+            _ => 0.0,
+        };
+        if let Some(end) = previous_end {
+            if segment.start + offset < end {
+                offset = end - segment.start;
+            }
+        }
+
+        // Lines before the first time frame of a later recording would land
+        // at the end of the previous one, so they follow its first frame
+        let mut pending = (index > 0).then(String::new);
+        for (_, line) in logical_lines(segment.acmi) {
+            let target = pending.as_mut().unwrap_or(&mut out);
+            match AcmiRecord::parse(&line) {
+                Ok(AcmiRecord::TimeFrame(time)) => {
+                    let time = shift_time(time, offset);
+                    // A recording placed right after the previous one starts
+                    // at its last time frame, which is not repeated
+                    if last_frame != Some(time) {
+                        writeln!(out, "#{time}")?;
+                        last_frame = Some(time);
+                    }
+                    if let Some(pending) = pending.take() {
+                        out.push_str(&pending);
+                    }
+                }
+                Ok(AcmiRecord::Header { .. }) if index > 0 => {}
+                Ok(AcmiRecord::Update(mut update)) if index > 0 && update.is_global() => {
+                    update
+                        .properties
+                        .retain(|(key, _)| !FILE_PROPERTIES.contains(&key.as_str()));
+                    if !update.properties.is_empty() {
+                        writeln!(target, "{}", AcmiRecord::Update(update))?;
+                    }
+                }
+                _ => writeln!(target, "{line}")?,
+            }
+        }
+        if let Some(pending) = pending {
+            out.push_str(&pending);
+        }
+        previous_end = Some(segment.end + offset);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_rebases_segments() {
+        let first = "FileType=text/acmi/tacview\n\
+            FileVersion=2.2\n\
+            0,ReferenceTime=2023-01-01T00:00:00.000Z\n\
+            #0\n\
+            101,T=1|2|100,Name=Heli\n\
+            #10\n\
+            101,T=||150\n";
+        let second = "FileType=text/acmi/tacview\n\
+            FileVersion=2.2\n\
+            0,ReferenceTime=2023-01-01T00:01:00.000Z\n\
+            0,Title=Part 2\n\
+            #0\n\
+            101,T=||200\n";
+
+        // Ordered by start, with the second recording 60 seconds later
+        let merged = merge(&[second.to_string(), first.to_string()]).unwrap();
+        assert_eq!(
+            merged,
+            "FileType=text/acmi/tacview\n\
+             FileVersion=2.2\n\
+             0,ReferenceTime=2023-01-01T00:00:00.000Z\n\
+             #0\n\
+             101,T=1|2|100,Name=Heli\n\
+             #10\n\
+             101,T=||150\n\
+             #60\n\
+             0,Title=Part 2\n\
+             101,T=||200\n"
+        );
+
+        // The same ReferenceTime would overlap, so the second follows the first
+        let restarted = second.replace("00:01:00", "00:00:00");
+        let merged = merge(&[first.to_string(), restarted]).unwrap();
+        assert!(merged.ends_with("#10\n101,T=||150\n0,Title=Part 2\n101,T=||200\n"));
+    }
+}
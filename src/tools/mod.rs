@@ -0,0 +1,25 @@
+//! Offline tools for recorded ACMI files
+//!
+//! These back the subcommands that work on recordings without running the
+//! bridge, so recordings can be examined on a headless server without a
+//! Tacview install. The traffic simulator drives a running bridge instead,
+//! for load testing without Stormworks.
+
+pub mod csv;
+pub mod cut;
+pub mod geojson;
+pub mod import;
+pub mod inspect;
+pub mod kml;
+pub mod merge;
+pub mod simulate;
+pub mod tracks;
+
+pub use cut::{cut, parse_offset};
+pub use geojson::to_geojson;
+pub use import::import;
+pub use inspect::Inspection;
+pub use kml::to_kml;
+pub use merge::merge;
+pub use simulate::{simulate, SimulationOptions, SimulationReport};
+pub use tracks::Tracks;
@@ -0,0 +1,367 @@
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+use std::f64::consts::TAU;
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+use crate::testing::http_get;
+
+/// Meters per degree of latitude, and of longitude at the equator
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// Largest base64 payload of a single `/acmi/` request, well below the
+/// 16 KB the HTTP server reads per request
+pub const MAX_PAYLOAD: usize = 8000;
+
+/// Options of a simulation run
+#[derive(Debug, Clone)]
+pub struct SimulationOptions {
+    /// Address of the bridge's HTTP server
+    pub target: SocketAddr,
+    pub aircraft: usize,
+    pub ships: usize,
+    /// Frames per second
+    pub rate: f64,
+    pub duration: Duration,
+    /// Start a recording before sending and stop it afterwards
+    pub record: bool,
+}
+
+/// Throughput and latency achieved by a simulation run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SimulationReport {
+    pub frames: u64,
+    /// Frames that should have been sent at the requested rate
+    pub target_frames: u64,
+    pub requests: u64,
+    pub failed_requests: u64,
+    /// ACMI bytes sent, before base64 encoding
+    pub bytes: u64,
+    pub seconds: f64,
+    /// Request latency percentiles in milliseconds
+    pub latency_p50_ms: f64,
+    pub latency_p95_ms: f64,
+    pub latency_p99_ms: f64,
+    pub latency_max_ms: f64,
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.seconds.max(f64::EPSILON);
+        writeln!(
+            f,
+            "Frames:   {} in {:.1} s ({:.1}/s, {} expected at the requested rate)",
+            self.frames,
+            self.seconds,
+            self.frames as f64 / seconds,
+            self.target_frames
+        )?;
+        writeln!(
+            f,
+            "Requests: {} ({:.1}/s), {} failed",
+            self.requests,
+            self.requests as f64 / seconds,
+            self.failed_requests
+        )?;
+        writeln!(
+            f,
+            "ACMI:     {} bytes ({:.1} kB/s)",
+            self.bytes,
+            self.bytes as f64 / seconds / 1000.0
+        )?;
+        writeln!(
+            f,
+            "Latency:  p50 {:.2} ms, p95 {:.2} ms, p99 {:.2} ms, max {:.2} ms",
+            self.latency_p50_ms, self.latency_p95_ms, self.latency_p99_ms, self.latency_max_ms
+        )
+    }
+}
+
+/// A simulated object moving along a simple trajectory
+#[derive(Debug, Clone)]
+struct SimObject {
+    id: u64,
+    name: String,
+    object_type: &'static str,
+    coalition: &'static str,
+    color: &'static str,
+    motion: Motion,
+}
+
+#[derive(Debug, Clone)]
+enum Motion {
+    /// Circling a point at constant speed, with a gentle altitude wave
+    Orbit {
+        center: (f64, f64),
+        radius: f64,
+        speed: f64,
+        altitude: f64,
+        phase: f64,
+    },
+    /// Sailing a straight course at constant speed
+    Course {
+        start: (f64, f64),
+        heading: f64,
+        speed: f64,
+    },
+}
+
+/// Generates traffic in the format the Stormworks addon sends
+///
+/// Aircraft orbit at 80 to 250 m/s between 300 and 3000 m; ships sail
+/// straight courses at 5 to 15 m/s. Every property of every object is sent on
+/// each frame, as the addon does. The traffic is the same on every run.
+#[derive(Debug, Clone)]
+pub struct Traffic {
+    objects: Vec<SimObject>,
+}
+
+impl Traffic {
+    pub fn new(aircraft: usize, ships: usize) -> Self {
+        let mut random = Lcg(0x5eed);
+        let mut objects = Vec::with_capacity(aircraft + ships);
+
+        for i in 0..aircraft {
+            let allies = i % 2 == 0;
+            objects.push(SimObject {
+                id: 0x1000 + i as u64,
+                name: format!("Aircraft {}", i + 1),
+                object_type: if i % 4 == 3 {
+                    "Air+Rotorcraft"
+                } else {
+                    "Air+FixedWing"
+                },
+                coalition: if allies { "Allies" } else { "Enemies" },
+                color: if allies { "Blue" } else { "Red" },
+                motion: Motion::Orbit {
+                    center: (random.range(-0.2, 0.2), random.range(-0.2, 0.2)),
+                    radius: random.range(2_000.0, 15_000.0),
+                    speed: random.range(80.0, 250.0),
+                    altitude: random.range(300.0, 3_000.0),
+                    phase: random.range(0.0, TAU),
+                },
+            });
+        }
+        for i in 0..ships {
+            objects.push(SimObject {
+                id: 0x2000 + i as u64,
+                name: format!("Ship {}", i + 1),
+                object_type: "Sea+Watercraft",
+                coalition: "Allies",
+                color: "Blue",
+                motion: Motion::Course {
+                    start: (random.range(-0.2, 0.2), random.range(-0.2, 0.2)),
+                    heading: random.range(0.0, 360.0),
+                    speed: random.range(5.0, 15.0),
+                },
+            });
+        }
+
+        Self { objects }
+    }
+
+    /// ACMI for the state of every object at a time, starting with `#time`
+    pub fn frame(&self, time: f64) -> String {
+        let mut acmi = format!("#{time:.2}\n");
+        for object in &self.objects {
+            let (lon, lat, alt, roll, pitch, heading) = object.motion.position(time);
+            acmi.push_str(&format!(
+                "{:x},T={lon:.7}|{lat:.7}|{alt:.1}|{roll:.1}|{pitch:.1}|{heading:.1},\
+                 Name={},Type={},Coalition={},Color={}\n",
+                object.id, object.name, object.object_type, object.coalition, object.color,
+            ));
+        }
+        acmi
+    }
+}
+
+impl Motion {
+    /// Longitude and latitude offsets, altitude, roll, pitch and heading
+    fn position(&self, time: f64) -> (f64, f64, f64, f64, f64, f64) {
+        match *self {
+            Motion::Orbit {
+                center,
+                radius,
+                speed,
+                altitude,
+                phase,
+            } => {
+                let angle = phase + time * speed / radius;
+                // Counter-clockwise, so the course is 90 degrees left of the radial
+                let heading = (-angle.to_degrees()).rem_euclid(360.0);
+                let roll = -(speed * speed / radius / 9.81).atan().to_degrees();
+                // Climb and descend 100 m over a two minute wave
+                let climb_rate = 100.0 / 60.0 * (time / 60.0).cos();
+                (
+                    center.0 + radius * angle.cos() / METERS_PER_DEGREE,
+                    center.1 + radius * angle.sin() / METERS_PER_DEGREE,
+                    altitude + 100.0 * (time / 60.0).sin(),
+                    roll,
+                    (climb_rate / speed).atan().to_degrees(),
+                    heading,
+                )
+            }
+            Motion::Course {
+                start,
+                heading,
+                speed,
+            } => {
+                let distance = speed * time / METERS_PER_DEGREE;
+                let course = heading.to_radians();
+                (
+                    start.0 + distance * course.sin(),
+                    start.1 + distance * course.cos(),
+                    0.0,
+                    0.0,
+                    0.0,
+                    heading,
+                )
+            }
+        }
+    }
+}
+
+/// Small deterministic pseudo-random generator, so runs are repeatable
+#[derive(Debug, Clone)]
+struct Lcg(u64);
+
+impl Lcg {
+    fn range(&mut self, min: f64, max: f64) -> f64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        let unit = (self.0 >> 11) as f64 / (1u64 << 53) as f64;
+        min + unit * (max - min)
+    }
+}
+
+/// Split a frame into base64 payloads of at most [`MAX_PAYLOAD`] bytes,
+/// breaking only between lines
+pub fn payloads(acmi: &str) -> Vec<String> {
+    // Base64 turns every 3 bytes into 4
+    let max_bytes = MAX_PAYLOAD / 4 * 3;
+    let mut chunks = vec![String::new()];
+    for line in acmi.lines() {
+        let chunk = chunks.last_mut().unwrap();
+        if !chunk.is_empty() && chunk.len() + line.len() + 1 > max_bytes {
+            chunks.push(String::new());
+        }
+        let chunk = chunks.last_mut().unwrap();
+        chunk.push_str(line);
+        chunk.push('\n');
+    }
+    chunks.iter().map(|chunk| STANDARD.encode(chunk)).collect()
+}
+
+/// Send simulated traffic to a running bridge and measure it
+pub async fn simulate(options: &SimulationOptions) -> Result<SimulationReport> {
+    if !(options.rate.is_finite() && options.rate > 0.0) {
+        bail!("the rate must be a positive number of frames per second");
+    }
+    let traffic = Traffic::new(options.aircraft, options.ships);
+    TcpStream::connect(options.target)
+        .await
+        .with_context(|| format!("No bridge listening on {}", options.target))?;
+    if options.record && http_get(options.target, "/start").await? != 200 {
+        bail!("the bridge did not start a recording");
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / options.rate));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut report = SimulationReport::default();
+    let mut latencies = Vec::new();
+    let started = Instant::now();
+
+    loop {
+        interval.tick().await;
+        if started.elapsed() >= options.duration {
+            break;
+        }
+        let frame = traffic.frame(started.elapsed().as_secs_f64());
+        report.frames += 1;
+        report.bytes += frame.len() as u64;
+
+        for payload in payloads(&frame) {
+            let sent = Instant::now();
+            let result = http_get(options.target, &format!("/acmi/{payload}")).await;
+            latencies.push(sent.elapsed());
+            report.requests += 1;
+            if !matches!(result, Ok(200)) {
+                report.failed_requests += 1;
+            }
+        }
+    }
+
+    report.seconds = started.elapsed().as_secs_f64();
+    report.target_frames = (options.duration.as_secs_f64() * options.rate).ceil() as u64;
+    latencies.sort_unstable();
+    let percentile = |p: f64| {
+        let index = ((latencies.len() as f64 * p).ceil() as usize).saturating_sub(1);
+        latencies
+            .get(index)
+            .map_or(0.0, |latency| latency.as_secs_f64() * 1000.0)
+    };
+    report.latency_p50_ms = percentile(0.50);
+    report.latency_p95_ms = percentile(0.95);
+    report.latency_p99_ms = percentile(0.99);
+    report.latency_max_ms = percentile(1.0);
+
+    if options.record && http_get(options.target, "/stop").await? != 200 {
+        bail!("the bridge did not stop the recording");
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acmi::{validate, WorldState};
+
+    #[test]
+    fn test_traffic_is_valid_and_plausible() {
+        let traffic = Traffic::new(12, 4);
+        let first = traffic.frame(10.0);
+        let second = traffic.frame(11.0);
+        assert!(validate(&first).is_empty());
+        assert_eq!(first.lines().count(), 17);
+
+        let (mut before, mut after) = (WorldState::new(), WorldState::new());
+        for record in crate::acmi::parse_records(&first) {
+            before.apply(&record);
+        }
+        for record in crate::acmi::parse_records(&second) {
+            after.apply(&record);
+        }
+        for object in after.objects() {
+            let (lon, lat, alt) = after.absolute_position(object).unwrap();
+            let previous = before.object(object.id).unwrap();
+            let (lon0, lat0, _) = before.absolute_position(previous).unwrap();
+            let speed = ((lon - lon0).hypot(lat - lat0)) * METERS_PER_DEGREE;
+            if object.has_type_tag("Air") {
+                assert!((70.0..260.0).contains(&speed), "{speed} m/s");
+                assert!(alt > 100.0);
+            } else {
+                assert!((4.0..16.0).contains(&speed), "{speed} m/s");
+            }
+        }
+    }
+
+    #[test]
+    fn test_payloads_split_between_lines() {
+        let frame = Traffic::new(200, 0).frame(0.0);
+        let payloads = payloads(&frame);
+        assert!(payloads.len() > 1);
+        assert!(payloads.iter().all(|p| p.len() <= MAX_PAYLOAD));
+
+        let decoded: String = payloads
+            .iter()
+            .map(|p| String::from_utf8(STANDARD.decode(p).unwrap()).unwrap())
+            .collect();
+        assert_eq!(decoded, frame);
+    }
+}
@@ -0,0 +1,339 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::BTreeMap;
+
+use super::cut::parse_reference_time;
+use crate::acmi::{logical_lines, AcmiRecord, WorldState};
+
+/// Mean Earth radius in meters, for distances between nearby points
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// A position of an object at a time frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackPoint {
+    /// Seconds since ReferenceTime
+    pub time: f64,
+    /// Absolute longitude and latitude in degrees, longitude in [-180, 180)
+    pub longitude: f64,
+    pub latitude: f64,
+    /// Altitude in meters
+    pub altitude: f64,
+}
+
+/// The path of one object through a recording
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub id: u64,
+    /// Latest value of every property except `T`, kept after removal
+    pub properties: BTreeMap<String, String>,
+    pub points: Vec<TrackPoint>,
+}
+
+impl Track {
+    pub fn name(&self) -> Option<&str> {
+        self.properties.get("Name").map(String::as_str)
+    }
+
+    /// Check whether the object's `Type` has every tag of a filter such as
+    /// `Air` or `Air+Rotorcraft`
+    pub fn matches_type(&self, filter: &str) -> bool {
+        let Some(object_type) = self.properties.get("Type") else {
+            return false;
+        };
+        filter.split('+').all(|tag| {
+            object_type
+                .split('+')
+                .any(|part| part.eq_ignore_ascii_case(tag))
+        })
+    }
+
+    /// Remove points that are within `tolerance` meters of the simplified
+    /// path, using the Douglas-Peucker algorithm
+    pub fn simplify(&mut self, tolerance: f64) {
+        if tolerance <= 0.0 || self.points.len() < 3 {
+            return;
+        }
+
+        let mut keep = vec![false; self.points.len()];
+        keep[0] = true;
+        keep[self.points.len() - 1] = true;
+        let mut ranges = vec![(0, self.points.len() - 1)];
+
+        while let Some((first, last)) = ranges.pop() {
+            let (a, b) = (&self.points[first], &self.points[last]);
+            let farthest = (first + 1..last)
+                .map(|i| (i, distance_to_segment(&self.points[i], a, b)))
+                .max_by(|x, y| x.1.total_cmp(&y.1));
+
+            if let Some((index, distance)) = farthest {
+                if distance > tolerance {
+                    keep[index] = true;
+                    ranges.push((first, index));
+                    ranges.push((index, last));
+                }
+            }
+        }
+
+        let mut keep = keep.into_iter();
+        self.points.retain(|_| keep.next().unwrap_or(false));
+    }
+
+    /// The points split into parts wherever the track crosses the antimeridian
+    ///
+    /// A part that crosses ends on longitude 180 or -180 and the next one
+    /// starts on the other side, at the interpolated latitude, altitude and
+    /// time, so map tools do not draw a line around the globe.
+    pub fn segments(&self) -> Vec<Vec<TrackPoint>> {
+        let mut segments: Vec<Vec<TrackPoint>> = vec![Vec::new()];
+        for point in &self.points {
+            let current = segments.last_mut().expect("segments are never empty");
+            if let Some(last) = current.last().copied() {
+                let delta = point.longitude - last.longitude;
+                if delta.abs() > 180.0 {
+                    // Going east the line leaves at 180, going west at -180
+                    let edge = if delta < 0.0 { 180.0 } else { -180.0 };
+                    let span = delta - 360.0_f64.copysign(delta);
+                    let t = (edge - last.longitude) / span;
+                    let crossing = |longitude| TrackPoint {
+                        time: last.time + (point.time - last.time) * t,
+                        longitude,
+                        latitude: last.latitude + (point.latitude - last.latitude) * t,
+                        altitude: last.altitude + (point.altitude - last.altitude) * t,
+                    };
+                    current.push(crossing(edge));
+                    segments.push(vec![crossing(-edge)]);
+                }
+            }
+            segments
+                .last_mut()
+                .expect("segments are never empty")
+                .push(*point);
+        }
+        segments
+    }
+}
+
+/// Tracks of every object with a known position
+#[derive(Debug, Clone, Default)]
+pub struct Tracks {
+    pub title: Option<String>,
+    /// ReferenceTime of the recording, if it has a valid one
+    pub reference_time: Option<DateTime<Utc>>,
+    /// Tracks sorted by object ID, then by time when an ID was reused
+    pub tracks: Vec<Track>,
+}
+
+impl Tracks {
+    /// Collect the track of every object from ACMI text
+    ///
+    /// A point is added whenever an object reports a new position. A removed
+    /// object's track ends there, and an object reusing its ID starts a new one.
+    /// Positions are absolute, with ReferenceLongitude and ReferenceLatitude
+    /// added to the offsets in the ACMI, and longitudes wrapped into
+    /// [-180, 180) since the bridge's ReferenceLongitude is 180.
+    pub fn from_acmi(acmi: &str) -> Self {
+        let mut world = WorldState::new();
+        let mut tracks: BTreeMap<u64, Track> = BTreeMap::new();
+        let mut ended = Vec::new();
+
+        for (_, line) in logical_lines(acmi) {
+            let Ok(record) = AcmiRecord::parse(&line) else {
+                continue;
+            };
+            world.apply(&record);
+
+            // A later object with the same ID gets a track of its own
+            if let AcmiRecord::Removal(id) = &record {
+                ended.extend(tracks.remove(id));
+                continue;
+            }
+            let AcmiRecord::Update(update) = &record else {
+                continue;
+            };
+            if update.is_global() {
+                continue;
+            }
+
+            let track = tracks.entry(update.id).or_insert_with(|| Track {
+                id: update.id,
+                properties: BTreeMap::new(),
+                points: Vec::new(),
+            });
+            for (key, value) in &update.properties {
+                if key != "T" {
+                    track.properties.insert(key.clone(), value.clone());
+                }
+            }
+
+            let position = world
+                .object(update.id)
+                .and_then(|object| world.absolute_position(object));
+            if let Some((longitude, latitude, altitude)) = position {
+                let longitude = normalize_longitude(longitude);
+                let point = TrackPoint {
+                    time: world.time,
+                    longitude,
+                    latitude,
+                    altitude,
+                };
+                let moved = track.points.last().is_none_or(|last| {
+                    (last.longitude, last.latitude, last.altitude)
+                        != (longitude, latitude, altitude)
+                });
+                if moved {
+                    track.points.push(point);
+                }
+            }
+        }
+
+        // Sorting is stable, so tracks of a reused ID stay in order
+        let mut tracks: Vec<Track> = ended
+            .into_iter()
+            .chain(tracks.into_values())
+            .filter(|track| !track.points.is_empty())
+            .collect();
+        tracks.sort_by_key(|track| track.id);
+
+        Self {
+            title: world.global.get("Title").cloned(),
+            reference_time: world
+                .global
+                .get("ReferenceTime")
+                .and_then(|value| parse_reference_time(value)),
+            tracks,
+        }
+    }
+
+    /// Keep the tracks whose type matches any of the filters, or every
+    /// track when there are none
+    pub fn filter_types(&mut self, filters: &[String]) {
+        if filters.is_empty() {
+            return;
+        }
+        self.tracks
+            .retain(|track| filters.iter().any(|filter| track.matches_type(filter)));
+    }
+
+    /// Simplify every track to a tolerance in meters, see [`Track::simplify`]
+    pub fn simplify(&mut self, tolerance: f64) {
+        for track in &mut self.tracks {
+            track.simplify(tolerance);
+        }
+    }
+
+    /// Absolute time of a point, counted from the Unix epoch when the
+    /// recording has no ReferenceTime
+    pub fn timestamp(&self, point: &TrackPoint) -> String {
+        let reference = self.reference_time.unwrap_or(DateTime::UNIX_EPOCH);
+        let time = reference + Duration::microseconds((point.time * 1e6).round() as i64);
+        time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+    }
+}
+
+/// Wrap a longitude into [-180, 180)
+fn normalize_longitude(longitude: f64) -> f64 {
+    (longitude + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Distance in meters from a point to the segment between two others
+///
+/// Points are projected onto a plane around `a`, which is accurate for the
+/// short distances between consecutive track points.
+fn distance_to_segment(point: &TrackPoint, a: &TrackPoint, b: &TrackPoint) -> f64 {
+    let scale = a.latitude.to_radians().cos();
+    let local = |p: &TrackPoint| {
+        [
+            normalize_longitude(p.longitude - a.longitude).to_radians() * EARTH_RADIUS * scale,
+            (p.latitude - a.latitude).to_radians() * EARTH_RADIUS,
+            p.altitude - a.altitude,
+        ]
+    };
+    let (p, b) = (local(point), local(b));
+
+    let length_squared: f64 = b.iter().map(|c| c * c).sum();
+    let t = if length_squared > 0.0 {
+        (p.iter().zip(&b).map(|(p, b)| p * b).sum::<f64>() / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    p.iter()
+        .zip(&b)
+        .map(|(p, b)| (p - b * t).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracks_filter_and_simplify() {
+        let acmi = "FileType=text/acmi/tacview\n\
+            0,ReferenceTime=2023-01-01T00:00:00Z\n\
+            0,ReferenceLongitude=10\n\
+            #0\n\
+            101,T=0|0|100,Name=Heli,Type=Air+Rotorcraft\n\
+            102,T=1|1|0,Type=Sea+Watercraft\n\
+            #1\n\
+            101,T=0.001|0|100\n\
+            102,T=1|1|0\n\
+            #2\n\
+            101,T=0.002|0.00001|100\n\
+            -101\n\
+            #3\n\
+            101,T=0.003|0.01|100,Name=Boat,Type=Sea+Watercraft\n";
+
+        let mut tracks = Tracks::from_acmi(acmi);
+        assert_eq!(tracks.tracks.len(), 3);
+        // The reused ID starts a new track instead of continuing the removed one
+        assert_eq!(tracks.tracks[1].id, 0x101);
+        assert_eq!(tracks.tracks[1].name(), Some("Boat"));
+        assert_eq!(tracks.tracks[1].points.len(), 1);
+        // The boat did not move, so its repeated position is not a new point
+        assert_eq!(tracks.tracks[2].points.len(), 1);
+
+        tracks.filter_types(&["air+ROTORCRAFT".to_string()]);
+        assert_eq!(tracks.tracks.len(), 1);
+        let heli = &mut tracks.tracks[0];
+        assert_eq!(heli.name(), Some("Heli"));
+        assert_eq!(heli.points.len(), 3);
+        assert_eq!(heli.points[0].longitude, 10.0);
+
+        // The second point is about half a meter off the line to the third
+        heli.simplify(5.0);
+        let times: Vec<f64> = heli.points.iter().map(|p| p.time).collect();
+        assert_eq!(times, [0.0, 2.0]);
+
+        assert_eq!(
+            tracks.timestamp(&tracks.tracks[0].points[1]),
+            "2023-01-01T00:00:02.000Z"
+        );
+    }
+
+    #[test]
+    fn test_tracks_cross_the_antimeridian() {
+        // The bridge's own header puts the reference point on longitude 180
+        let acmi = format!(
+            "{}#0\n101,T=-0.5|0|100\n#1\n101,T=0.5|1|200\n#2\n101,T=1|1|200\n",
+            crate::infra::FileAcmiRepository::generate_acmi_header()
+        );
+
+        let tracks = Tracks::from_acmi(&acmi);
+        let track = tracks.tracks.iter().find(|t| t.id == 0x101).unwrap();
+        let longitudes: Vec<f64> = track.points.iter().map(|p| p.longitude).collect();
+        assert_eq!(longitudes, [179.5, -179.5, -179.0]);
+
+        let segments = track.segments();
+        assert_eq!(segments.len(), 2);
+        let (end, start) = (segments[0][1], segments[1][0]);
+        assert_eq!((end.longitude, start.longitude), (180.0, -180.0));
+        assert_eq!((end.time, end.latitude, end.altitude), (0.5, 0.5, 150.0));
+        assert_eq!(start.time, end.time);
+        assert_eq!(segments[1].len(), 3);
+
+        // Simplifying measures across the antimeridian, not around the globe
+        let mut track = track.clone();
+        track.simplify(1e6);
+        assert_eq!(track.points.len(), 2);
+    }
+}
@@ -1,6 +1,8 @@
 use std::sync::Arc;
+use stormworks_tacview::acmi::read_recording;
 use stormworks_tacview::domain::{AcmiFileRepository, AcmiRepository};
 use stormworks_tacview::infra::FileAcmiRepository;
+use stormworks_tacview::AppConfig;
 
 #[tokio::test]
 async fn test_file_repository_write() {
@@ -48,3 +50,63 @@ async fn test_repository_step() {
     // Step should not panic
     repo.step();
 }
+
+#[tokio::test]
+async fn test_log_level_reload_toggles_verbose_logs() {
+    let state = stormworks_tacview::AppState::new();
+    assert!(!state.is_verbose());
+
+    let debug = AppConfig {
+        log_level: "debug".to_string(),
+        ..Default::default()
+    };
+    state.apply_config(&debug).await;
+    assert!(state.is_verbose());
+
+    state.apply_config(&Default::default()).await;
+    assert!(!state.is_verbose());
+}
+
+#[tokio::test]
+async fn test_file_repository_template_filters_and_retention() {
+    let output_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        output_dir: output_dir.path().to_path_buf(),
+        filename_template: "Flight-{timestamp}".to_string(),
+        exclude_types: vec!["Sea+Watercraft".to_string()],
+        ..AppConfig::default()
+    };
+    let repo = FileAcmiRepository::new_with_config(config.clone());
+
+    repo.start().unwrap();
+    let first = repo.current_file().unwrap();
+    assert!(first
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .starts_with("Flight-"));
+    repo.write("#1\n101,T=1|2|300,Type=Air+FixedWing\n102,T=1|2|0,Type=Sea+Watercraft\n")
+        .await
+        .unwrap();
+    repo.write("#2\n101,T=1|2|310\n102,T=1|2.1|0\n-102\n")
+        .await
+        .unwrap();
+    repo.stop().await.unwrap();
+
+    let acmi = read_recording(&first).unwrap();
+    assert!(acmi.ends_with("#1\n101,T=1|2|300,Type=Air+FixedWing\n#2\n101,T=1|2|310\n"));
+
+    // Only the newest two recordings are kept from the next one on
+    repo.set_config(AppConfig {
+        retention_max_files: 2,
+        ..config
+    });
+    let mut saved = vec![first];
+    for _ in 0..2 {
+        repo.start().unwrap();
+        saved.push(repo.current_file().unwrap());
+        repo.stop().await.unwrap();
+    }
+    assert!(!saved[0].exists());
+    assert!(saved[1].exists() && saved[2].exists());
+}
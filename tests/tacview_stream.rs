@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use stormworks_tacview::acmi::read_recording;
+use stormworks_tacview::domain::AcmiFileRepository;
+use stormworks_tacview::testing::{http_get, http_request, send_acmi, MockTacviewClient};
+use stormworks_tacview::{
+    AppConfig, AppState, FileAcmiRepository, HttpServer, ShutdownToken, TcpServer,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Wait until the given number of outputs are registered
+async fn wait_for_outputs(state: &AppState, count: usize) {
+    for _ in 0..100 {
+        if state.acmi_repositories.lock().await.len() == count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("expected {count} outputs");
+}
+
+/// Start the HTTP and TCP servers on free ports, returning their addresses
+async fn start_bridge(state: Arc<AppState>, shutdown: &ShutdownToken) -> (SocketAddr, SocketAddr) {
+    let any_port: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let http_server = HttpServer::bind(any_port, state.clone()).await.unwrap();
+    let tcp_server = TcpServer::bind(any_port, state).await.unwrap();
+    let addrs = (http_server.local_addr(), tcp_server.local_addr());
+
+    tokio::spawn(http_server.run(shutdown.clone()));
+    tokio::spawn(tcp_server.run(shutdown.clone()));
+    addrs
+}
+
+#[tokio::test]
+async fn test_frames_reach_every_tacview_client_in_order() {
+    let state = Arc::new(AppState::new());
+    let (http_addr, tcp_addr) = start_bridge(state.clone(), &ShutdownToken::new()).await;
+
+    let mut first = MockTacviewClient::connect(tcp_addr).await.unwrap();
+    let mut second = MockTacviewClient::connect(tcp_addr).await.unwrap();
+    assert_eq!(first.host(), "stormworks");
+    wait_for_outputs(&state, 2).await;
+
+    let frames = [
+        "#1\n101,T=1|2|300,Name=Alpha,Type=Air+FixedWing\n",
+        "#2\n101,T=1.001|2|310\n",
+        "#3\n101,T=1.002|2|320\n-101\n",
+    ];
+    for frame in frames {
+        assert_eq!(send_acmi(http_addr, frame).await.unwrap(), 200);
+    }
+
+    for client in [&mut first, &mut second] {
+        client.read_until("-101\n", TIMEOUT).await.unwrap();
+        assert!(client
+            .received()
+            .starts_with("FileType=text/acmi/tacview\nFileVersion=2.2\n"));
+        client.assert_in_order(&[
+            "40000003,T=0|0|2000|0|0,Type=Navaid+Static+Bullseye,Color=Blue,Coalition=Allies",
+            "#1",
+            "101,T=1|2|300,Name=Alpha,Type=Air+FixedWing",
+            "#2",
+            "101,T=1.001|2|310",
+            "#3",
+            "101,T=1.002|2|320",
+            "-101",
+        ]);
+        client.assert_time_ordered();
+    }
+}
+
+#[tokio::test]
+async fn test_late_and_disconnected_clients() {
+    let state = Arc::new(AppState::new());
+    let (http_addr, tcp_addr) = start_bridge(state.clone(), &ShutdownToken::new()).await;
+
+    let early = MockTacviewClient::connect(tcp_addr).await.unwrap();
+    wait_for_outputs(&state, 1).await;
+    send_acmi(http_addr, "#1\n101,T=1|2|300\n").await.unwrap();
+
+    // A client joining later only receives what is sent after it connected
+    let mut late = MockTacviewClient::connect(tcp_addr).await.unwrap();
+    wait_for_outputs(&state, 2).await;
+    send_acmi(http_addr, "#2\n101,T=1.001|2|310\n")
+        .await
+        .unwrap();
+    late.read_until("#2\n", TIMEOUT).await.unwrap();
+    assert!(!late.received().contains("#1\n"));
+
+    // The output of a client that went away is removed on the next write
+    drop(early);
+    for _ in 0..50 {
+        send_acmi(http_addr, "#3\n").await.unwrap();
+        if state.acmi_repositories.lock().await.len() == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    wait_for_outputs(&state, 1).await;
+
+    send_acmi(http_addr, "#4\n").await.unwrap();
+    late.read_until("#4\n", TIMEOUT).await.unwrap();
+    late.assert_time_ordered();
+}
+
+#[tokio::test]
+async fn test_servers_stop_on_shutdown() {
+    let state = Arc::new(AppState::new());
+    let any_port: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let http_server = HttpServer::bind(any_port, state.clone()).await.unwrap();
+    let tcp_server = TcpServer::bind(any_port, state).await.unwrap();
+    let (http_addr, tcp_addr) = (http_server.local_addr(), tcp_server.local_addr());
+    assert_ne!(http_addr.port(), 0);
+
+    let shutdown = ShutdownToken::new();
+    let http = tokio::spawn(http_server.run(shutdown.clone()));
+    let tcp = tokio::spawn(tcp_server.run(shutdown.clone()));
+    assert_eq!(http_get(http_addr, "/status").await.unwrap(), 200);
+
+    shutdown.cancel();
+    tokio::time::timeout(TIMEOUT, http)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    tokio::time::timeout(TIMEOUT, tcp)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+    // The listeners are closed, so the ports no longer accept connections
+    assert!(http_get(http_addr, "/status").await.is_err());
+    assert!(MockTacviewClient::connect(tcp_addr).await.is_err());
+}
+
+#[tokio::test]
+async fn test_shutdown_flushes_clients_and_recordings() {
+    let output_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        output_dir: output_dir.path().to_path_buf(),
+        // Tacview clients only get updates once a minute, so frames are still queued
+        output_rates: BTreeMap::from([("tacview".to_string(), 1.0 / 60.0)]),
+        ..AppConfig::default()
+    };
+    let state = Arc::new(AppState::new());
+    let file_repo = Arc::new(FileAcmiRepository::new_with_config(config.clone()));
+    state.file_repositories.lock().await.push(file_repo.clone());
+    state.apply_config(&config).await;
+    state.add_output(file_repo.clone(), None).await;
+
+    let shutdown = ShutdownToken::new();
+    let http_server = HttpServer::bind("127.0.0.1:0".parse().unwrap(), state.clone())
+        .await
+        .unwrap();
+    let tcp_server = TcpServer::bind("127.0.0.1:0".parse().unwrap(), state.clone())
+        .await
+        .unwrap();
+    let (http_addr, tcp_addr) = (http_server.local_addr(), tcp_server.local_addr());
+    let http = tokio::spawn(http_server.run(shutdown.clone()));
+    tokio::spawn(tcp_server.run(shutdown.clone()));
+
+    let mut client = MockTacviewClient::connect(tcp_addr).await.unwrap();
+    wait_for_outputs(&state, 2).await;
+    assert_eq!(http_get(http_addr, "/start").await.unwrap(), 200);
+    send_acmi(http_addr, "#1\n101,T=1|2|300,Name=Alpha\n")
+        .await
+        .unwrap();
+    send_acmi(http_addr, "#2\n101,T=1.001|2|310\n")
+        .await
+        .unwrap();
+    let recording = file_repo.current_file().unwrap();
+
+    shutdown.cancel();
+    tokio::time::timeout(TIMEOUT, http)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    state.shutdown(TIMEOUT).await.unwrap();
+
+    // The queued frames were sent before the connection was closed
+    client.read_to_end(TIMEOUT).await.unwrap();
+    client.assert_in_order(&["#2", "101,T=1.001|2|310,Name=Alpha"]);
+    assert_eq!(state.clients.count(), 0);
+
+    assert!(!file_repo.is_recording());
+    let acmi = read_recording(&recording).unwrap();
+    assert!(acmi.contains("#2\n101,T=1.001|2|310\n"));
+}
+
+#[tokio::test]
+async fn test_output_rate_changes_only_on_post() {
+    let state = Arc::new(AppState::new());
+    let (http_addr, tcp_addr) = start_bridge(state.clone(), &ShutdownToken::new()).await;
+    let _client = MockTacviewClient::connect(tcp_addr).await.unwrap();
+    wait_for_outputs(&state, 1).await;
+    let id = state.outputs.lock().unwrap()[0].id();
+    let path = format!("/admin/outputs/{id}/rate?rate=5");
+
+    assert_eq!(http_get(http_addr, &path).await.unwrap(), 405);
+    assert_eq!(state.output(id).unwrap().rate(), 0.0);
+
+    assert_eq!(http_request(http_addr, "POST", &path).await.unwrap(), 200);
+    assert_eq!(state.output(id).unwrap().rate(), 5.0);
+    assert_eq!(http_get(http_addr, "/admin/outputs").await.unwrap(), 200);
+}
+
+#[tokio::test]
+async fn test_shutdown_drops_clients_still_in_handshake() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let state = Arc::new(AppState::new());
+    let tcp_server = TcpServer::bind("127.0.0.1:0".parse().unwrap(), state.clone())
+        .await
+        .unwrap();
+    let tcp_addr = tcp_server.local_addr();
+    let shutdown = ShutdownToken::new();
+    let tcp = tokio::spawn(tcp_server.run(shutdown.clone()));
+
+    // The client reads the server handshake but has not answered yet
+    let mut stream = tokio::net::TcpStream::connect(tcp_addr).await.unwrap();
+    let mut buffer = [0u8; 1024];
+    let n = stream.read(&mut buffer).await.unwrap();
+    assert!(buffer[..n].starts_with(b"XtraLib.Stream.0\n"));
+
+    shutdown.cancel();
+    tokio::time::timeout(TIMEOUT, tcp)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let _ = stream
+        .write_all(b"XtraLib.Stream.0\nTacview.RealTimeTelemetry.0\nClient\n\0")
+        .await;
+
+    // The connection is dropped instead of becoming an output nothing closes
+    tokio::time::timeout(Duration::from_secs(1), state.shutdown(TIMEOUT))
+        .await
+        .unwrap()
+        .unwrap();
+    let mut rest = Vec::new();
+    tokio::time::timeout(TIMEOUT, stream.read_to_end(&mut rest))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(state.clients.count(), 0);
+    assert!(state.outputs.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_closing_a_stream_sends_everything_queued() {
+    use stormworks_tacview::domain::AcmiRepository;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let state = Arc::new(AppState::new());
+    let (http_addr, _) = start_bridge(state.clone(), &ShutdownToken::new()).await;
+    let mut stream = tokio::net::TcpStream::connect(http_addr).await.unwrap();
+    stream
+        .write_all(b"GET /stream.acmi HTTP/1.1\r\n\r\n")
+        .await
+        .unwrap();
+    wait_for_outputs(&state, 1).await;
+
+    // Queue several writes and close before the connection task sends any
+    let output = state.outputs.lock().unwrap()[0].clone();
+    for time in 1..=5 {
+        output.write(&format!("#{time}\n")).await.unwrap();
+    }
+    output.close().await.unwrap();
+
+    let mut response = Vec::new();
+    tokio::time::timeout(TIMEOUT, stream.read_to_end(&mut response))
+        .await
+        .unwrap()
+        .unwrap();
+    let response = String::from_utf8(response).unwrap();
+    for time in 1..=5 {
+        assert!(
+            response.contains(&format!("\r\n#{time}\n\r\n")),
+            "{response}"
+        );
+    }
+    assert!(response.ends_with("\r\n0\r\n\r\n"), "{response}");
+}
+
+#[tokio::test]
+async fn test_wall_clock_headers_share_the_session_start() {
+    use stormworks_tacview::config::TimeMode;
+
+    let output_dir = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        output_dir: output_dir.path().to_path_buf(),
+        time_mode: TimeMode::WallClock,
+        ..AppConfig::default()
+    };
+    let state = Arc::new(AppState::new());
+    let file_repo = Arc::new(FileAcmiRepository::new_with_config(config.clone()));
+    state.file_repositories.lock().await.push(file_repo.clone());
+    state.apply_config(&config).await;
+    state.add_output(file_repo.clone(), None).await;
+    let (http_addr, tcp_addr) = start_bridge(state.clone(), &ShutdownToken::new()).await;
+
+    let reference_time = |acmi: &str| {
+        let line = acmi
+            .lines()
+            .find_map(|line| line.strip_prefix("0,ReferenceTime="))
+            .unwrap();
+        chrono::DateTime::parse_from_rfc3339(line).unwrap()
+    };
+
+    // The live header has no time frame of its own
+    let mut client = MockTacviewClient::connect(tcp_addr).await.unwrap();
+    client.read_until("Bullseye", TIMEOUT).await.unwrap();
+    assert!(!client.received().contains("#0.001"));
+    let live = reference_time(client.received());
+
+    // A recording started later uses the same ReferenceTime as live clients
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    send_acmi(http_addr, "#1\n101,T=1|2|300\n").await.unwrap();
+    assert_eq!(http_get(http_addr, "/start").await.unwrap(), 200);
+    send_acmi(http_addr, "#2\n101,T=1|2|310\n").await.unwrap();
+    let recording = file_repo.current_file().unwrap();
+    assert_eq!(http_get(http_addr, "/stop").await.unwrap(), 200);
+
+    let file = reference_time(&read_recording(&recording).unwrap());
+    assert!((file - live).num_milliseconds().abs() <= 5, "{file} {live}");
+}